@@ -0,0 +1,29 @@
+CREATE TABLE IF NOT EXISTS clients (
+  id BIGSERIAL PRIMARY KEY NOT NULL,
+  display_name TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS client_api_keys (
+  id BIGSERIAL PRIMARY KEY NOT NULL,
+  valid_to TIMESTAMP WITH TIME ZONE NOT NULL,
+  key TEXT NOT NULL,
+  client_id BIGINT NOT NULL,
+  FOREIGN KEY(client_id) REFERENCES clients(id)
+);
+
+CREATE INDEX IF NOT EXISTS client_api_valid_to_index ON client_api_keys(client_id, valid_to);
+
+CREATE TABLE IF NOT EXISTS work_lists (
+  id BIGSERIAL PRIMARY KEY NOT NULL,
+  name TEXT NOT NULL,
+  client_id BIGINT NOT NULL,
+  FOREIGN KEY(client_id) REFERENCES clients(id)
+);
+
+CREATE TABLE IF NOT EXISTS todos (
+  id BIGSERIAL PRIMARY KEY NOT NULL,
+  content TEXT NOT NULL,
+  completed BOOLEAN NOT NULL,
+  work_list_id BIGINT NOT NULL,
+  FOREIGN KEY(work_list_id) REFERENCES work_lists(id)
+);
@@ -0,0 +1,6 @@
+ALTER TABLE client_api_keys ALTER COLUMN key DROP NOT NULL;
+ALTER TABLE client_api_keys ADD COLUMN prefix TEXT;
+ALTER TABLE client_api_keys ADD COLUMN salt TEXT;
+ALTER TABLE client_api_keys ADD COLUMN key_hash TEXT;
+
+CREATE INDEX client_api_prefix_index ON client_api_keys(prefix);
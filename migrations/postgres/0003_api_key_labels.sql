@@ -0,0 +1,2 @@
+ALTER TABLE client_api_keys ADD COLUMN label TEXT;
+ALTER TABLE client_api_keys ADD COLUMN last_used_at TIMESTAMP WITH TIME ZONE;
@@ -0,0 +1,5 @@
+ALTER TABLE work_lists ADD COLUMN created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW();
+
+CREATE INDEX IF NOT EXISTS work_lists_client_name_index ON work_lists(client_id, name, id);
+CREATE INDEX IF NOT EXISTS work_lists_client_created_at_index ON work_lists(client_id, created_at, id);
+CREATE INDEX IF NOT EXISTS todos_work_list_index ON todos(work_list_id, id);
@@ -0,0 +1,2 @@
+CREATE INDEX IF NOT EXISTS todos_search_index ON todos USING GIN (to_tsvector('simple', content));
+CREATE INDEX IF NOT EXISTS work_lists_search_index ON work_lists USING GIN (to_tsvector('simple', name));
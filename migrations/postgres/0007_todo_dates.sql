@@ -0,0 +1,12 @@
+ALTER TABLE todos ADD COLUMN due_date DATE;
+ALTER TABLE todos ADD COLUMN due_at TIMESTAMP WITH TIME ZONE;
+ALTER TABLE todos ADD COLUMN created_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW();
+ALTER TABLE todos ADD COLUMN updated_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW();
+ALTER TABLE todos ADD COLUMN completed_at TIMESTAMP WITH TIME ZONE;
+
+UPDATE todos SET completed_at = NOW() WHERE completed;
+
+CREATE INDEX IF NOT EXISTS todos_due_date_index ON todos(due_date);
+CREATE INDEX IF NOT EXISTS todos_due_at_index ON todos(due_at);
+
+ALTER TABLE clients ADD COLUMN time_zone TEXT NOT NULL DEFAULT 'UTC';
@@ -0,0 +1,7 @@
+ALTER TABLE todos ADD COLUMN priority INTEGER NOT NULL DEFAULT 1;
+ALTER TABLE todos ADD COLUMN position BIGINT NOT NULL DEFAULT 0;
+
+-- Existing todos keep their order by ID, spaced the same way new ones are.
+UPDATE todos SET position = id * 65536;
+
+CREATE INDEX IF NOT EXISTS todos_work_list_position_index ON todos(work_list_id, position);
@@ -0,0 +1,3 @@
+ALTER TABLE todos ADD COLUMN parent_id BIGINT REFERENCES todos(id) ON DELETE CASCADE;
+
+CREATE INDEX IF NOT EXISTS todos_parent_index ON todos(parent_id);
@@ -0,0 +1,5 @@
+ALTER TABLE todos ADD COLUMN recurrence TEXT;
+ALTER TABLE todos ADD COLUMN series_id BIGINT;
+ALTER TABLE todos ADD COLUMN occurrence INTEGER NOT NULL DEFAULT 1;
+
+CREATE INDEX IF NOT EXISTS todos_series_index ON todos(series_id);
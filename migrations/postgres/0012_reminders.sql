@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS reminders (
+  id BIGSERIAL PRIMARY KEY NOT NULL,
+  todo_id BIGINT NOT NULL,
+  remind_at TIMESTAMP WITH TIME ZONE NOT NULL,
+  sent_at TIMESTAMP WITH TIME ZONE,
+  failed_at TIMESTAMP WITH TIME ZONE,
+  attempts INTEGER NOT NULL DEFAULT 0,
+  locked_by TEXT,
+  locked_until TIMESTAMP WITH TIME ZONE,
+  FOREIGN KEY(todo_id) REFERENCES todos(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS reminders_todo_index ON reminders(todo_id);
+CREATE INDEX IF NOT EXISTS reminders_pending_index ON reminders(remind_at) WHERE sent_at IS NULL AND failed_at IS NULL;
@@ -0,0 +1,41 @@
+CREATE TABLE IF NOT EXISTS webhooks (
+  id BIGSERIAL PRIMARY KEY NOT NULL,
+  client_id BIGINT NOT NULL,
+  url TEXT NOT NULL,
+  secret TEXT NOT NULL,
+  events TEXT,
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+  FOREIGN KEY(client_id) REFERENCES clients(id)
+);
+
+CREATE INDEX IF NOT EXISTS webhooks_client_index ON webhooks(client_id);
+
+CREATE TABLE IF NOT EXISTS events (
+  id BIGSERIAL PRIMARY KEY NOT NULL,
+  client_id BIGINT NOT NULL,
+  kind TEXT NOT NULL,
+  data TEXT NOT NULL,
+  created_at TIMESTAMP WITH TIME ZONE NOT NULL,
+  FOREIGN KEY(client_id) REFERENCES clients(id)
+);
+
+CREATE INDEX IF NOT EXISTS events_client_index ON events(client_id, id);
+
+CREATE TABLE IF NOT EXISTS webhook_deliveries (
+  id BIGSERIAL PRIMARY KEY NOT NULL,
+  webhook_id BIGINT NOT NULL,
+  event_id BIGINT NOT NULL,
+  status TEXT NOT NULL DEFAULT 'pending',
+  attempts INTEGER NOT NULL DEFAULT 0,
+  next_attempt_at TIMESTAMP WITH TIME ZONE NOT NULL,
+  last_attempt_at TIMESTAMP WITH TIME ZONE,
+  response_status INTEGER,
+  error TEXT,
+  locked_by TEXT,
+  locked_until TIMESTAMP WITH TIME ZONE,
+  FOREIGN KEY(webhook_id) REFERENCES webhooks(id) ON DELETE CASCADE,
+  FOREIGN KEY(event_id) REFERENCES events(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS webhook_deliveries_webhook_index ON webhook_deliveries(webhook_id, id);
+CREATE INDEX IF NOT EXISTS webhook_deliveries_pending_index ON webhook_deliveries(next_attempt_at) WHERE status = 'pending';
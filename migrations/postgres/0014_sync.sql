@@ -0,0 +1,38 @@
+CREATE SEQUENCE IF NOT EXISTS change_versions;
+
+ALTER TABLE work_lists ADD COLUMN version BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE todos ADD COLUMN version BIGINT NOT NULL DEFAULT 0;
+
+-- Existing rows get distinct versions, so that they can be paged through.
+UPDATE work_lists SET version = nextval('change_versions');
+UPDATE todos SET version = nextval('change_versions');
+
+CREATE INDEX IF NOT EXISTS work_lists_version_index ON work_lists(client_id, version);
+CREATE INDEX IF NOT EXISTS todos_version_index ON todos(version);
+
+CREATE TABLE IF NOT EXISTS tombstones (
+  id BIGSERIAL PRIMARY KEY NOT NULL,
+  client_id BIGINT NOT NULL,
+  kind TEXT NOT NULL,
+  resource_id BIGINT NOT NULL,
+  work_list_id BIGINT,
+  version BIGINT NOT NULL DEFAULT nextval('change_versions'),
+  deleted_at TIMESTAMP WITH TIME ZONE NOT NULL,
+  FOREIGN KEY(client_id) REFERENCES clients(id)
+);
+
+CREATE INDEX IF NOT EXISTS tombstones_version_index ON tombstones(client_id, version);
+
+-- Every write takes the next version.
+CREATE OR REPLACE FUNCTION next_change_version() RETURNS trigger AS $$
+BEGIN
+  NEW.version := nextval('change_versions');
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS work_lists_version ON work_lists;
+CREATE TRIGGER work_lists_version BEFORE INSERT OR UPDATE ON work_lists FOR EACH ROW EXECUTE PROCEDURE next_change_version();
+
+DROP TRIGGER IF EXISTS todos_version ON todos;
+CREATE TRIGGER todos_version BEFORE INSERT OR UPDATE ON todos FOR EACH ROW EXECUTE PROCEDURE next_change_version();
@@ -0,0 +1,29 @@
+-- Versions and event IDs are used as watermarks - a client that has seen N skips everything up
+-- to N. Sequences hand out numbers before commit, so a transaction taking a lower number could
+-- commit after a higher one is already read. Numbers are therefore taken under a lock held until
+-- commit, which makes them visible in the order they are taken.
+CREATE OR REPLACE FUNCTION next_change_version() RETURNS trigger AS $$
+BEGIN
+  PERFORM pg_advisory_xact_lock(hashtext('change_versions'));
+  NEW.version := nextval('change_versions');
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+-- Tombstones took their version as a column default, which is evaluated before any trigger.
+ALTER TABLE tombstones ALTER COLUMN version DROP DEFAULT;
+
+DROP TRIGGER IF EXISTS tombstones_version ON tombstones;
+CREATE TRIGGER tombstones_version BEFORE INSERT ON tombstones FOR EACH ROW EXECUTE PROCEDURE next_change_version();
+
+-- Events share the lock, a transaction recording both would otherwise take two locks.
+CREATE OR REPLACE FUNCTION next_event_id() RETURNS trigger AS $$
+BEGIN
+  PERFORM pg_advisory_xact_lock(hashtext('change_versions'));
+  NEW.id := nextval(pg_get_serial_sequence('events', 'id'));
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+DROP TRIGGER IF EXISTS events_id ON events;
+CREATE TRIGGER events_id BEFORE INSERT ON events FOR EACH ROW EXECUTE PROCEDURE next_event_id();
@@ -0,0 +1,10 @@
+-- Creates pushed by sync clients, so that a batch sent again does not create items twice.
+CREATE TABLE IF NOT EXISTS applied_mutations (
+  client_id BIGINT NOT NULL,
+  mutation_id TEXT NOT NULL,
+  kind TEXT NOT NULL,
+  resource_id BIGINT NOT NULL,
+  applied_at TIMESTAMP WITH TIME ZONE NOT NULL,
+  PRIMARY KEY(client_id, mutation_id),
+  FOREIGN KEY(client_id) REFERENCES clients(id)
+);
@@ -0,0 +1,30 @@
+-- Versions and event IDs only have to become visible in order within a client, as clients sync
+-- and resume their own changes only. The lock taken for them is therefore held per client, so
+-- that writes of different clients don't wait for each other. The sequences stay shared -
+-- numbers taken by other clients in between only leave gaps, which watermarks don't mind.
+CREATE OR REPLACE FUNCTION lock_client_changes(client BIGINT) RETURNS void AS $$
+BEGIN
+  PERFORM pg_advisory_xact_lock(hashtext('change_versions'), mod(client, 2147483647)::INT);
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION next_change_version() RETURNS trigger AS $$
+BEGIN
+  IF TG_TABLE_NAME = 'todos' THEN
+    PERFORM lock_client_changes(client_id) FROM work_lists WHERE id = NEW.work_list_id;
+  ELSE
+    PERFORM lock_client_changes(NEW.client_id);
+  END IF;
+
+  NEW.version := nextval('change_versions');
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE OR REPLACE FUNCTION next_event_id() RETURNS trigger AS $$
+BEGIN
+  PERFORM lock_client_changes(NEW.client_id);
+  NEW.id := nextval(pg_get_serial_sequence('events', 'id'));
+  RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
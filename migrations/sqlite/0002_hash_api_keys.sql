@@ -0,0 +1,19 @@
+CREATE TABLE client_api_keys_new (
+  id INTEGER PRIMARY KEY NOT NULL,
+  valid_to INTEGER NOT NULL,
+  prefix TEXT,
+  salt TEXT,
+  key_hash TEXT,
+  key TEXT,
+  client_id INTEGER NOT NULL,
+  FOREIGN KEY(client_id) REFERENCES clients(id)
+);
+
+INSERT INTO client_api_keys_new (id, valid_to, key, client_id)
+  SELECT id, valid_to, key, client_id FROM client_api_keys;
+
+DROP TABLE client_api_keys;
+ALTER TABLE client_api_keys_new RENAME TO client_api_keys;
+
+CREATE INDEX client_api_valid_to_index ON client_api_keys(client_id, valid_to);
+CREATE INDEX client_api_prefix_index ON client_api_keys(prefix);
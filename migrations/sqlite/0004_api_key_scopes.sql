@@ -0,0 +1,2 @@
+ALTER TABLE client_api_keys ADD COLUMN scopes TEXT;
+ALTER TABLE client_api_keys ADD COLUMN work_list_ids TEXT;
@@ -0,0 +1,31 @@
+CREATE VIRTUAL TABLE IF NOT EXISTS todos_fts USING fts5(content, content='todos', content_rowid='id');
+CREATE VIRTUAL TABLE IF NOT EXISTS work_lists_fts USING fts5(name, content='work_lists', content_rowid='id');
+
+INSERT INTO todos_fts(todos_fts) VALUES ('rebuild');
+INSERT INTO work_lists_fts(work_lists_fts) VALUES ('rebuild');
+
+CREATE TRIGGER IF NOT EXISTS todos_fts_insert AFTER INSERT ON todos BEGIN
+  INSERT INTO todos_fts(rowid, content) VALUES (new.id, new.content);
+END;
+
+CREATE TRIGGER IF NOT EXISTS todos_fts_delete AFTER DELETE ON todos BEGIN
+  INSERT INTO todos_fts(todos_fts, rowid, content) VALUES ('delete', old.id, old.content);
+END;
+
+CREATE TRIGGER IF NOT EXISTS todos_fts_update AFTER UPDATE OF content ON todos BEGIN
+  INSERT INTO todos_fts(todos_fts, rowid, content) VALUES ('delete', old.id, old.content);
+  INSERT INTO todos_fts(rowid, content) VALUES (new.id, new.content);
+END;
+
+CREATE TRIGGER IF NOT EXISTS work_lists_fts_insert AFTER INSERT ON work_lists BEGIN
+  INSERT INTO work_lists_fts(rowid, name) VALUES (new.id, new.name);
+END;
+
+CREATE TRIGGER IF NOT EXISTS work_lists_fts_delete AFTER DELETE ON work_lists BEGIN
+  INSERT INTO work_lists_fts(work_lists_fts, rowid, name) VALUES ('delete', old.id, old.name);
+END;
+
+CREATE TRIGGER IF NOT EXISTS work_lists_fts_update AFTER UPDATE OF name ON work_lists BEGIN
+  INSERT INTO work_lists_fts(work_lists_fts, rowid, name) VALUES ('delete', old.id, old.name);
+  INSERT INTO work_lists_fts(rowid, name) VALUES (new.id, new.name);
+END;
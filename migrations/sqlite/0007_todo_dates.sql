@@ -0,0 +1,13 @@
+ALTER TABLE todos ADD COLUMN due_date TEXT;
+ALTER TABLE todos ADD COLUMN due_at INTEGER;
+ALTER TABLE todos ADD COLUMN created_at INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE todos ADD COLUMN updated_at INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE todos ADD COLUMN completed_at INTEGER;
+
+UPDATE todos SET created_at = strftime('%s','now'), updated_at = strftime('%s','now');
+UPDATE todos SET completed_at = strftime('%s','now') WHERE completed;
+
+CREATE INDEX IF NOT EXISTS todos_due_date_index ON todos(due_date);
+CREATE INDEX IF NOT EXISTS todos_due_at_index ON todos(due_at);
+
+ALTER TABLE clients ADD COLUMN time_zone TEXT NOT NULL DEFAULT 'UTC';
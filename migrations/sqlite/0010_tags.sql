@@ -0,0 +1,19 @@
+CREATE TABLE IF NOT EXISTS tags (
+  id INTEGER PRIMARY KEY NOT NULL,
+  client_id INTEGER NOT NULL,
+  name TEXT NOT NULL,
+  color TEXT,
+  FOREIGN KEY(client_id) REFERENCES clients(id)
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS tags_client_name_index ON tags(client_id, name);
+
+CREATE TABLE IF NOT EXISTS todo_tags (
+  todo_id INTEGER NOT NULL,
+  tag_id INTEGER NOT NULL,
+  PRIMARY KEY(todo_id, tag_id),
+  FOREIGN KEY(todo_id) REFERENCES todos(id) ON DELETE CASCADE,
+  FOREIGN KEY(tag_id) REFERENCES tags(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS todo_tags_tag_index ON todo_tags(tag_id);
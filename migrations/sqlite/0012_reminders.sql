@@ -0,0 +1,14 @@
+CREATE TABLE IF NOT EXISTS reminders (
+  id INTEGER PRIMARY KEY NOT NULL,
+  todo_id INTEGER NOT NULL,
+  remind_at INTEGER NOT NULL,
+  sent_at INTEGER,
+  failed_at INTEGER,
+  attempts INTEGER NOT NULL DEFAULT 0,
+  locked_by TEXT,
+  locked_until INTEGER,
+  FOREIGN KEY(todo_id) REFERENCES todos(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS reminders_todo_index ON reminders(todo_id);
+CREATE INDEX IF NOT EXISTS reminders_pending_index ON reminders(remind_at) WHERE sent_at IS NULL AND failed_at IS NULL;
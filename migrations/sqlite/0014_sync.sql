@@ -0,0 +1,54 @@
+CREATE TABLE IF NOT EXISTS change_version (
+  version INTEGER NOT NULL
+);
+
+ALTER TABLE work_lists ADD COLUMN version INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE todos ADD COLUMN version INTEGER NOT NULL DEFAULT 0;
+
+-- Existing rows get distinct versions, so that they can be paged through.
+UPDATE work_lists SET version = id;
+UPDATE todos SET version = id + (SELECT COALESCE(MAX(id), 0) FROM work_lists);
+INSERT INTO change_version (version) SELECT MAX(version) FROM (SELECT COALESCE(MAX(version), 0) AS version FROM work_lists UNION ALL SELECT COALESCE(MAX(version), 0) FROM todos);
+
+CREATE INDEX IF NOT EXISTS work_lists_version_index ON work_lists(client_id, version);
+CREATE INDEX IF NOT EXISTS todos_version_index ON todos(version);
+
+CREATE TABLE IF NOT EXISTS tombstones (
+  id INTEGER PRIMARY KEY NOT NULL,
+  client_id INTEGER NOT NULL,
+  kind TEXT NOT NULL,
+  resource_id INTEGER NOT NULL,
+  work_list_id INTEGER,
+  version INTEGER NOT NULL DEFAULT 0,
+  deleted_at INTEGER NOT NULL,
+  FOREIGN KEY(client_id) REFERENCES clients(id)
+);
+
+CREATE INDEX IF NOT EXISTS tombstones_version_index ON tombstones(client_id, version);
+
+-- Every write takes the next version. Updates made by the triggers themselves change the version,
+-- so they don't fire the update triggers again.
+CREATE TRIGGER IF NOT EXISTS work_lists_version_insert AFTER INSERT ON work_lists BEGIN
+  UPDATE change_version SET version = version + 1;
+  UPDATE work_lists SET version = (SELECT version FROM change_version) WHERE id = new.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS work_lists_version_update AFTER UPDATE ON work_lists WHEN new.version = old.version BEGIN
+  UPDATE change_version SET version = version + 1;
+  UPDATE work_lists SET version = (SELECT version FROM change_version) WHERE id = new.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS todos_version_insert AFTER INSERT ON todos BEGIN
+  UPDATE change_version SET version = version + 1;
+  UPDATE todos SET version = (SELECT version FROM change_version) WHERE id = new.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS todos_version_update AFTER UPDATE ON todos WHEN new.version = old.version BEGIN
+  UPDATE change_version SET version = version + 1;
+  UPDATE todos SET version = (SELECT version FROM change_version) WHERE id = new.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS tombstones_version_insert AFTER INSERT ON tombstones BEGIN
+  UPDATE change_version SET version = version + 1;
+  UPDATE tombstones SET version = (SELECT version FROM change_version) WHERE id = new.id;
+END;
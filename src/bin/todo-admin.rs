@@ -0,0 +1,179 @@
+use anyhow::{anyhow, Result};
+use chrono::{Duration, Utc};
+use chrono_tz::Tz;
+use dotenv::dotenv;
+use std::env;
+use std::str::FromStr;
+
+use todo_list_api::database::{self, ClientRepository, Storage};
+use todo_list_api::model::{ApiKey, Permissions, Scope};
+
+const USAGE: &str = "Usage: todo-admin <command>
+
+Commands:
+    clients create <display_name>
+    clients list
+    clients rename <client_id> <display_name>
+    clients timezone <client_id> <time_zone>
+    clients delete <client_id>
+    keys issue <client_id> <valid_days> [scope,...]
+    keys list <client_id>
+    keys revoke <key_id>
+    keys rotate <key_id> <valid_days>";
+
+fn arg<T: FromStr>(args: &[String], idx: usize, name: &str) -> Result<T> {
+    args.get(idx)
+        .ok_or(anyhow!("Missing argument <{}>\n\n{}", name, USAGE))?
+        .parse()
+        .map_err(|_| anyhow!("Invalid value for <{}>", name))
+}
+
+fn permissions(scopes: Option<&String>) -> Result<Permissions> {
+    let scopes = match scopes {
+        Some(scopes) => scopes
+            .split(',')
+            .map(|scope| Scope::parse(scope).ok_or(anyhow!("Unknown scope {}", scope)))
+            .collect::<Result<Vec<Scope>>>()
+            .map(Some)?,
+        None => None,
+    };
+
+    Ok(Permissions {
+        scopes,
+        work_list_ids: None,
+    })
+}
+
+async fn issue_key(
+    storage: &Storage,
+    client_id: i64,
+    valid_days: i64,
+    permissions: &Permissions,
+) -> Result<()> {
+    let valid_to = Utc::now() + Duration::days(valid_days);
+    let issued = ApiKey::issue(client_id, None, valid_to, permissions, storage).await?;
+
+    println!(
+        "Issued key {} valid to {}",
+        issued.api_key.id, issued.api_key.valid_to
+    );
+    println!("Token (it will not be shown again): {}", issued.token);
+    Ok(())
+}
+
+async fn clients(storage: &Storage, args: &[String]) -> Result<()> {
+    match args.get(0).map(String::as_str) {
+        Some("create") => {
+            let display_name: String = arg(args, 1, "display_name")?;
+            let client = storage.create_client(&display_name).await?;
+            println!("Created client {} ({})", client.id(), client.display_name());
+        }
+        Some("list") => {
+            for client in storage.list_clients().await? {
+                println!(
+                    "{}\t{}\t{}",
+                    client.id(),
+                    client.display_name(),
+                    client.time_zone().name()
+                );
+            }
+        }
+        Some("rename") => {
+            let id: i64 = arg(args, 1, "client_id")?;
+            let display_name: String = arg(args, 2, "display_name")?;
+
+            if !storage.rename_client(id, &display_name).await? {
+                return Err(anyhow!("Client {} not found", id));
+            }
+            println!("Renamed client {} to {}", id, display_name);
+        }
+        Some("timezone") => {
+            let id: i64 = arg(args, 1, "client_id")?;
+            let time_zone: Tz = arg(args, 2, "time_zone")?;
+
+            if !storage.set_client_time_zone(id, time_zone.name()).await? {
+                return Err(anyhow!("Client {} not found", id));
+            }
+            println!("Set time zone of client {} to {}", id, time_zone.name());
+        }
+        Some("delete") => {
+            let id: i64 = arg(args, 1, "client_id")?;
+
+            if !storage.delete_client(id).await? {
+                return Err(anyhow!("Client {} not found", id));
+            }
+            println!("Deleted client {}", id);
+        }
+        _ => return Err(anyhow!(USAGE)),
+    }
+
+    Ok(())
+}
+
+async fn keys(storage: &Storage, args: &[String]) -> Result<()> {
+    match args.get(0).map(String::as_str) {
+        Some("issue") => {
+            let client_id: i64 = arg(args, 1, "client_id")?;
+            let valid_days: i64 = arg(args, 2, "valid_days")?;
+            issue_key(storage, client_id, valid_days, &permissions(args.get(3))?).await?;
+        }
+        Some("list") => {
+            let client_id: i64 = arg(args, 1, "client_id")?;
+
+            for api_key in storage.list_api_keys(client_id).await? {
+                println!(
+                    "{}\t{}\t{}\t{}\t{}",
+                    api_key.id,
+                    api_key.prefix,
+                    api_key.label.as_deref().unwrap_or(""),
+                    api_key.valid_to,
+                    if api_key.is_expired() {
+                        "expired"
+                    } else {
+                        "active"
+                    }
+                );
+            }
+        }
+        Some("revoke") => {
+            let id: i64 = arg(args, 1, "key_id")?;
+
+            if !storage.revoke_api_key(id).await? {
+                return Err(anyhow!("Key {} not found", id));
+            }
+            println!("Revoked key {}", id);
+        }
+        Some("rotate") => {
+            let id: i64 = arg(args, 1, "key_id")?;
+            let valid_days: i64 = arg(args, 2, "valid_days")?;
+            let api_key = storage
+                .find_api_key(id)
+                .await?
+                .ok_or(anyhow!("Key {} not found", id))?;
+
+            issue_key(storage, api_key.client_id, valid_days, &api_key.permissions).await?;
+            storage.revoke_api_key(id).await?;
+            println!("Revoked key {}", id);
+        }
+        _ => return Err(anyhow!(USAGE)),
+    }
+
+    Ok(())
+}
+
+#[actix_rt::main]
+async fn main() -> Result<()> {
+    dotenv().ok();
+    env_logger::init();
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    let db_cfg = database::PoolConfig::from_env()?;
+    let storage = database::connect(db_cfg).await?;
+    database::migrations::prepare(&storage).await?;
+
+    match args.get(0).map(String::as_str) {
+        Some("clients") => clients(&storage, &args[1..]).await,
+        Some("keys") => keys(&storage, &args[1..]).await,
+        _ => Err(anyhow!(USAGE)),
+    }
+}
@@ -0,0 +1,51 @@
+use actix_web::{delete, get, post, web, Result};
+use serde_json::json;
+
+use crate::database::Storage;
+use crate::error::WebError;
+use crate::forms::api_key::CreateApiKey;
+use crate::model::{ApiKey, IssuedApiKey, Scope};
+use crate::web_app::{Client, ValidatedJson};
+
+#[get("")]
+async fn list(
+    client: Client,
+    storage: web::Data<Storage>,
+) -> Result<web::Json<Vec<ApiKey>>, WebError> {
+    client.require(Scope::ApiKeysRead)?;
+
+    ApiKey::list(&client, &storage)
+        .await
+        .map(|collection| web::Json(collection))
+}
+
+#[post("")]
+async fn create(
+    form: ValidatedJson<CreateApiKey>,
+    client: Client,
+    storage: web::Data<Storage>,
+) -> Result<web::Json<IssuedApiKey>, WebError> {
+    client.require(Scope::ApiKeysWrite)?;
+
+    ApiKey::create(form.into_inner(), &client, &storage)
+        .await
+        .map(|issued| web::Json(issued))
+}
+
+#[delete("{id}")]
+async fn delete(
+    id: web::Path<i64>,
+    client: Client,
+    storage: web::Data<Storage>,
+) -> Result<web::Json<serde_json::Value>, WebError> {
+    client.require(Scope::ApiKeysWrite)?;
+
+    let api_key = ApiKey::find(id.into_inner(), &client, &storage).await?;
+    api_key.revoke(&client, &storage).await?;
+
+    Ok(web::Json(json!({ "status": "ok" })))
+}
+
+pub fn init(config: &mut web::ServiceConfig) {
+    config.service(list).service(create).service(delete);
+}
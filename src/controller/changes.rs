@@ -0,0 +1,133 @@
+use actix::{Actor, ActorContext, AsyncContext, StreamHandler};
+use actix_web::{get, web, FromRequest, HttpRequest, HttpResponse, Result};
+use actix_web_actors::ws;
+use serde::Deserialize;
+use serde_json::json;
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::RecvError;
+
+use crate::database::Storage;
+use crate::error::WebError;
+use crate::model::{ChangeFeed, Event, Permissions, Scope};
+use crate::web_app::Client;
+
+/// How often the server pings the other side.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+/// Streams not answering pings for so long are closed.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(45);
+
+#[derive(Deserialize)]
+struct StreamQuery {
+    token: Option<String>,
+}
+
+/// Pushes changes of the client visible to its API key as JSON text messages.
+struct ChangeStream {
+    client_id: i64,
+    permissions: Permissions,
+    changes: ChangeFeed,
+    heartbeat: Instant,
+}
+
+impl ChangeStream {
+    fn ping(&self, ctx: &mut ws::WebsocketContext<Self>) {
+        ctx.run_interval(HEARTBEAT_INTERVAL, |stream, ctx| {
+            if stream.heartbeat.elapsed() > CLIENT_TIMEOUT {
+                ctx.stop();
+            } else {
+                ctx.ping(b"");
+            }
+        });
+    }
+}
+
+impl Actor for ChangeStream {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.add_stream(self.changes.subscribe());
+        self.ping(ctx);
+    }
+}
+
+impl StreamHandler<Result<Event, RecvError>> for ChangeStream {
+    fn handle(&mut self, event: Result<Event, RecvError>, ctx: &mut Self::Context) {
+        match event {
+            Ok(event)
+                if event.client_id == self.client_id && event.is_visible(&self.permissions) =>
+            {
+                ctx.text(serde_json::to_string(&event).unwrap_or_default());
+            }
+            Ok(_) => {}
+            // Changes were missed, so the other side has to fetch its data again.
+            Err(RecvError::Lagged(skipped)) => {
+                ctx.text(json!({ "event": "stream.lagged", "skipped": skipped }).to_string());
+            }
+            Err(RecvError::Closed) => ctx.stop(),
+        }
+    }
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for ChangeStream {
+    fn handle(&mut self, message: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        match message {
+            Ok(ws::Message::Ping(message)) => {
+                self.heartbeat = Instant::now();
+                ctx.pong(&message);
+            }
+            Ok(ws::Message::Pong(_)) => self.heartbeat = Instant::now(),
+            Ok(ws::Message::Close(reason)) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            // The stream is one-way, anything else sent by the other side is ignored.
+            Ok(_) => {}
+            Err(_) => ctx.stop(),
+        }
+    }
+}
+
+/// Streams accept the token in the query as well, since browsers can't set headers of WebSocket
+/// and `EventSource` requests. They need read access to todos or work lists.
+pub(super) async fn stream_client(
+    req: &HttpRequest,
+    token: Option<String>,
+    storage: &Storage,
+) -> Result<Client, WebError> {
+    let client = match token {
+        Some(token) => Client::authorize(&token, storage).await?,
+        None => Client::extract(req).await?,
+    };
+
+    if client.permissions().allows(Scope::WorkListsRead)
+        || client.permissions().allows(Scope::TodosRead)
+    {
+        Ok(client)
+    } else {
+        Err(WebError::Forbidden)
+    }
+}
+
+#[get("")]
+async fn stream(
+    req: HttpRequest,
+    payload: web::Payload,
+    query: web::Query<StreamQuery>,
+    storage: web::Data<Storage>,
+    changes: web::Data<ChangeFeed>,
+) -> Result<HttpResponse> {
+    let client = stream_client(&req, query.into_inner().token, &storage).await?;
+
+    let stream = ChangeStream {
+        client_id: client.id(),
+        permissions: client.permissions().clone(),
+        changes: changes.get_ref().clone(),
+        heartbeat: Instant::now(),
+    };
+
+    ws::start(stream, &req, payload)
+}
+
+pub fn init(cfg: &mut web::ServiceConfig) {
+    cfg.service(stream);
+}
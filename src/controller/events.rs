@@ -0,0 +1,171 @@
+use actix_web::web::Bytes;
+use actix_web::{get, web, HttpRequest, HttpResponse, Result};
+use futures::stream;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::VecDeque;
+use std::time::Duration;
+use tokio::sync::broadcast::{self, RecvError};
+use tokio::time::timeout;
+
+use super::changes::stream_client;
+use crate::database::{EventRepository, Storage};
+use crate::error::WebError;
+use crate::model::{ChangeFeed, Event, Permissions};
+
+/// Events read from storage at once when catching up.
+const BATCH_SIZE: u32 = 100;
+/// Idle streams get a comment this often, so proxies don't close them. Events recorded by other
+/// instances are picked up then as well.
+const KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+#[derive(Deserialize)]
+struct EventsQuery {
+    token: Option<String>,
+    /// For clients which can't send the `Last-Event-ID` header.
+    last_event_id: Option<i64>,
+}
+
+/// Sends events of the client recorded after `last_id` in order. The feed only wakes the
+/// stream up, events themselves are always read from storage, so none is skipped.
+struct EventStream {
+    client_id: i64,
+    permissions: Permissions,
+    storage: Storage,
+    changes: broadcast::Receiver<Event>,
+    last_id: i64,
+    pending: VecDeque<Event>,
+    /// Set when the last read filled a whole batch.
+    behind: bool,
+    /// `Last-Event-ID` the stream could not resume from, announced before anything else.
+    reset: Option<i64>,
+}
+
+impl EventStream {
+    fn message(event: &Event) -> Bytes {
+        Bytes::from(format!(
+            "id: {}\nevent: {}\ndata: {}\n\n",
+            event.id,
+            event.kind.as_str(),
+            serde_json::to_string(event).unwrap_or_default()
+        ))
+    }
+
+    /// Tells the client that events after `last_event_id` were pruned, so it has to fetch its
+    /// data again. Carries no ID, so that reconnecting streams resume from the last event sent.
+    fn reset_message(last_event_id: i64) -> Bytes {
+        Bytes::from(format!(
+            "event: stream.reset\ndata: {}\n\n",
+            json!({ "event": "stream.reset", "last_event_id": last_event_id })
+        ))
+    }
+
+    async fn catch_up(&mut self) -> Result<(), WebError> {
+        let events = self
+            .storage
+            .list_events(self.client_id, self.last_id, BATCH_SIZE)
+            .await?;
+
+        self.behind = events.len() == BATCH_SIZE as usize;
+        self.pending.extend(events);
+        Ok(())
+    }
+
+    /// Next chunk of the response. Ends the stream when the feed closes or storage fails.
+    async fn next(mut self) -> Option<(Result<Bytes, WebError>, Self)> {
+        if let Some(last_event_id) = self.reset.take() {
+            return Some((Ok(Self::reset_message(last_event_id)), self));
+        }
+
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                self.last_id = event.id;
+
+                if event.is_visible(&self.permissions) {
+                    return Some((Ok(Self::message(&event)), self));
+                }
+
+                continue;
+            }
+
+            let idle = if self.behind {
+                false
+            } else {
+                match timeout(KEEP_ALIVE, self.changes.recv()).await {
+                    Ok(Ok(event)) if event.client_id != self.client_id => continue,
+                    Ok(Ok(event)) if event.id <= self.last_id => continue,
+                    // Missed events are read from storage all the same.
+                    Ok(Ok(_)) | Ok(Err(RecvError::Lagged(_))) => false,
+                    Ok(Err(RecvError::Closed)) => return None,
+                    Err(_) => true,
+                }
+            };
+
+            if let Err(err) = self.catch_up().await {
+                return Some((Err(err), self));
+            }
+
+            if idle && self.pending.is_empty() {
+                return Some((Ok(Bytes::from_static(b": keep-alive\n\n")), self));
+            }
+        }
+    }
+}
+
+#[get("")]
+async fn stream(
+    req: HttpRequest,
+    query: web::Query<EventsQuery>,
+    storage: web::Data<Storage>,
+    changes: web::Data<ChangeFeed>,
+) -> Result<HttpResponse> {
+    let query = query.into_inner();
+    let client = stream_client(&req, query.token, &storage).await?;
+
+    // Subscribing first, so nothing recorded in the meantime is missed.
+    let changes = changes.subscribe();
+
+    let last_event_id = req
+        .headers()
+        .get("Last-Event-ID")
+        .and_then(|header| header.to_str().ok())
+        .and_then(|id| id.trim().parse().ok())
+        .or(query.last_event_id);
+
+    // Streams resuming from a pruned event can't be caught up. They are reset and continue as
+    // new streams, which start with events recorded after they were opened.
+    let reset = match last_event_id {
+        Some(id) => match storage.first_event_id(client.id()).await? {
+            Some(first_id) if id >= first_id => None,
+            _ => Some(id),
+        },
+        None => None,
+    };
+    let last_id = match last_event_id {
+        Some(id) if reset.is_none() => id,
+        _ => storage.last_event_id(client.id()).await?.unwrap_or(0),
+    };
+
+    let events = EventStream {
+        client_id: client.id(),
+        permissions: client.permissions().clone(),
+        storage: storage.get_ref().clone(),
+        changes,
+        last_id,
+        pending: VecDeque::new(),
+        // Reconnecting streams catch up right away.
+        behind: last_event_id.is_some() && reset.is_none(),
+        reset,
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .header("Cache-Control", "no-cache")
+        // Keeps nginx from buffering the response.
+        .header("X-Accel-Buffering", "no")
+        .streaming(stream::unfold(events, EventStream::next)))
+}
+
+pub fn init(cfg: &mut web::ServiceConfig) {
+    cfg.service(stream);
+}
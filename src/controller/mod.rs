@@ -1,2 +1,9 @@
+pub mod api_keys;
+pub mod changes;
+pub mod events;
+pub mod search;
+pub mod sync;
+pub mod tags;
 pub mod todos;
+pub mod webhooks;
 pub mod work_lists;
@@ -0,0 +1,22 @@
+use actix_web::{get, web, Result};
+
+use crate::database::Storage;
+use crate::error::WebError;
+use crate::forms::search::Search;
+use crate::model::SearchResult;
+use crate::web_app::{Client, ValidatedQuery};
+
+#[get("")]
+async fn search(
+    query: ValidatedQuery<Search>,
+    client: Client,
+    storage: web::Data<Storage>,
+) -> Result<web::Json<Vec<SearchResult>>, WebError> {
+    SearchResult::search(query.into_inner(), &client, &storage)
+        .await
+        .map(|results| web::Json(results))
+}
+
+pub fn init(config: &mut web::ServiceConfig) {
+    config.service(search);
+}
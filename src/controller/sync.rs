@@ -0,0 +1,34 @@
+use actix_web::{get, post, web, Result};
+
+use crate::database::Storage;
+use crate::error::WebError;
+use crate::forms::sync::{PushMutations, SyncQuery};
+use crate::model::{MutationResult, SyncChanges};
+use crate::web_app::{Client, ValidatedJson, ValidatedQuery};
+
+#[get("")]
+async fn pull(
+    query: ValidatedQuery<SyncQuery>,
+    client: Client,
+    storage: web::Data<Storage>,
+) -> Result<web::Json<SyncChanges>, WebError> {
+    SyncChanges::since(query.into_inner(), &client, &storage)
+        .await
+        .map(|changes| web::Json(changes))
+}
+
+/// Results are in the order of the mutations.
+#[post("")]
+async fn push(
+    form: ValidatedJson<PushMutations>,
+    client: Client,
+    storage: web::Data<Storage>,
+) -> Result<web::Json<Vec<MutationResult>>, WebError> {
+    MutationResult::push(form.into_inner(), &client, &storage)
+        .await
+        .map(|results| web::Json(results))
+}
+
+pub fn init(config: &mut web::ServiceConfig) {
+    config.service(pull).service(push);
+}
@@ -0,0 +1,79 @@
+use actix_web::{delete, get, patch, post, web, Result};
+use serde_json::json;
+
+use crate::database::Storage;
+use crate::error::WebError;
+use crate::forms::tag::{CreateTag, UpdateTag};
+use crate::model::{Scope, Tag};
+use crate::web_app::{Client, ValidatedJson};
+
+/// Tags are shared by all work lists, so keys restricted to some of them can't change tags.
+fn require_tags_write(client: &Client) -> Result<(), WebError> {
+    client.require(Scope::TodosWrite)?;
+
+    if client.permissions().is_restricted_to_work_lists() {
+        Err(WebError::Forbidden)
+    } else {
+        Ok(())
+    }
+}
+
+#[get("")]
+async fn list(
+    client: Client,
+    storage: web::Data<Storage>,
+) -> Result<web::Json<Vec<Tag>>, WebError> {
+    client.require(Scope::TodosRead)?;
+
+    Tag::list(&client, &storage)
+        .await
+        .map(|tags| web::Json(tags))
+}
+
+#[post("")]
+async fn create(
+    form: ValidatedJson<CreateTag>,
+    client: Client,
+    storage: web::Data<Storage>,
+) -> Result<web::Json<Tag>, WebError> {
+    require_tags_write(&client)?;
+
+    Tag::create(form.into_inner(), &client, &storage)
+        .await
+        .map(|tag| web::Json(tag))
+}
+
+#[patch("/{tagid}")]
+async fn update(
+    id: web::Path<i64>,
+    form: ValidatedJson<UpdateTag>,
+    client: Client,
+    storage: web::Data<Storage>,
+) -> Result<web::Json<Tag>, WebError> {
+    require_tags_write(&client)?;
+
+    let mut tag = Tag::find(id.into_inner(), &client, &storage).await?;
+    tag.update(form.into_inner(), &client, &storage).await?;
+    Ok(web::Json(tag))
+}
+
+#[delete("/{tagid}")]
+async fn delete(
+    id: web::Path<i64>,
+    client: Client,
+    storage: web::Data<Storage>,
+) -> Result<web::Json<serde_json::Value>, WebError> {
+    require_tags_write(&client)?;
+
+    let tag = Tag::find(id.into_inner(), &client, &storage).await?;
+    tag.delete(&client, &storage).await?;
+
+    Ok(web::Json(json!({ "status": "ok" })))
+}
+
+pub fn init(cfg: &mut web::ServiceConfig) {
+    cfg.service(list)
+        .service(create)
+        .service(update)
+        .service(delete);
+}
@@ -1,20 +1,54 @@
-use actix_web::{delete, patch, post, web, Result};
+use actix_web::{delete, get, patch, post, put, web, Result};
 use serde_json::json;
 
-use crate::database::Pool;
+use crate::database::Storage;
 use crate::error::WebError;
-use crate::forms::todo::{CreateTodo, UpdateTodo};
-use crate::model::Todo;
+use crate::forms::reminder::CreateReminder;
+use crate::forms::todo::{CopyTodos, CreateTodo, ListTodos, MoveTodo, SetRecurrence, UpdateTodo};
+use crate::model::{Page, Reminder, Scope, Todo};
 
-use crate::web_app::{Client, ValidatedJson};
+use crate::web_app::{Client, IfMatch, ValidatedJson, ValidatedQuery};
+
+#[get("")]
+async fn list(
+    query: ValidatedQuery<ListTodos>,
+    client: Client,
+    storage: web::Data<Storage>,
+) -> Result<Page<Todo>, WebError> {
+    let query = query.into_inner();
+
+    match query.work_list_id {
+        Some(work_list_id) => client.require_work_list(Scope::TodosRead, work_list_id)?,
+        None => client.require(Scope::TodosRead)?,
+    }
+
+    Todo::list(query, &client, &storage).await
+}
+
+#[get("/{todoid}")]
+async fn fetch(
+    id: web::Path<i64>,
+    client: Client,
+    storage: web::Data<Storage>,
+) -> Result<Todo, WebError> {
+    client.require(Scope::TodosRead)?;
+
+    let todo = Todo::find(id.into_inner(), &client, &storage).await?;
+    client.require_work_list(Scope::TodosRead, todo.work_list_id)?;
+
+    Ok(todo)
+}
 
 #[post("")]
 async fn create(
     form: ValidatedJson<CreateTodo>,
     client: Client,
-    pool: web::Data<Pool>,
+    storage: web::Data<Storage>,
 ) -> Result<web::Json<Todo>, WebError> {
-    Todo::create(form.into_inner(), &client, &pool)
+    let form = form.into_inner();
+    client.require_work_list(Scope::TodosWrite, form.work_list_id)?;
+
+    Todo::create(form, None, &client, &storage)
         .await
         .map(|todo| web::Json(todo))
 }
@@ -23,26 +57,166 @@ async fn create(
 async fn update(
     id: web::Path<i64>,
     form: ValidatedJson<UpdateTodo>,
+    if_match: IfMatch,
     client: Client,
-    pool: web::Data<Pool>,
+    storage: web::Data<Storage>,
+) -> Result<Todo, WebError> {
+    let form = form.into_inner();
+    let mut todo = Todo::find(id.into_inner(), &client, &storage).await?;
+    client.require_work_list(Scope::TodosWrite, todo.work_list_id)?;
+
+    if let Some(work_list_id) = form.work_list_id {
+        client.require_work_list(Scope::TodosWrite, work_list_id)?;
+    }
+
+    let expected_version = if_match.check(&todo.etag(), todo.version)?;
+    todo.update(form, expected_version, &client, &storage)
+        .await?;
+    Ok(todo)
+}
+
+#[post("/{todoid}/move")]
+async fn reorder(
+    id: web::Path<i64>,
+    form: ValidatedJson<MoveTodo>,
+    client: Client,
+    storage: web::Data<Storage>,
+) -> Result<web::Json<Todo>, WebError> {
+    let mut todo = Todo::find(id.into_inner(), &client, &storage).await?;
+    client.require_work_list(Scope::TodosWrite, todo.work_list_id)?;
+    todo.reorder(form.into_inner(), &client, &storage).await?;
+    Ok(web::Json(todo))
+}
+
+#[put("/{todoid}/recurrence")]
+async fn set_recurrence(
+    id: web::Path<i64>,
+    form: ValidatedJson<SetRecurrence>,
+    client: Client,
+    storage: web::Data<Storage>,
 ) -> Result<web::Json<Todo>, WebError> {
-    let mut todo = Todo::find(id.into_inner(), &client, &pool).await?;
-    todo.update(form.into_inner(), &client, &pool).await?;
+    let mut todo = Todo::find(id.into_inner(), &client, &storage).await?;
+    client.require_work_list(Scope::TodosWrite, todo.work_list_id)?;
+    todo.set_recurrence(form.into_inner(), &client, &storage)
+        .await?;
     Ok(web::Json(todo))
 }
 
+#[delete("/{todoid}/recurrence")]
+async fn stop_recurrence(
+    id: web::Path<i64>,
+    client: Client,
+    storage: web::Data<Storage>,
+) -> Result<web::Json<Todo>, WebError> {
+    let mut todo = Todo::find(id.into_inner(), &client, &storage).await?;
+    client.require_work_list(Scope::TodosWrite, todo.work_list_id)?;
+    todo.stop_recurrence(&client, &storage).await?;
+    Ok(web::Json(todo))
+}
+
+#[post("/{todoid}/skip")]
+async fn skip(
+    id: web::Path<i64>,
+    client: Client,
+    storage: web::Data<Storage>,
+) -> Result<web::Json<Todo>, WebError> {
+    let mut todo = Todo::find(id.into_inner(), &client, &storage).await?;
+    client.require_work_list(Scope::TodosWrite, todo.work_list_id)?;
+    todo.skip_occurrence(&client, &storage).await?;
+    Ok(web::Json(todo))
+}
+
+#[get("/{todoid}/reminders")]
+async fn list_reminders(
+    id: web::Path<i64>,
+    client: Client,
+    storage: web::Data<Storage>,
+) -> Result<web::Json<Vec<Reminder>>, WebError> {
+    client.require(Scope::TodosRead)?;
+
+    let todo = Todo::find(id.into_inner(), &client, &storage).await?;
+    client.require_work_list(Scope::TodosRead, todo.work_list_id)?;
+
+    Reminder::list(&todo, &storage)
+        .await
+        .map(|reminders| web::Json(reminders))
+}
+
+#[post("/{todoid}/reminders")]
+async fn create_reminder(
+    id: web::Path<i64>,
+    form: ValidatedJson<CreateReminder>,
+    client: Client,
+    storage: web::Data<Storage>,
+) -> Result<web::Json<Reminder>, WebError> {
+    let todo = Todo::find(id.into_inner(), &client, &storage).await?;
+    client.require_work_list(Scope::TodosWrite, todo.work_list_id)?;
+
+    Reminder::create(&todo, form.into_inner(), &storage)
+        .await
+        .map(|reminder| web::Json(reminder))
+}
+
+#[delete("/{todoid}/reminders/{reminderid}")]
+async fn delete_reminder(
+    path: web::Path<(i64, i64)>,
+    client: Client,
+    storage: web::Data<Storage>,
+) -> Result<web::Json<serde_json::Value>, WebError> {
+    let (todo_id, reminder_id) = path.into_inner();
+    let todo = Todo::find(todo_id, &client, &storage).await?;
+    client.require_work_list(Scope::TodosWrite, todo.work_list_id)?;
+    Reminder::delete(&todo, reminder_id, &storage).await?;
+
+    Ok(web::Json(json!({ "status": "ok" })))
+}
+
+#[post("/copy")]
+async fn copy(
+    form: ValidatedJson<CopyTodos>,
+    client: Client,
+    storage: web::Data<Storage>,
+) -> Result<web::Json<Vec<Todo>>, WebError> {
+    let form = form.into_inner();
+    client.require_work_list(Scope::TodosWrite, form.work_list_id)?;
+
+    let todos = Todo::find_many(&form.todo_ids, &client, &storage).await?;
+    for todo in todos.iter() {
+        client.require_work_list(Scope::TodosRead, todo.work_list_id)?;
+    }
+
+    Todo::copy_to(&todos, form.work_list_id, &client, &storage)
+        .await
+        .map(|copies| web::Json(copies))
+}
+
 #[delete("/{todoid}")]
 async fn delete(
     id: web::Path<i64>,
+    if_match: IfMatch,
     client: Client,
-    pool: web::Data<Pool>,
+    storage: web::Data<Storage>,
 ) -> Result<web::Json<serde_json::Value>, WebError> {
-    let todo = Todo::find(id.into_inner(), &client, &pool).await?;
-    todo.delete(&client, &pool).await?;
+    let todo = Todo::find(id.into_inner(), &client, &storage).await?;
+    client.require_work_list(Scope::TodosWrite, todo.work_list_id)?;
+    let expected_version = if_match.check(&todo.etag(), todo.version)?;
+    todo.delete(expected_version, &client, &storage).await?;
 
     Ok(web::Json(json!({ "status": "ok" })))
 }
 
 pub fn init(cfg: &mut web::ServiceConfig) {
-    cfg.service(create).service(update).service(delete);
+    cfg.service(list)
+        .service(fetch)
+        .service(create)
+        .service(update)
+        .service(reorder)
+        .service(copy)
+        .service(set_recurrence)
+        .service(stop_recurrence)
+        .service(skip)
+        .service(list_reminders)
+        .service(create_reminder)
+        .service(delete_reminder)
+        .service(delete);
 }
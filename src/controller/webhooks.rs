@@ -0,0 +1,110 @@
+use actix_web::{delete, get, patch, post, web, Result};
+use serde_json::json;
+
+use crate::database::Storage;
+use crate::error::WebError;
+use crate::forms::webhook::{CreateWebhook, ListDeliveries, UpdateWebhook};
+use crate::model::{Scope, Webhook, WebhookDelivery};
+use crate::web_app::{Client, ValidatedJson, ValidatedQuery};
+
+/// Webhooks receive events of all work lists, so keys restricted to some of them can't use them.
+fn require_webhooks(client: &Client, scope: Scope) -> Result<(), WebError> {
+    client.require(scope)?;
+
+    if client.permissions().is_restricted_to_work_lists() {
+        Err(WebError::Forbidden)
+    } else {
+        Ok(())
+    }
+}
+
+#[get("")]
+async fn list(
+    client: Client,
+    storage: web::Data<Storage>,
+) -> Result<web::Json<Vec<Webhook>>, WebError> {
+    require_webhooks(&client, Scope::WebhooksRead)?;
+
+    Webhook::list(&client, &storage)
+        .await
+        .map(|webhooks| web::Json(webhooks))
+}
+
+#[get("/{webhookid}")]
+async fn fetch(
+    id: web::Path<i64>,
+    client: Client,
+    storage: web::Data<Storage>,
+) -> Result<web::Json<Webhook>, WebError> {
+    require_webhooks(&client, Scope::WebhooksRead)?;
+
+    Webhook::find(id.into_inner(), &client, &storage)
+        .await
+        .map(|webhook| web::Json(webhook))
+}
+
+#[post("")]
+async fn create(
+    form: ValidatedJson<CreateWebhook>,
+    client: Client,
+    storage: web::Data<Storage>,
+) -> Result<web::Json<Webhook>, WebError> {
+    require_webhooks(&client, Scope::WebhooksWrite)?;
+
+    Webhook::create(form.into_inner(), &client, &storage)
+        .await
+        .map(|webhook| web::Json(webhook))
+}
+
+#[patch("/{webhookid}")]
+async fn update(
+    id: web::Path<i64>,
+    form: ValidatedJson<UpdateWebhook>,
+    client: Client,
+    storage: web::Data<Storage>,
+) -> Result<web::Json<Webhook>, WebError> {
+    require_webhooks(&client, Scope::WebhooksWrite)?;
+
+    let mut webhook = Webhook::find(id.into_inner(), &client, &storage).await?;
+    webhook.update(form.into_inner(), &client, &storage).await?;
+    Ok(web::Json(webhook))
+}
+
+#[delete("/{webhookid}")]
+async fn delete(
+    id: web::Path<i64>,
+    client: Client,
+    storage: web::Data<Storage>,
+) -> Result<web::Json<serde_json::Value>, WebError> {
+    require_webhooks(&client, Scope::WebhooksWrite)?;
+
+    let webhook = Webhook::find(id.into_inner(), &client, &storage).await?;
+    webhook.delete(&client, &storage).await?;
+
+    Ok(web::Json(json!({ "status": "ok" })))
+}
+
+#[get("/{webhookid}/deliveries")]
+async fn deliveries(
+    id: web::Path<i64>,
+    query: ValidatedQuery<ListDeliveries>,
+    client: Client,
+    storage: web::Data<Storage>,
+) -> Result<web::Json<Vec<WebhookDelivery>>, WebError> {
+    require_webhooks(&client, Scope::WebhooksRead)?;
+
+    let webhook = Webhook::find(id.into_inner(), &client, &storage).await?;
+    webhook
+        .deliveries(query.into_inner(), &storage)
+        .await
+        .map(|deliveries| web::Json(deliveries))
+}
+
+pub fn init(cfg: &mut web::ServiceConfig) {
+    cfg.service(list)
+        .service(fetch)
+        .service(create)
+        .service(update)
+        .service(delete)
+        .service(deliveries);
+}
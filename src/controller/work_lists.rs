@@ -1,32 +1,38 @@
 use actix_web::{delete, get, patch, post, web, Result};
 use serde_json::json;
 
-use crate::database::Pool;
+use crate::database::Storage;
 use crate::error::WebError;
-use crate::forms::work_list::{CreateWorkList, UpdateWorkList};
-use crate::model::WorkList;
-use crate::web_app::{Client, ValidatedJson};
+use crate::forms::work_list::{CreateWorkList, ListWorkLists, UpdateWorkList};
+use crate::model::{Page, Scope, WorkList};
+use crate::web_app::{Client, IfMatch, ValidatedJson, ValidatedQuery};
 
 #[get("{id}")]
 async fn fetch(
     id: web::Path<i64>,
     client: Client,
-    pool: web::Data<Pool>,
-) -> Result<web::Json<WorkList>, WebError> {
+    storage: web::Data<Storage>,
+) -> Result<WorkList, WebError> {
     let id = id.into_inner();
+    client.require_work_list(Scope::WorkListsRead, id)?;
 
-    WorkList::find(id, &client, &pool)
-        .await
-        .map(|work_list| web::Json(work_list))
+    WorkList::find(id, &client, &storage).await
 }
 
 #[post("")]
 async fn create(
     client: Client,
     form: ValidatedJson<CreateWorkList>,
-    pool: web::Data<Pool>,
+    storage: web::Data<Storage>,
 ) -> Result<web::Json<WorkList>, WebError> {
-    WorkList::create(form.into_inner(), &client, &pool)
+    client.require(Scope::WorkListsWrite)?;
+
+    // Keys restricted to particular work lists would not be able to access a new one.
+    if client.permissions().is_restricted_to_work_lists() {
+        return Err(WebError::Forbidden);
+    }
+
+    WorkList::create(form.into_inner(), None, &client, &storage)
         .await
         .map(|work_list| web::Json(work_list))
 }
@@ -34,11 +40,18 @@ async fn create(
 #[delete("{id}")]
 async fn delete(
     id: web::Path<i64>,
+    if_match: IfMatch,
     client: Client,
-    pool: web::Data<Pool>,
+    storage: web::Data<Storage>,
 ) -> Result<web::Json<serde_json::Value>, WebError> {
-    let work_list = WorkList::find(id.into_inner(), &client, &pool).await?;
-    work_list.delete(&client, &pool).await?;
+    let id = id.into_inner();
+    client.require_work_list(Scope::WorkListsWrite, id)?;
+
+    let work_list = WorkList::find(id, &client, &storage).await?;
+    let expected_version = if_match.check(&work_list.etag(), work_list.version())?;
+    work_list
+        .delete(expected_version, &client, &storage)
+        .await?;
     Ok(web::Json(json!({ "status": "ok" })))
 }
 
@@ -46,20 +59,33 @@ async fn delete(
 async fn update(
     id: web::Path<i64>,
     form: ValidatedJson<UpdateWorkList>,
+    if_match: IfMatch,
     client: Client,
-    pool: web::Data<Pool>,
-) -> Result<web::Json<WorkList>, WebError> {
-    let mut work_list = WorkList::find(id.into_inner(), &client, &pool).await?;
-    work_list.update(&client, form.into_inner(), &pool).await?;
+    storage: web::Data<Storage>,
+) -> Result<WorkList, WebError> {
+    let id = id.into_inner();
+    client.require_work_list(Scope::WorkListsWrite, id)?;
+
+    let mut work_list = WorkList::find(id, &client, &storage).await?;
+    // The composite tag covers todos of the list, while the write can only make sure that the
+    // list itself stays as it was.
+    let expected_version = if_match.check(&work_list.etag(), work_list.version())?;
+    work_list
+        .update(&client, form.into_inner(), expected_version, &storage)
+        .await?;
 
-    Ok(web::Json(work_list))
+    Ok(work_list)
 }
 
 #[get("")]
-async fn list(client: Client, pool: web::Data<Pool>) -> Result<web::Json<Vec<WorkList>>, WebError> {
-    WorkList::list(&client, &pool)
-        .await
-        .map(|collection| web::Json(collection))
+async fn list(
+    query: ValidatedQuery<ListWorkLists>,
+    client: Client,
+    storage: web::Data<Storage>,
+) -> Result<Page<WorkList>, WebError> {
+    client.require(Scope::WorkListsRead)?;
+
+    WorkList::list(query.into_inner(), &client, &storage).await
 }
 
 pub fn init(config: &mut web::ServiceConfig) {
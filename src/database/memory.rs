@@ -0,0 +1,1951 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, Utc};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::RwLock;
+
+use super::is_modified;
+use super::migrations::{Migration, Migrator};
+use super::repository::{
+    ClientRepository, DescribeEvent, DescribeTodo, EventRepository, ReminderRepository,
+    SearchRepository, SyncRepository, TagRepository, TodoRepository, WebhookRepository,
+    WorkListRepository,
+};
+use crate::error::{Resource, WebError};
+use crate::model::{
+    ApiKey, DeliveryAttempt, DeliveryStatus, Due, DueCondition, Event, EventKind, KeyHash,
+    NewEvent, NewTodo, PendingDelivery, Permissions, Reminder, SearchKind, SearchQuery,
+    SearchResult, Tag, Todo, TodoCascade, TodoChanges, TodoQuery, TodoSort, Tombstone,
+    TombstoneKind, Webhook, WebhookDelivery, WorkList, WorkListPosition, WorkListQuery,
+    WorkListSort, MATCH_END, MATCH_START, POSITION_STEP,
+};
+use crate::web_app::Client;
+
+struct ApiKeyEntry {
+    id: i64,
+    prefix: String,
+    label: Option<String>,
+    last_used_at: Option<DateTime<Utc>>,
+    permissions: Permissions,
+    key_hash: KeyHash,
+    valid_to: DateTime<Utc>,
+    client_id: i64,
+}
+
+impl ApiKeyEntry {
+    fn to_api_key(&self) -> ApiKey {
+        ApiKey::new(
+            self.id,
+            self.client_id,
+            self.prefix.clone(),
+            self.label.clone(),
+            self.valid_to,
+            self.last_used_at,
+            self.permissions.clone(),
+        )
+    }
+}
+
+struct ClientEntry {
+    display_name: String,
+    time_zone: String,
+}
+
+impl ClientEntry {
+    fn new(display_name: &str) -> Self {
+        Self {
+            display_name: display_name.to_owned(),
+            time_zone: "UTC".to_string(),
+        }
+    }
+
+    fn to_client(&self, id: i64) -> Client {
+        Client::new(id, self.display_name.clone(), &self.time_zone)
+    }
+}
+
+struct WorkListEntry {
+    name: String,
+    client_id: i64,
+    created_at: DateTime<Utc>,
+    version: i64,
+}
+
+impl WorkListEntry {
+    fn to_work_list(&self, id: i64) -> WorkList {
+        WorkList::new(id, self.name.clone(), self.created_at, self.version, vec![])
+    }
+
+    /// Orders work lists the way SQL backends do for given sort, with ID breaking ties.
+    fn compare(
+        sort: WorkListSort,
+        (id, name, created_at): (i64, &str, DateTime<Utc>),
+        (other_id, other_name, other_created_at): (i64, &str, DateTime<Utc>),
+    ) -> Ordering {
+        use WorkListSort::*;
+
+        let ordering = match sort {
+            IdAsc | IdDesc => Ordering::Equal,
+            NameAsc | NameDesc => name.cmp(other_name),
+            CreatedAtAsc | CreatedAtDesc => created_at.cmp(&other_created_at),
+        }
+        .then(id.cmp(&other_id));
+
+        if sort.is_descending() {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    }
+
+    fn sort_key(&self, id: i64) -> (i64, &str, DateTime<Utc>) {
+        (id, &self.name, self.created_at)
+    }
+}
+
+impl WorkListPosition {
+    fn sort_key(&self) -> (i64, &str, DateTime<Utc>) {
+        (self.id, &self.name, self.created_at)
+    }
+}
+
+struct TagEntry {
+    client_id: i64,
+    name: String,
+    color: Option<String>,
+}
+
+impl TagEntry {
+    fn to_tag(&self, id: i64, todos_count: Option<i64>) -> Tag {
+        Tag {
+            id,
+            name: self.name.clone(),
+            color: self.color.clone(),
+            todos_count,
+        }
+    }
+}
+
+struct ReminderEntry {
+    reminder: Reminder,
+    locked_by: Option<String>,
+    locked_until: Option<DateTime<Utc>>,
+}
+
+impl ReminderEntry {
+    fn is_leasable(&self, now: DateTime<Utc>) -> bool {
+        self.reminder.sent_at.is_none()
+            && self.reminder.failed_at.is_none()
+            && self.reminder.remind_at <= now
+            && self.locked_until.map(|until| until <= now).unwrap_or(true)
+    }
+
+    fn is_leased_by(&self, owner: &str) -> bool {
+        self.locked_by.as_deref() == Some(owner)
+    }
+}
+
+struct WebhookEntry {
+    client_id: i64,
+    webhook: Webhook,
+}
+
+struct DeliveryEntry {
+    webhook_id: i64,
+    event_id: i64,
+    status: DeliveryStatus,
+    attempts: u32,
+    response_status: Option<u16>,
+    error: Option<String>,
+    last_attempt_at: Option<DateTime<Utc>>,
+    next_attempt_at: DateTime<Utc>,
+    locked_by: Option<String>,
+    locked_until: Option<DateTime<Utc>>,
+}
+
+impl DeliveryEntry {
+    fn to_delivery(&self, id: i64, event: &Event) -> WebhookDelivery {
+        WebhookDelivery {
+            id,
+            event_id: self.event_id,
+            event: event.kind,
+            status: self.status,
+            attempts: self.attempts,
+            response_status: self.response_status,
+            error: self.error.clone(),
+            created_at: event.created_at,
+            last_attempt_at: self.last_attempt_at,
+            next_attempt_at: Some(self.next_attempt_at)
+                .filter(|_| self.status == DeliveryStatus::Pending),
+        }
+    }
+
+    fn is_leasable(&self, now: DateTime<Utc>) -> bool {
+        self.status == DeliveryStatus::Pending
+            && self.next_attempt_at <= now
+            && self.locked_until.map(|until| until <= now).unwrap_or(true)
+    }
+}
+
+#[derive(Default)]
+struct State {
+    last_id: i64,
+    clients: BTreeMap<i64, ClientEntry>,
+    api_keys: Vec<ApiKeyEntry>,
+    work_lists: BTreeMap<i64, WorkListEntry>,
+    todos: BTreeMap<i64, Todo>,
+    tags: BTreeMap<i64, TagEntry>,
+    /// Pairs of todo ID and tag ID.
+    todo_tags: BTreeSet<(i64, i64)>,
+    reminders: BTreeMap<i64, ReminderEntry>,
+    webhooks: BTreeMap<i64, WebhookEntry>,
+    events: BTreeMap<i64, Event>,
+    deliveries: BTreeMap<i64, DeliveryEntry>,
+    last_version: i64,
+    /// Tombstones by their version, with ID of the client.
+    tombstones: BTreeMap<i64, (i64, Tombstone)>,
+    /// Items created by sync mutations, by ID of the client and of the mutation.
+    applied_mutations: BTreeMap<(i64, String), (TombstoneKind, i64)>,
+}
+
+impl State {
+    fn next_id(&mut self) -> i64 {
+        self.last_id += 1;
+        self.last_id
+    }
+
+    /// Change versions are shared by todos, work lists and tombstones, like in SQL backends.
+    fn next_version(&mut self) -> i64 {
+        self.last_version += 1;
+        self.last_version
+    }
+
+    /// Returns version of the tombstone.
+    fn bury(
+        &mut self,
+        client_id: i64,
+        kind: TombstoneKind,
+        id: i64,
+        work_list_id: Option<i64>,
+    ) -> i64 {
+        let version = self.next_version();
+        let tombstone = Tombstone {
+            kind,
+            id,
+            work_list_id,
+            version,
+            deleted_at: Utc::now(),
+        };
+
+        self.tombstones.insert(version, (client_id, tombstone));
+        version
+    }
+
+    /// Records an event and queues its delivery to webhooks of the client subscribed to it.
+    fn record_event(&mut self, event: NewEvent) -> Event {
+        let recorded = Event {
+            id: self.next_id(),
+            client_id: event.client_id,
+            kind: event.kind,
+            created_at: event.created_at,
+            data: event.data,
+        };
+
+        let webhook_ids: Vec<i64> = self
+            .webhooks
+            .iter()
+            .filter(|(_, entry)| {
+                entry.client_id == recorded.client_id && entry.webhook.accepts(recorded.kind)
+            })
+            .map(|(id, _)| *id)
+            .collect();
+
+        for webhook_id in webhook_ids {
+            let id = self.next_id();
+            self.deliveries.insert(
+                id,
+                DeliveryEntry {
+                    webhook_id,
+                    event_id: recorded.id,
+                    status: DeliveryStatus::Pending,
+                    attempts: 0,
+                    response_status: None,
+                    error: None,
+                    last_attempt_at: None,
+                    next_attempt_at: recorded.created_at,
+                    locked_by: None,
+                    locked_until: None,
+                },
+            );
+        }
+
+        self.events.insert(recorded.id, recorded.clone());
+        recorded
+    }
+
+    fn tagged_todos(&self, tag_id: i64) -> Vec<i64> {
+        self.todo_tags
+            .iter()
+            .filter(|(_, id)| *id == tag_id)
+            .map(|(todo_id, _)| *todo_id)
+            .collect()
+    }
+
+    /// Takes a new version for each of given todos, which have changed.
+    fn touch_todos(&mut self, ids: &[i64]) {
+        for id in ids {
+            let version = self.next_version();
+
+            if let Some(todo) = self.todos.get_mut(id) {
+                todo.version = version;
+            }
+        }
+    }
+
+    /// IDs of subtasks of a todo at any depth, ordered by position.
+    fn subtree(&self, id: i64) -> Vec<i64> {
+        let mut parents = vec![id];
+        let mut subtasks: Vec<&Todo> = vec![];
+
+        while let Some(parent_id) = parents.pop() {
+            for todo in self.todos.values() {
+                if todo.parent_id == Some(parent_id) {
+                    parents.push(todo.id);
+                    subtasks.push(todo);
+                }
+            }
+        }
+
+        subtasks.sort_by_key(|todo| (todo.position, todo.id));
+        subtasks.into_iter().map(|todo| todo.id).collect()
+    }
+
+    /// Position right after the last todo of a work list.
+    fn end_position(&self, work_list_id: i64) -> i64 {
+        self.todos
+            .values()
+            .filter(|todo| todo.work_list_id == work_list_id)
+            .map(|todo| todo.position + POSITION_STEP)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Inserts a todo with its tag assignments at the end of its work list.
+    fn insert_todo(&mut self, todo: &NewTodo) -> Todo {
+        let id = self.next_id();
+        let version = self.next_version();
+        let position = self.end_position(todo.work_list_id);
+
+        self.todo_tags
+            .extend(todo.tags.iter().map(|tag| (id, tag.id)));
+
+        let todo = todo.clone().into_todo(id, version, position);
+        self.todos.insert(id, todo.clone());
+        todo
+    }
+
+    /// Changes an existing todo and replaces its tags. Returns the todo as stored.
+    fn update_todo(&mut self, id: i64, changes: &TodoChanges) -> Todo {
+        let version = self.next_version();
+        // Moved todos go to the end of the target list.
+        let position = changes
+            .work_list_id
+            .map(|work_list_id| self.end_position(work_list_id));
+
+        let todo = self.todos.get_mut(&id).unwrap();
+        todo.apply(changes);
+        todo.version = version;
+        todo.position = position.unwrap_or(todo.position);
+        let todo = todo.clone();
+
+        if let Some(tags) = changes.tags.as_ref() {
+            self.todo_tags.retain(|(todo_id, _)| *todo_id != id);
+            self.todo_tags.extend(tags.iter().map(|tag| (id, tag.id)));
+        }
+
+        todo
+    }
+
+    fn owns_work_list(&self, id: i64, client_id: i64) -> bool {
+        self.work_lists
+            .get(&id)
+            .map(|entry| entry.client_id == client_id)
+            .unwrap_or(false)
+    }
+
+    fn has_tag(&self, todo_id: i64, name: &str) -> bool {
+        self.todo_tags
+            .range((todo_id, i64::MIN)..=(todo_id, i64::MAX))
+            .any(|(_, tag_id)| {
+                self.tags
+                    .get(tag_id)
+                    .map(|entry| entry.name == name)
+                    .unwrap_or(false)
+            })
+    }
+
+    fn tag_name_taken(&self, client_id: i64, name: &str, except_id: Option<i64>) -> bool {
+        self.tags.iter().any(|(id, entry)| {
+            entry.client_id == client_id && entry.name == name && Some(*id) != except_id
+        })
+    }
+}
+
+/// Highlights words of `text` starting with any of the terms. Returns highlighted text and the
+/// number of matching words, or `None` unless every term matches.
+fn highlight(text: &str, terms: &[String]) -> Option<(String, usize)> {
+    let mut highlighted = String::with_capacity(text.len());
+    let mut matched_terms = vec![false; terms.len()];
+    let mut matches = 0;
+    let mut rest = text;
+
+    while !rest.is_empty() {
+        let word_len = rest
+            .find(|c: char| !c.is_alphanumeric())
+            .unwrap_or(rest.len());
+
+        if word_len == 0 {
+            let separator = rest.chars().next().unwrap();
+            highlighted.push(separator);
+            rest = &rest[separator.len_utf8()..];
+            continue;
+        }
+
+        let word = &rest[..word_len];
+        let lowercase = word.to_lowercase();
+        let mut is_match = false;
+
+        for (idx, term) in terms.iter().enumerate() {
+            if lowercase.starts_with(term.as_str()) {
+                matched_terms[idx] = true;
+                is_match = true;
+            }
+        }
+
+        if is_match {
+            matches += 1;
+            highlighted.push_str(MATCH_START);
+            highlighted.push_str(word);
+            highlighted.push_str(MATCH_END);
+        } else {
+            highlighted.push_str(word);
+        }
+        rest = &rest[word_len..];
+    }
+
+    if matched_terms.iter().all(|matched| *matched) {
+        Some((highlighted, matches))
+    } else {
+        None
+    }
+}
+
+fn is_due(todo: &Todo, condition: DueCondition) -> bool {
+    let date = todo.due_at.and_then(Due::date);
+    let time = todo.due_at.and_then(Due::time);
+
+    match condition {
+        DueCondition::Within {
+            date: day,
+            from,
+            to,
+        } => date == Some(day) || time.map(|time| time >= from && time < to).unwrap_or(false),
+        DueCondition::Before {
+            date: day,
+            time: now,
+        } => {
+            !todo.completed
+                && (date.map(|date| date < day).unwrap_or(false)
+                    || time.map(|time| time < now).unwrap_or(false))
+        }
+    }
+}
+
+/// Keeps everything in process memory. Data is lost on restart - use it for tests and demos only.
+#[derive(Default)]
+pub struct MemoryRepository {
+    state: RwLock<State>,
+}
+
+impl MemoryRepository {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a client with an API key valid for `validity`, returning the client ID.
+    pub fn seed_client(&self, display_name: &str, key: &str, validity: Duration) -> i64 {
+        let mut state = self.state.write().unwrap();
+        let id = state.next_id();
+        let key_id = state.next_id();
+
+        state.clients.insert(id, ClientEntry::new(display_name));
+        state.api_keys.push(ApiKeyEntry {
+            id: key_id,
+            prefix: ApiKey::token_prefix(key).to_owned(),
+            label: None,
+            last_used_at: None,
+            permissions: Permissions::full(),
+            key_hash: KeyHash::new(key),
+            valid_to: Utc::now() + validity,
+            client_id: id,
+        });
+
+        id
+    }
+}
+
+/// There is no schema to maintain in memory.
+#[async_trait]
+impl Migrator for MemoryRepository {
+    fn migrations(&self) -> &'static [Migration] {
+        &[]
+    }
+
+    async fn applied_versions(&self) -> Result<Vec<i64>> {
+        Ok(vec![])
+    }
+
+    async fn apply(&self, _migration: &Migration) -> Result<bool> {
+        Ok(true)
+    }
+}
+
+#[async_trait]
+impl ClientRepository for MemoryRepository {
+    async fn find_client(&self, id: i64) -> Result<Option<Client>, WebError> {
+        let state = self.state.read().unwrap();
+
+        Ok(state.clients.get(&id).map(|entry| entry.to_client(id)))
+    }
+
+    async fn find_api_keys_by_prefix(
+        &self,
+        prefix: &str,
+    ) -> Result<Vec<(ApiKey, KeyHash)>, WebError> {
+        let state = self.state.read().unwrap();
+
+        Ok(state
+            .api_keys
+            .iter()
+            .filter(|api_key| api_key.prefix == prefix)
+            .map(|api_key| (api_key.to_api_key(), api_key.key_hash.clone()))
+            .collect())
+    }
+
+    async fn touch_api_key(&self, id: i64) -> Result<(), WebError> {
+        let mut state = self.state.write().unwrap();
+
+        if let Some(api_key) = state.api_keys.iter_mut().find(|api_key| api_key.id == id) {
+            api_key.last_used_at = Some(Utc::now());
+        }
+
+        Ok(())
+    }
+
+    async fn create_client(&self, display_name: &str) -> Result<Client, WebError> {
+        let mut state = self.state.write().unwrap();
+        let id = state.next_id();
+
+        let entry = ClientEntry::new(display_name);
+        let client = entry.to_client(id);
+
+        state.clients.insert(id, entry);
+        Ok(client)
+    }
+
+    async fn list_clients(&self) -> Result<Vec<Client>, WebError> {
+        let state = self.state.read().unwrap();
+
+        Ok(state
+            .clients
+            .iter()
+            .map(|(id, entry)| entry.to_client(*id))
+            .collect())
+    }
+
+    async fn rename_client(&self, id: i64, display_name: &str) -> Result<bool, WebError> {
+        let mut state = self.state.write().unwrap();
+
+        match state.clients.get_mut(&id) {
+            Some(entry) => {
+                entry.display_name = display_name.to_owned();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn set_client_time_zone(&self, id: i64, time_zone: &str) -> Result<bool, WebError> {
+        let mut state = self.state.write().unwrap();
+
+        match state.clients.get_mut(&id) {
+            Some(entry) => {
+                entry.time_zone = time_zone.to_owned();
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn delete_client(&self, id: i64) -> Result<bool, WebError> {
+        let mut state = self.state.write().unwrap();
+
+        if state.clients.remove(&id).is_none() {
+            return Ok(false);
+        }
+
+        let work_list_ids: Vec<i64> = state
+            .work_lists
+            .iter()
+            .filter(|(_, entry)| entry.client_id == id)
+            .map(|(work_list_id, _)| *work_list_id)
+            .collect();
+
+        state
+            .todos
+            .retain(|_, todo| !work_list_ids.contains(&todo.work_list_id));
+        let State {
+            todos, reminders, ..
+        } = &mut *state;
+        reminders.retain(|_, entry| todos.contains_key(&entry.reminder.todo_id));
+        let tag_ids: Vec<i64> = state
+            .tags
+            .iter()
+            .filter(|(_, entry)| entry.client_id == id)
+            .map(|(tag_id, _)| *tag_id)
+            .collect();
+        state
+            .todo_tags
+            .retain(|(_, tag_id)| !tag_ids.contains(tag_id));
+        state.tags.retain(|_, entry| entry.client_id != id);
+        state.webhooks.retain(|_, entry| entry.client_id != id);
+        state.events.retain(|_, event| event.client_id != id);
+        state
+            .tombstones
+            .retain(|_, (client_id, _)| *client_id != id);
+        state
+            .applied_mutations
+            .retain(|(client_id, _), _| *client_id != id);
+        let State {
+            webhooks,
+            deliveries,
+            ..
+        } = &mut *state;
+        deliveries.retain(|_, entry| webhooks.contains_key(&entry.webhook_id));
+        state.work_lists.retain(|_, entry| entry.client_id != id);
+        state.api_keys.retain(|api_key| api_key.client_id != id);
+
+        Ok(true)
+    }
+
+    async fn create_api_key(
+        &self,
+        client_id: i64,
+        label: Option<&str>,
+        prefix: &str,
+        key_hash: &KeyHash,
+        valid_to: DateTime<Utc>,
+        permissions: &Permissions,
+    ) -> Result<ApiKey, WebError> {
+        let mut state = self.state.write().unwrap();
+        let id = state.next_id();
+        let entry = ApiKeyEntry {
+            id,
+            prefix: prefix.to_owned(),
+            label: label.map(str::to_owned),
+            last_used_at: None,
+            permissions: permissions.clone(),
+            key_hash: key_hash.clone(),
+            valid_to,
+            client_id,
+        };
+        let api_key = entry.to_api_key();
+
+        state.api_keys.push(entry);
+        Ok(api_key)
+    }
+
+    async fn find_api_key(&self, id: i64) -> Result<Option<ApiKey>, WebError> {
+        let state = self.state.read().unwrap();
+
+        Ok(state
+            .api_keys
+            .iter()
+            .find(|api_key| api_key.id == id)
+            .map(ApiKeyEntry::to_api_key))
+    }
+
+    async fn list_api_keys(&self, client_id: i64) -> Result<Vec<ApiKey>, WebError> {
+        let state = self.state.read().unwrap();
+
+        Ok(state
+            .api_keys
+            .iter()
+            .filter(|api_key| api_key.client_id == client_id)
+            .map(ApiKeyEntry::to_api_key)
+            .collect())
+    }
+
+    async fn revoke_api_key(&self, id: i64) -> Result<bool, WebError> {
+        let mut state = self.state.write().unwrap();
+        let count = state.api_keys.len();
+
+        state.api_keys.retain(|api_key| api_key.id != id);
+        Ok(state.api_keys.len() < count)
+    }
+
+    /// Keys are hashed from the start in memory.
+    async fn plaintext_api_keys(&self) -> Result<Vec<(i64, String)>, WebError> {
+        Ok(vec![])
+    }
+
+    async fn hash_plaintext_api_key(
+        &self,
+        _id: i64,
+        _prefix: &str,
+        _key_hash: &KeyHash,
+    ) -> Result<(), WebError> {
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl WorkListRepository for MemoryRepository {
+    async fn work_list_exists(&self, id: i64, client_id: i64) -> Result<bool, WebError> {
+        Ok(self.state.read().unwrap().owns_work_list(id, client_id))
+    }
+
+    async fn create_work_list(
+        &self,
+        name: &str,
+        client_id: i64,
+        created_at: DateTime<Utc>,
+        mutation_id: Option<&str>,
+        event: &DescribeEvent<'_>,
+    ) -> Result<(WorkList, Event), WebError> {
+        let mut state = self.state.write().unwrap();
+        let mutation_key = mutation_id.map(|mutation_id| (client_id, mutation_id.to_owned()));
+
+        if let Some(key) = mutation_key.as_ref() {
+            if state.applied_mutations.contains_key(key) {
+                return Err(WebError::Conflict);
+            }
+        }
+
+        let id = state.next_id();
+        let version = state.next_version();
+
+        let entry = WorkListEntry {
+            name: name.to_owned(),
+            client_id,
+            created_at,
+            version,
+        };
+        let work_list = entry.to_work_list(id);
+
+        state.work_lists.insert(id, entry);
+
+        if let Some(key) = mutation_key {
+            state
+                .applied_mutations
+                .insert(key, (TombstoneKind::WorkList, id));
+        }
+
+        let event = state.record_event(event(id, version));
+        Ok((work_list, event))
+    }
+
+    async fn list_work_lists(
+        &self,
+        client_id: i64,
+        query: &WorkListQuery,
+    ) -> Result<Vec<WorkList>, WebError> {
+        let state = self.state.read().unwrap();
+        let name = query.name.as_ref().map(|name| name.to_lowercase());
+
+        let mut entries: Vec<(i64, &WorkListEntry)> = state
+            .work_lists
+            .iter()
+            .map(|(id, entry)| (*id, entry))
+            .filter(|(id, entry)| {
+                entry.client_id == client_id
+                    && query
+                        .ids
+                        .as_ref()
+                        .map(|ids| ids.contains(id))
+                        .unwrap_or(true)
+                    && name
+                        .as_ref()
+                        .map(|name| entry.name.to_lowercase().contains(name))
+                        .unwrap_or(true)
+                    && query
+                        .tag
+                        .as_ref()
+                        .map(|tag| {
+                            state
+                                .todos
+                                .values()
+                                .any(|todo| todo.work_list_id == *id && state.has_tag(todo.id, tag))
+                        })
+                        .unwrap_or(true)
+                    && query
+                        .after
+                        .as_ref()
+                        .map(|after| {
+                            WorkListEntry::compare(
+                                query.sort,
+                                entry.sort_key(*id),
+                                after.sort_key(),
+                            ) == Ordering::Greater
+                        })
+                        .unwrap_or(true)
+            })
+            .collect();
+
+        entries.sort_by(|(id, entry), (other_id, other)| {
+            WorkListEntry::compare(query.sort, entry.sort_key(*id), other.sort_key(*other_id))
+        });
+
+        Ok(entries
+            .into_iter()
+            .take(query.limit as usize)
+            .map(|(id, entry)| entry.to_work_list(id))
+            .collect())
+    }
+
+    async fn find_work_list(&self, id: i64, client_id: i64) -> Result<Option<WorkList>, WebError> {
+        let state = self.state.read().unwrap();
+
+        Ok(state
+            .work_lists
+            .get(&id)
+            .filter(|entry| entry.client_id == client_id)
+            .map(|entry| entry.to_work_list(id)))
+    }
+
+    async fn update_work_list(
+        &self,
+        id: i64,
+        client_id: i64,
+        name: &str,
+        expected_version: Option<i64>,
+        event: &DescribeEvent<'_>,
+    ) -> Result<Option<(i64, Event)>, WebError> {
+        let mut state = self.state.write().unwrap();
+
+        if !state.owns_work_list(id, client_id) {
+            return Ok(None);
+        }
+
+        if is_modified(state.work_lists[&id].version, expected_version) {
+            return Err(WebError::PreconditionFailed);
+        }
+
+        let version = state.next_version();
+        let entry = state.work_lists.get_mut(&id).unwrap();
+        entry.name = name.to_owned();
+        entry.version = version;
+
+        let event = state.record_event(event(id, version));
+        Ok(Some((version, event)))
+    }
+
+    async fn delete_work_list(
+        &self,
+        id: i64,
+        client_id: i64,
+        expected_version: Option<i64>,
+        event: &DescribeEvent<'_>,
+    ) -> Result<Option<Event>, WebError> {
+        let mut state = self.state.write().unwrap();
+
+        if !state.owns_work_list(id, client_id) {
+            return Ok(None);
+        }
+
+        if is_modified(state.work_lists[&id].version, expected_version) {
+            return Err(WebError::PreconditionFailed);
+        }
+
+        state.work_lists.remove(&id);
+        state.todos.retain(|_, todo| todo.work_list_id != id);
+        let State {
+            todos,
+            todo_tags,
+            reminders,
+            ..
+        } = &mut *state;
+        todo_tags.retain(|(todo_id, _)| todos.contains_key(todo_id));
+        reminders.retain(|_, entry| todos.contains_key(&entry.reminder.todo_id));
+
+        let version = state.bury(client_id, TombstoneKind::WorkList, id, None);
+        Ok(Some(state.record_event(event(id, version))))
+    }
+}
+
+#[async_trait]
+impl TodoRepository for MemoryRepository {
+    async fn find_todo(&self, id: i64, client_id: i64) -> Result<Option<Todo>, WebError> {
+        let state = self.state.read().unwrap();
+
+        Ok(state
+            .todos
+            .get(&id)
+            .filter(|todo| state.owns_work_list(todo.work_list_id, client_id))
+            .cloned())
+    }
+
+    async fn todos_in_work_lists(
+        &self,
+        work_list_ids: &[i64],
+        per_list: Option<u32>,
+    ) -> Result<Vec<Todo>, WebError> {
+        let state = self.state.read().unwrap();
+        let mut counts: HashMap<i64, u32> = HashMap::new();
+
+        let mut todos: Vec<&Todo> = state
+            .todos
+            .values()
+            .filter(|todo| work_list_ids.contains(&todo.work_list_id))
+            .collect();
+        todos.sort_by_key(|todo| (todo.position, todo.id));
+
+        Ok(todos
+            .into_iter()
+            .filter(|todo| {
+                let count = counts.entry(todo.work_list_id).or_insert(0);
+                *count += 1;
+                per_list.map(|per_list| *count <= per_list).unwrap_or(true)
+            })
+            .cloned()
+            .collect())
+    }
+
+    async fn search_todos(&self, client_id: i64, query: &TodoQuery) -> Result<Vec<Todo>, WebError> {
+        let state = self.state.read().unwrap();
+        let content = query.content.as_ref().map(|content| content.to_lowercase());
+
+        // Orders todos the way SQL backends do for given sort, with ID breaking ties.
+        let compare = |(id, content): (i64, &str), (other_id, other_content): (i64, &str)| {
+            let ordering = match query.sort {
+                TodoSort::IdAsc | TodoSort::IdDesc => Ordering::Equal,
+                TodoSort::ContentAsc | TodoSort::ContentDesc => content.cmp(other_content),
+            }
+            .then(id.cmp(&other_id));
+
+            if query.sort.is_descending() {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        };
+
+        let mut todos: Vec<&Todo> = state
+            .todos
+            .values()
+            .filter(|todo| {
+                state.owns_work_list(todo.work_list_id, client_id)
+                    && query
+                        .completed
+                        .map(|completed| todo.completed == completed)
+                        .unwrap_or(true)
+                    && query
+                        .work_list_ids
+                        .as_ref()
+                        .map(|ids| ids.contains(&todo.work_list_id))
+                        .unwrap_or(true)
+                    && content
+                        .as_ref()
+                        .map(|content| todo.content.to_lowercase().contains(content))
+                        .unwrap_or(true)
+                    && query.due.map(|due| is_due(todo, due)).unwrap_or(true)
+                    && query
+                        .tag
+                        .as_ref()
+                        .map(|tag| state.has_tag(todo.id, tag))
+                        .unwrap_or(true)
+                    && query
+                        .after
+                        .as_ref()
+                        .map(|after| {
+                            compare((todo.id, &todo.content), (after.id, &after.content))
+                                == Ordering::Greater
+                        })
+                        .unwrap_or(true)
+            })
+            .collect();
+
+        todos.sort_by(|todo, other| compare((todo.id, &todo.content), (other.id, &other.content)));
+
+        Ok(todos
+            .into_iter()
+            .take(query.limit as usize)
+            .cloned()
+            .collect())
+    }
+
+    async fn create_todo(
+        &self,
+        todo: &NewTodo,
+        event: &DescribeTodo<'_>,
+    ) -> Result<(Todo, Event), WebError> {
+        let mut state = self.state.write().unwrap();
+        let mutation_key = match todo.mutation_id.as_ref() {
+            Some(mutation_id) => {
+                let client_id = state
+                    .work_lists
+                    .get(&todo.work_list_id)
+                    .map(|entry| entry.client_id)
+                    .ok_or(WebError::NotFound(Resource::WorkList))?;
+                let key = (client_id, mutation_id.clone());
+
+                if state.applied_mutations.contains_key(&key) {
+                    return Err(WebError::Conflict);
+                }
+
+                Some(key)
+            }
+            None => None,
+        };
+
+        let todo = state.insert_todo(todo);
+
+        if let Some(key) = mutation_key {
+            state
+                .applied_mutations
+                .insert(key, (TombstoneKind::Todo, todo.id));
+        }
+
+        let event = state.record_event(event(EventKind::TodoCreated, &todo));
+        Ok((todo, event))
+    }
+
+    async fn update_todo(
+        &self,
+        id: i64,
+        changes: &TodoChanges,
+        expected_version: Option<i64>,
+        event: &DescribeEvent<'_>,
+    ) -> Result<(i64, Event), WebError> {
+        let mut state = self.state.write().unwrap();
+
+        let current = state
+            .todos
+            .get(&id)
+            .map(|todo| todo.version)
+            .ok_or(WebError::NotFound(Resource::Todo))?;
+
+        if is_modified(current, expected_version) {
+            return Err(WebError::PreconditionFailed);
+        }
+
+        let version = state.update_todo(id, changes).version;
+        let event = state.record_event(event(id, version));
+        Ok((version, event))
+    }
+
+    async fn update_todo_cascade(
+        &self,
+        cascade: &TodoCascade,
+        expected_version: Option<i64>,
+        event: &DescribeTodo<'_>,
+    ) -> Result<(Todo, Vec<Event>), WebError> {
+        let mut state = self.state.write().unwrap();
+
+        let current = state
+            .todos
+            .get(&cascade.todo.todo.id)
+            .map(|todo| todo.version)
+            .ok_or(WebError::NotFound(Resource::Todo))?;
+
+        if is_modified(current, expected_version) {
+            return Err(WebError::PreconditionFailed);
+        }
+
+        // Nothing is written unless all of the todos still exist.
+        if cascade
+            .subtasks
+            .iter()
+            .any(|write| !state.todos.contains_key(&write.todo.id))
+        {
+            return Err(WebError::NotFound(Resource::Todo));
+        }
+
+        let write = &cascade.todo;
+        let todo = state.update_todo(write.todo.id, &write.changes);
+        let mut events = vec![state.record_event(event(write.kind, &todo))];
+
+        for write in cascade.subtasks.iter() {
+            let subtask = state.update_todo(write.todo.id, &write.changes);
+            events.push(state.record_event(event(write.kind, &subtask)));
+        }
+
+        if let Some(next) = cascade.next_occurrence.as_ref() {
+            let next = state.insert_todo(next);
+            let remind_at: Vec<DateTime<Utc>> = state
+                .reminders
+                .values()
+                .filter(|entry| entry.reminder.todo_id == todo.id)
+                .map(|entry| entry.reminder.remind_at + cascade.reminder_offset)
+                .collect();
+
+            for remind_at in remind_at {
+                let reminder = Reminder {
+                    id: state.next_id(),
+                    todo_id: next.id,
+                    remind_at,
+                    sent_at: None,
+                    failed_at: None,
+                    attempts: 0,
+                };
+
+                state.reminders.insert(
+                    reminder.id,
+                    ReminderEntry {
+                        reminder,
+                        locked_by: None,
+                        locked_until: None,
+                    },
+                );
+            }
+
+            events.push(state.record_event(event(EventKind::TodoCreated, &next)));
+        }
+
+        Ok((todo, events))
+    }
+
+    async fn delete_todo(
+        &self,
+        id: i64,
+        expected_version: Option<i64>,
+        event: &DescribeEvent<'_>,
+    ) -> Result<Vec<Event>, WebError> {
+        let mut state = self.state.write().unwrap();
+
+        let todo = state
+            .todos
+            .get(&id)
+            .ok_or(WebError::NotFound(Resource::Todo))?;
+        let client_id = state
+            .work_lists
+            .get(&todo.work_list_id)
+            .map(|entry| entry.client_id)
+            .ok_or(WebError::NotFound(Resource::Todo))?;
+
+        if is_modified(todo.version, expected_version) {
+            return Err(WebError::PreconditionFailed);
+        }
+
+        let mut ids = state.subtree(id);
+        ids.push(id);
+
+        let mut events = Vec::with_capacity(ids.len());
+        for todo_id in ids {
+            if let Some(todo) = state.todos.remove(&todo_id) {
+                state.todo_tags.retain(|(id, _)| *id != todo_id);
+                state
+                    .reminders
+                    .retain(|_, entry| entry.reminder.todo_id != todo_id);
+
+                let version = state.bury(
+                    client_id,
+                    TombstoneKind::Todo,
+                    todo_id,
+                    Some(todo.work_list_id),
+                );
+                events.push(state.record_event(event(todo_id, version)));
+            }
+        }
+
+        Ok(events)
+    }
+
+    async fn find_subtasks(&self, id: i64) -> Result<Vec<Todo>, WebError> {
+        let state = self.state.read().unwrap();
+
+        Ok(state
+            .subtree(id)
+            .into_iter()
+            .map(|id| state.todos[&id].clone())
+            .collect())
+    }
+
+    async fn adjacent_todo_position(
+        &self,
+        work_list_id: i64,
+        position: i64,
+        after: bool,
+        exclude_id: i64,
+    ) -> Result<Option<i64>, WebError> {
+        let state = self.state.read().unwrap();
+        let positions = state
+            .todos
+            .values()
+            .filter(|todo| todo.work_list_id == work_list_id && todo.id != exclude_id)
+            .map(|todo| todo.position);
+
+        Ok(if after {
+            positions.filter(|other| *other > position).min()
+        } else {
+            positions.filter(|other| *other < position).max()
+        })
+    }
+
+    async fn move_todos(
+        &self,
+        moves: &[(i64, i64, i64)],
+        event: &DescribeEvent<'_>,
+    ) -> Result<Vec<Event>, WebError> {
+        let mut state = self.state.write().unwrap();
+
+        let unchanged = moves.iter().all(|(id, _, expected_version)| {
+            state
+                .todos
+                .get(id)
+                .map(|todo| todo.version == *expected_version)
+                .unwrap_or(false)
+        });
+
+        if !unchanged {
+            return Err(WebError::PreconditionFailed);
+        }
+
+        let mut events = Vec::with_capacity(moves.len());
+        for &(id, position, _) in moves {
+            let version = state.next_version();
+            let todo = state.todos.get_mut(&id).unwrap();
+            todo.position = position;
+            todo.version = version;
+
+            events.push(state.record_event(event(id, version)));
+        }
+
+        Ok(events)
+    }
+}
+
+/// Search without an index - relevance is the number of matching words.
+#[async_trait]
+impl TagRepository for MemoryRepository {
+    async fn list_tags(
+        &self,
+        client_id: i64,
+        work_list_ids: Option<&[i64]>,
+    ) -> Result<Vec<Tag>, WebError> {
+        let state = self.state.read().unwrap();
+
+        let mut tags: Vec<Tag> = state
+            .tags
+            .iter()
+            .filter(|(_, entry)| entry.client_id == client_id)
+            .map(|(id, entry)| {
+                let todos_count = state
+                    .todo_tags
+                    .iter()
+                    .filter(|(todo_id, tag_id)| {
+                        tag_id == id
+                            && state
+                                .todos
+                                .get(todo_id)
+                                .map(|todo| {
+                                    work_list_ids
+                                        .map(|ids| ids.contains(&todo.work_list_id))
+                                        .unwrap_or(true)
+                                })
+                                .unwrap_or(false)
+                    })
+                    .count();
+
+                entry.to_tag(*id, Some(todos_count as i64))
+            })
+            .collect();
+
+        tags.sort_by(|tag, other| tag.name.cmp(&other.name));
+        Ok(tags)
+    }
+
+    async fn find_tag(&self, id: i64, client_id: i64) -> Result<Option<Tag>, WebError> {
+        let state = self.state.read().unwrap();
+
+        Ok(state
+            .tags
+            .get(&id)
+            .filter(|entry| entry.client_id == client_id)
+            .map(|entry| entry.to_tag(id, None)))
+    }
+
+    async fn create_tag(
+        &self,
+        client_id: i64,
+        name: &str,
+        color: Option<&str>,
+    ) -> Result<Tag, WebError> {
+        let mut state = self.state.write().unwrap();
+
+        if state.tag_name_taken(client_id, name, None) {
+            return Err(WebError::Conflict);
+        }
+
+        let id = state.next_id();
+        let entry = TagEntry {
+            client_id,
+            name: name.to_owned(),
+            color: color.map(str::to_owned),
+        };
+        let tag = entry.to_tag(id, None);
+
+        state.tags.insert(id, entry);
+        Ok(tag)
+    }
+
+    async fn update_tag(
+        &self,
+        id: i64,
+        client_id: i64,
+        name: Option<&str>,
+        color: Option<Option<&str>>,
+    ) -> Result<bool, WebError> {
+        let mut state = self.state.write().unwrap();
+
+        if let Some(name) = name {
+            if state.tag_name_taken(client_id, name, Some(id)) {
+                return Err(WebError::Conflict);
+            }
+        }
+
+        match state
+            .tags
+            .get_mut(&id)
+            .filter(|entry| entry.client_id == client_id)
+        {
+            Some(entry) => {
+                if let Some(color) = color {
+                    entry.color = color.map(str::to_owned);
+                }
+
+                if let Some(name) = name {
+                    entry.name = name.to_owned();
+
+                    // Tagged todos show the new name.
+                    let todo_ids = state.tagged_todos(id);
+                    state.touch_todos(&todo_ids);
+                }
+
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    async fn delete_tag(&self, id: i64, client_id: i64) -> Result<bool, WebError> {
+        let mut state = self.state.write().unwrap();
+
+        match state.tags.get(&id) {
+            Some(entry) if entry.client_id == client_id => {
+                let todo_ids = state.tagged_todos(id);
+                state.tags.remove(&id);
+                state.todo_tags.retain(|(_, tag_id)| *tag_id != id);
+                state.touch_todos(&todo_ids);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn ensure_tags(&self, client_id: i64, names: &[String]) -> Result<Vec<Tag>, WebError> {
+        let mut state = self.state.write().unwrap();
+        let mut tags = Vec::with_capacity(names.len());
+
+        for name in names {
+            let existing = state
+                .tags
+                .iter()
+                .find(|(_, entry)| entry.client_id == client_id && entry.name == *name)
+                .map(|(id, entry)| entry.to_tag(*id, None));
+
+            let tag = match existing {
+                Some(tag) => tag,
+                None => {
+                    let id = state.next_id();
+                    let entry = TagEntry {
+                        client_id,
+                        name: name.clone(),
+                        color: None,
+                    };
+                    let tag = entry.to_tag(id, None);
+
+                    state.tags.insert(id, entry);
+                    tag
+                }
+            };
+
+            tags.push(tag);
+        }
+
+        Ok(tags)
+    }
+
+    async fn todo_tag_names(&self, todo_ids: &[i64]) -> Result<Vec<(i64, String)>, WebError> {
+        let state = self.state.read().unwrap();
+
+        let mut names: Vec<(i64, String)> = state
+            .todo_tags
+            .iter()
+            .filter(|(todo_id, _)| todo_ids.contains(todo_id))
+            .filter_map(|(todo_id, tag_id)| {
+                state
+                    .tags
+                    .get(tag_id)
+                    .map(|entry| (*todo_id, entry.name.clone()))
+            })
+            .collect();
+
+        names.sort_by(|(_, name), (_, other)| name.cmp(other));
+        Ok(names)
+    }
+}
+
+#[async_trait]
+impl ReminderRepository for MemoryRepository {
+    async fn list_reminders(&self, todo_id: i64) -> Result<Vec<Reminder>, WebError> {
+        let state = self.state.read().unwrap();
+
+        let mut reminders: Vec<Reminder> = state
+            .reminders
+            .values()
+            .filter(|entry| entry.reminder.todo_id == todo_id)
+            .map(|entry| entry.reminder.clone())
+            .collect();
+
+        reminders.sort_by_key(|reminder| (reminder.remind_at, reminder.id));
+        Ok(reminders)
+    }
+
+    async fn create_reminder(
+        &self,
+        todo_id: i64,
+        remind_at: DateTime<Utc>,
+    ) -> Result<Reminder, WebError> {
+        let mut state = self.state.write().unwrap();
+        let reminder = Reminder {
+            id: state.next_id(),
+            todo_id,
+            remind_at,
+            sent_at: None,
+            failed_at: None,
+            attempts: 0,
+        };
+
+        state.reminders.insert(
+            reminder.id,
+            ReminderEntry {
+                reminder: reminder.clone(),
+                locked_by: None,
+                locked_until: None,
+            },
+        );
+
+        Ok(reminder)
+    }
+
+    async fn delete_reminder(&self, id: i64, todo_id: i64) -> Result<bool, WebError> {
+        let mut state = self.state.write().unwrap();
+
+        match state.reminders.get(&id) {
+            Some(entry) if entry.reminder.todo_id == todo_id => {
+                state.reminders.remove(&id);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn lease_due_reminders(
+        &self,
+        owner: &str,
+        now: DateTime<Utc>,
+        lease_until: DateTime<Utc>,
+        limit: u32,
+    ) -> Result<Vec<(Reminder, i64)>, WebError> {
+        let mut state = self.state.write().unwrap();
+        let State {
+            reminders,
+            todos,
+            work_lists,
+            ..
+        } = &mut *state;
+
+        let mut due: Vec<(&mut ReminderEntry, i64)> = reminders
+            .values_mut()
+            .filter(|entry| entry.is_leasable(now))
+            .filter_map(|entry| {
+                let todo = todos
+                    .get(&entry.reminder.todo_id)
+                    .filter(|todo| !todo.completed)?;
+                let client_id = work_lists.get(&todo.work_list_id)?.client_id;
+
+                Some((entry, client_id))
+            })
+            .collect();
+
+        due.sort_by_key(|(entry, _)| (entry.reminder.remind_at, entry.reminder.id));
+        due.truncate(limit as usize);
+
+        Ok(due
+            .into_iter()
+            .map(|(entry, client_id)| {
+                entry.locked_by = Some(owner.to_owned());
+                entry.locked_until = Some(lease_until);
+
+                (entry.reminder.clone(), client_id)
+            })
+            .collect())
+    }
+
+    async fn finish_reminder(
+        &self,
+        id: i64,
+        owner: &str,
+        sent: bool,
+        at: DateTime<Utc>,
+    ) -> Result<bool, WebError> {
+        let mut state = self.state.write().unwrap();
+
+        match state.reminders.get_mut(&id) {
+            Some(entry) if entry.is_leased_by(owner) => {
+                if sent {
+                    entry.reminder.sent_at = Some(at);
+                } else {
+                    entry.reminder.failed_at = Some(at);
+                }
+
+                entry.locked_by = None;
+                entry.locked_until = None;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn release_reminder(
+        &self,
+        id: i64,
+        owner: &str,
+        retry_at: DateTime<Utc>,
+    ) -> Result<bool, WebError> {
+        let mut state = self.state.write().unwrap();
+
+        match state.reminders.get_mut(&id) {
+            Some(entry) if entry.is_leased_by(owner) => {
+                entry.reminder.attempts += 1;
+                entry.locked_by = None;
+                entry.locked_until = Some(retry_at);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
+#[async_trait]
+impl EventRepository for MemoryRepository {
+    async fn list_events(
+        &self,
+        client_id: i64,
+        after_id: i64,
+        limit: u32,
+    ) -> Result<Vec<Event>, WebError> {
+        let state = self.state.read().unwrap();
+
+        Ok(state
+            .events
+            .range(after_id + 1..)
+            .map(|(_, event)| event)
+            .filter(|event| event.client_id == client_id)
+            .take(limit as usize)
+            .cloned()
+            .collect())
+    }
+
+    async fn last_event_id(&self, client_id: i64) -> Result<Option<i64>, WebError> {
+        let state = self.state.read().unwrap();
+
+        Ok(state
+            .events
+            .values()
+            .rev()
+            .find(|event| event.client_id == client_id)
+            .map(|event| event.id))
+    }
+
+    async fn first_event_id(&self, client_id: i64) -> Result<Option<i64>, WebError> {
+        let state = self.state.read().unwrap();
+
+        Ok(state
+            .events
+            .values()
+            .find(|event| event.client_id == client_id)
+            .map(|event| event.id))
+    }
+
+    async fn prune_events(&self, before: DateTime<Utc>) -> Result<(), WebError> {
+        let mut state = self.state.write().unwrap();
+
+        let State {
+            events, deliveries, ..
+        } = &mut *state;
+        events.retain(|id, event| {
+            event.created_at >= before || deliveries.values().any(|entry| entry.event_id == *id)
+        });
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl WebhookRepository for MemoryRepository {
+    async fn list_webhooks(&self, client_id: i64) -> Result<Vec<Webhook>, WebError> {
+        let state = self.state.read().unwrap();
+
+        Ok(state
+            .webhooks
+            .values()
+            .filter(|entry| entry.client_id == client_id)
+            .map(|entry| entry.webhook.clone())
+            .collect())
+    }
+
+    async fn find_webhook(&self, id: i64, client_id: i64) -> Result<Option<Webhook>, WebError> {
+        let state = self.state.read().unwrap();
+
+        Ok(state
+            .webhooks
+            .get(&id)
+            .filter(|entry| entry.client_id == client_id)
+            .map(|entry| entry.webhook.clone()))
+    }
+
+    async fn create_webhook(
+        &self,
+        client_id: i64,
+        url: &str,
+        secret: &str,
+        events: Option<&[EventKind]>,
+        created_at: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<Option<Webhook>, WebError> {
+        let mut state = self.state.write().unwrap();
+        let count = state
+            .webhooks
+            .values()
+            .filter(|entry| entry.client_id == client_id)
+            .count();
+
+        if count >= limit {
+            return Ok(None);
+        }
+
+        let webhook = Webhook {
+            id: state.next_id(),
+            url: url.to_owned(),
+            secret: secret.to_owned(),
+            events: events.map(|events| events.to_vec()),
+            created_at,
+        };
+
+        state.webhooks.insert(
+            webhook.id,
+            WebhookEntry {
+                client_id,
+                webhook: webhook.clone(),
+            },
+        );
+
+        Ok(Some(webhook))
+    }
+
+    async fn update_webhook(
+        &self,
+        id: i64,
+        client_id: i64,
+        url: Option<&str>,
+        secret: Option<&str>,
+        events: Option<Option<&[EventKind]>>,
+    ) -> Result<bool, WebError> {
+        let mut state = self.state.write().unwrap();
+
+        match state.webhooks.get_mut(&id) {
+            Some(entry) if entry.client_id == client_id => {
+                if let Some(url) = url {
+                    entry.webhook.url = url.to_owned();
+                }
+
+                if let Some(secret) = secret {
+                    entry.webhook.secret = secret.to_owned();
+                }
+
+                if let Some(events) = events {
+                    entry.webhook.events = events.map(|events| events.to_vec());
+                }
+
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn delete_webhook(&self, id: i64, client_id: i64) -> Result<bool, WebError> {
+        let mut state = self.state.write().unwrap();
+
+        match state.webhooks.get(&id) {
+            Some(entry) if entry.client_id == client_id => {
+                state.webhooks.remove(&id);
+                state.deliveries.retain(|_, entry| entry.webhook_id != id);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn list_webhook_deliveries(
+        &self,
+        webhook_id: i64,
+        limit: u32,
+    ) -> Result<Vec<WebhookDelivery>, WebError> {
+        let state = self.state.read().unwrap();
+
+        Ok(state
+            .deliveries
+            .iter()
+            .rev()
+            .filter(|(_, entry)| entry.webhook_id == webhook_id)
+            .filter_map(|(id, entry)| {
+                state
+                    .events
+                    .get(&entry.event_id)
+                    .map(|event| entry.to_delivery(*id, event))
+            })
+            .take(limit as usize)
+            .collect())
+    }
+
+    async fn lease_webhook_deliveries(
+        &self,
+        owner: &str,
+        now: DateTime<Utc>,
+        lease_until: DateTime<Utc>,
+        limit: u32,
+    ) -> Result<Vec<PendingDelivery>, WebError> {
+        let mut state = self.state.write().unwrap();
+        let State {
+            deliveries,
+            webhooks,
+            events,
+            ..
+        } = &mut *state;
+
+        let mut due: Vec<(i64, &mut DeliveryEntry)> = deliveries
+            .iter_mut()
+            .filter(|(_, entry)| entry.is_leasable(now))
+            .map(|(id, entry)| (*id, entry))
+            .collect();
+
+        due.sort_by_key(|(id, entry)| (entry.next_attempt_at, *id));
+        due.truncate(limit as usize);
+
+        Ok(due
+            .into_iter()
+            .filter_map(|(id, entry)| {
+                let webhook = &webhooks.get(&entry.webhook_id)?.webhook;
+                let event = events.get(&entry.event_id)?;
+
+                entry.locked_by = Some(owner.to_owned());
+                entry.locked_until = Some(lease_until);
+
+                Some(PendingDelivery {
+                    id,
+                    attempts: entry.attempts,
+                    url: webhook.url.clone(),
+                    secret: webhook.secret.clone(),
+                    event: event.clone(),
+                })
+            })
+            .collect())
+    }
+
+    async fn record_delivery_attempt(
+        &self,
+        id: i64,
+        owner: &str,
+        attempt: &DeliveryAttempt,
+    ) -> Result<bool, WebError> {
+        let mut state = self.state.write().unwrap();
+
+        match state.deliveries.get_mut(&id) {
+            Some(entry) if entry.locked_by.as_deref() == Some(owner) => {
+                entry.status = attempt.status;
+                entry.attempts += 1;
+                entry.last_attempt_at = Some(attempt.at);
+                entry.response_status = attempt.response_status;
+                entry.error = attempt.error.clone();
+                entry.next_attempt_at = attempt.next_attempt_at.unwrap_or(entry.next_attempt_at);
+                entry.locked_by = None;
+                entry.locked_until = None;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn prune_webhook_deliveries(&self, before: DateTime<Utc>) -> Result<(), WebError> {
+        let mut state = self.state.write().unwrap();
+
+        state.deliveries.retain(|_, entry| {
+            entry.status == DeliveryStatus::Pending
+                || entry.last_attempt_at.map(|at| at >= before).unwrap_or(true)
+        });
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SearchRepository for MemoryRepository {
+    async fn search(
+        &self,
+        client_id: i64,
+        query: &SearchQuery,
+    ) -> Result<Vec<SearchResult>, WebError> {
+        let state = self.state.read().unwrap();
+        let allowed = |work_list_id: i64| {
+            state.owns_work_list(work_list_id, client_id)
+                && query
+                    .work_list_ids
+                    .as_ref()
+                    .map(|ids| ids.contains(&work_list_id))
+                    .unwrap_or(true)
+        };
+        let mut results = vec![];
+
+        if query.kinds.contains(&SearchKind::Todo) {
+            for todo in state
+                .todos
+                .values()
+                .filter(|todo| allowed(todo.work_list_id))
+            {
+                if let Some((snippet, matches)) = highlight(&todo.content, &query.terms) {
+                    results.push(SearchResult {
+                        kind: SearchKind::Todo,
+                        id: todo.id,
+                        work_list_id: todo.work_list_id,
+                        snippet,
+                        score: matches as f64,
+                    });
+                }
+            }
+        }
+
+        if query.kinds.contains(&SearchKind::WorkList) {
+            for (id, entry) in state.work_lists.iter().filter(|(id, _)| allowed(**id)) {
+                if let Some((snippet, matches)) = highlight(&entry.name, &query.terms) {
+                    results.push(SearchResult {
+                        kind: SearchKind::WorkList,
+                        id: *id,
+                        work_list_id: *id,
+                        snippet,
+                        score: matches as f64,
+                    });
+                }
+            }
+        }
+
+        results.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(Ordering::Equal)
+                .then(a.id.cmp(&b.id))
+        });
+        results.truncate(query.limit as usize);
+
+        Ok(results)
+    }
+}
+
+#[async_trait]
+impl SyncRepository for MemoryRepository {
+    async fn changed_work_lists(
+        &self,
+        client_id: i64,
+        since: i64,
+        limit: u32,
+    ) -> Result<Vec<WorkList>, WebError> {
+        let state = self.state.read().unwrap();
+
+        let mut entries: Vec<(i64, &WorkListEntry)> = state
+            .work_lists
+            .iter()
+            .filter(|(_, entry)| entry.client_id == client_id && entry.version > since)
+            .map(|(id, entry)| (*id, entry))
+            .collect();
+        entries.sort_by_key(|(_, entry)| entry.version);
+
+        Ok(entries
+            .into_iter()
+            .take(limit as usize)
+            .map(|(id, entry)| entry.to_work_list(id))
+            .collect())
+    }
+
+    async fn changed_todos(
+        &self,
+        client_id: i64,
+        since: i64,
+        limit: u32,
+    ) -> Result<Vec<Todo>, WebError> {
+        let state = self.state.read().unwrap();
+
+        let mut todos: Vec<&Todo> = state
+            .todos
+            .values()
+            .filter(|todo| {
+                todo.version > since && state.owns_work_list(todo.work_list_id, client_id)
+            })
+            .collect();
+        todos.sort_by_key(|todo| todo.version);
+
+        Ok(todos.into_iter().take(limit as usize).cloned().collect())
+    }
+
+    async fn list_tombstones(
+        &self,
+        client_id: i64,
+        since: i64,
+        limit: u32,
+    ) -> Result<Vec<Tombstone>, WebError> {
+        let state = self.state.read().unwrap();
+
+        Ok(state
+            .tombstones
+            .range(since + 1..)
+            .map(|(_, entry)| entry)
+            .filter(|(tombstone_client_id, _)| *tombstone_client_id == client_id)
+            .take(limit as usize)
+            .map(|(_, tombstone)| tombstone.clone())
+            .collect())
+    }
+
+    async fn find_mutation(
+        &self,
+        client_id: i64,
+        mutation_id: &str,
+    ) -> Result<Option<(TombstoneKind, i64)>, WebError> {
+        let state = self.state.read().unwrap();
+
+        Ok(state
+            .applied_mutations
+            .get(&(client_id, mutation_id.to_owned()))
+            .copied())
+    }
+}
@@ -0,0 +1,193 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use log::info;
+use std::env;
+
+use super::{ClientRepository, Storage};
+use crate::model::{ApiKey, KeyHash};
+
+/// Schema change embedded into the binary. Versions must be strictly increasing.
+///
+/// Versions are numbered per backend. A change needed by one backend only, such as the ordering
+/// of versions on PostgreSQL, takes the next number there alone, so the same number may stand
+/// for different changes in `SQLITE` and `POSTGRES`.
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+macro_rules! migration {
+    ($backend:literal, $version:literal, $name:literal) => {
+        Migration {
+            version: $version,
+            name: $name,
+            sql: include_str!(concat!(
+                "../../migrations/",
+                $backend,
+                "/",
+                stringify!($version),
+                "_",
+                $name,
+                ".sql"
+            )),
+        }
+    };
+}
+
+pub const SQLITE: &[Migration] = &[
+    migration!("sqlite", 0001, "create_tables"),
+    migration!("sqlite", 0002, "hash_api_keys"),
+    migration!("sqlite", 0003, "api_key_labels"),
+    migration!("sqlite", 0004, "api_key_scopes"),
+    migration!("sqlite", 0005, "work_list_created_at"),
+    migration!("sqlite", 0006, "full_text_search"),
+    migration!("sqlite", 0007, "todo_dates"),
+    migration!("sqlite", 0008, "todo_ordering"),
+    migration!("sqlite", 0009, "subtasks"),
+    migration!("sqlite", 0010, "tags"),
+    migration!("sqlite", 0011, "recurrence"),
+    migration!("sqlite", 0012, "reminders"),
+    migration!("sqlite", 0013, "webhooks"),
+    migration!("sqlite", 0014, "sync"),
+    migration!("sqlite", 0015, "applied_mutations"),
+];
+
+pub const POSTGRES: &[Migration] = &[
+    migration!("postgres", 0001, "create_tables"),
+    migration!("postgres", 0002, "hash_api_keys"),
+    migration!("postgres", 0003, "api_key_labels"),
+    migration!("postgres", 0004, "api_key_scopes"),
+    migration!("postgres", 0005, "work_list_created_at"),
+    migration!("postgres", 0006, "full_text_search"),
+    migration!("postgres", 0007, "todo_dates"),
+    migration!("postgres", 0008, "todo_ordering"),
+    migration!("postgres", 0009, "subtasks"),
+    migration!("postgres", 0010, "tags"),
+    migration!("postgres", 0011, "recurrence"),
+    migration!("postgres", 0012, "reminders"),
+    migration!("postgres", 0013, "webhooks"),
+    migration!("postgres", 0014, "sync"),
+    migration!("postgres", 0015, "ordered_versions"),
+    migration!("postgres", 0016, "applied_mutations"),
+    migration!("postgres", 0017, "client_change_locks"),
+];
+
+/// Backend able to track and apply schema migrations.
+#[async_trait]
+pub trait Migrator {
+    fn migrations(&self) -> &'static [Migration];
+    /// Creates `schema_migrations` table if needed and returns versions recorded there.
+    async fn applied_versions(&self) -> Result<Vec<i64>>;
+    /// Runs migration and records it in `schema_migrations` atomically. Processes starting at
+    /// the same time apply each migration once, the others get `false`.
+    async fn apply(&self, migration: &Migration) -> Result<bool>;
+}
+
+fn pending<'a>(storage: &'a Storage, applied: &[i64]) -> Result<Vec<&'a Migration>> {
+    let known = storage.migrations();
+    let latest_known = known.iter().map(|m| m.version).max().unwrap_or(0);
+
+    if let Some(latest_applied) = applied.iter().max() {
+        if *latest_applied > latest_known {
+            return Err(anyhow!(
+                "Database schema is at version {} but this binary only knows migrations up to {}. Refusing to start.",
+                latest_applied,
+                latest_known
+            ));
+        }
+    }
+
+    Ok(known
+        .iter()
+        .filter(|m| !applied.contains(&m.version))
+        .collect())
+}
+
+/// Applies every migration not yet recorded in the database.
+pub async fn run(storage: &Storage) -> Result<()> {
+    let applied = storage.applied_versions().await?;
+
+    for migration in pending(storage, &applied)? {
+        info!(
+            "Applying migration {} ({})",
+            migration.version, migration.name
+        );
+
+        if !storage.apply(migration).await? {
+            info!(
+                "Migration {} ({}) was applied by another process",
+                migration.version, migration.name
+            );
+        }
+    }
+
+    hash_plaintext_api_keys(storage).await
+}
+
+/// Keys issued before hashing was introduced are hashed in place. Their tokens stay valid.
+async fn hash_plaintext_api_keys(storage: &Storage) -> Result<()> {
+    let plaintext_keys = storage
+        .plaintext_api_keys()
+        .await
+        .map_err(|err| anyhow!("Failed to read plaintext API keys: {:?}", err))?;
+
+    if !plaintext_keys.is_empty() {
+        info!("Hashing {} plaintext API keys", plaintext_keys.len());
+    }
+
+    for (id, token) in plaintext_keys {
+        storage
+            .hash_plaintext_api_key(id, ApiKey::token_prefix(&token), &KeyHash::new(&token))
+            .await
+            .map_err(|err| anyhow!("Failed to hash API key {}: {:?}", id, err))?;
+    }
+
+    Ok(())
+}
+
+/// Verifies schema version without changing the database. The check fails while any migration
+/// is not applied, or any key is stored in plaintext and so can't be authenticated.
+pub async fn check(storage: &Storage) -> Result<()> {
+    let applied = storage.applied_versions().await?;
+    let pending = pending(storage, &applied)?;
+
+    if !pending.is_empty() {
+        let names: Vec<String> = pending
+            .iter()
+            .map(|migration| format!("{} ({})", migration.version, migration.name))
+            .collect();
+
+        return Err(anyhow!(
+            "Migrations {} are not applied. Run with DATABASE_AUTO_MIGRATE enabled once to apply them. Refusing to start.",
+            names.join(", ")
+        ));
+    }
+
+    let plaintext_keys = storage
+        .plaintext_api_keys()
+        .await
+        .map_err(|err| anyhow!("Failed to read plaintext API keys: {:?}", err))?;
+
+    if !plaintext_keys.is_empty() {
+        return Err(anyhow!(
+            "{} API keys are stored in plaintext and would be rejected. Run with DATABASE_AUTO_MIGRATE enabled once to hash them. Refusing to start.",
+            plaintext_keys.len()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Runs or only checks migrations, depending on `DATABASE_AUTO_MIGRATE` (enabled by default).
+pub async fn prepare(storage: &Storage) -> Result<()> {
+    let auto_migrate = env::var("DATABASE_AUTO_MIGRATE")
+        .map(|value| value != "0" && value.to_lowercase() != "false")
+        .unwrap_or(true);
+
+    if auto_migrate {
+        run(storage).await
+    } else {
+        check(storage).await
+    }
+}
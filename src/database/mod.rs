@@ -0,0 +1,187 @@
+use anyhow::{anyhow, Result};
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+
+mod memory;
+pub mod migrations;
+mod postgres;
+mod repository;
+mod sqlite;
+
+pub use memory::MemoryRepository;
+pub use postgres::PgRepository;
+pub use repository::{
+    ClientRepository, DescribeEvent, DescribeTodo, EventRepository, ReminderRepository, Repository,
+    SearchRepository, Storage, SyncRepository, TagRepository, TodoRepository, WebhookRepository,
+    WorkListRepository,
+};
+pub use sqlite::SqliteRepository;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backend {
+    Sqlite,
+    Postgres,
+    Memory,
+}
+
+impl Backend {
+    pub fn from_url(url: &str) -> Result<Self> {
+        if url.starts_with("sqlite:") {
+            Ok(Backend::Sqlite)
+        } else if url.starts_with("postgres:") || url.starts_with("postgresql:") {
+            Ok(Backend::Postgres)
+        } else if url.starts_with("memory:") {
+            Ok(Backend::Memory)
+        } else {
+            Err(anyhow!(
+                "Unsupported DATABASE_URL scheme, expected sqlite://, postgres:// or memory://"
+            ))
+        }
+    }
+}
+
+pub struct PoolConfig {
+    backend: Backend,
+    url: String,
+    max_lifetime: Duration,
+    idle_timeout: Duration,
+    conn_timeout: Duration,
+    pool_size: (u32, u32),
+    memory_api_key: Option<String>,
+}
+
+use log::{info, warn};
+
+impl PoolConfig {
+    /// Default settings for a database, as used by `from_env` when nothing else is set.
+    pub fn from_url(url: &str) -> Result<Self> {
+        Ok(Self {
+            backend: Backend::from_url(url)?,
+            url: url.to_owned(),
+            max_lifetime: Duration::from_millis(5000),
+            idle_timeout: Duration::from_millis(10000),
+            conn_timeout: Duration::from_millis(1000),
+            pool_size: (16, 8),
+            memory_api_key: None,
+        })
+    }
+
+    pub fn from_env() -> Result<Self> {
+        let url = env::var("DATABASE_URL").map_err(|_| anyhow!("DATABASE_URL not provided"))?;
+        let defaults = Self::from_url(&url)?;
+        let backend = defaults.backend;
+
+        let max_lifetime = env::var("DATABASE_MAX_LIFETIME")
+            .map(|millis| {
+                millis
+                    .parse()
+                    .map_err(|err| warn!("Failed to read max lifetime from env var: {:?}", err))
+                    .map(|millis| Duration::from_millis(millis))
+                    .unwrap_or_default()
+            })
+            .unwrap_or(defaults.max_lifetime);
+        let max_pool_size = env::var("DATABASE_POOL_MAX")
+            .map(|pool_size| {
+                pool_size
+                    .parse()
+                    .map_err(|err| warn!("Failed to read pool max from env var: {:?}", err))
+                    .unwrap_or_default()
+            })
+            .unwrap_or(defaults.pool_size.0);
+        let min_pool_size = env::var("DATABASE_POOL_MIN")
+            .map(|pool_size| {
+                pool_size
+                    .parse()
+                    .map_err(|err| warn!("Failed to read pool min from env var: {:?}", err))
+                    .unwrap_or_default()
+            })
+            .unwrap_or(defaults.pool_size.1);
+        let idle_timeout = env::var("DATABASE_IDLE_TIMEOUT")
+            .map(|millis| {
+                millis
+                    .parse()
+                    .map_err(|err| warn!("Failed to read idle timeout from env var: {:?}", err))
+                    .map(|millis| Duration::from_millis(millis))
+                    .unwrap_or_default()
+            })
+            .unwrap_or(defaults.idle_timeout);
+        let conn_timeout = env::var("DATABASE_CONN_TIMEOUT")
+            .map(|millis| {
+                millis
+                    .parse()
+                    .map_err(|err| warn!("Failed to read connect timeout from env var: {:?}", err))
+                    .map(|millis| Duration::from_millis(millis))
+                    .unwrap_or_default()
+            })
+            .unwrap_or(defaults.conn_timeout);
+
+        match backend {
+            Backend::Postgres => info!("Database pool initialized (PGSql)"),
+            Backend::Sqlite => {
+                info!("Database pool initialized (SQLite)");
+                info!("Database URL: {}", url);
+            }
+            Backend::Memory => info!("Database initialized (in-memory, data is not persisted)"),
+        }
+        info!("Pool size: max {}, min {}", max_pool_size, min_pool_size);
+        info!("Idle timeout: {} ms", idle_timeout.as_millis());
+        info!("Connect timeout: {} ms", conn_timeout.as_millis());
+        info!("Max lifetime of conn: {} ms", max_lifetime.as_millis());
+
+        Ok(Self {
+            backend,
+            url,
+            max_lifetime,
+            idle_timeout,
+            conn_timeout,
+            pool_size: (max_pool_size, min_pool_size),
+            memory_api_key: env::var("MEMORY_API_KEY").ok(),
+        })
+    }
+}
+
+pub async fn connect(config: PoolConfig) -> Result<Storage> {
+    let storage: Storage = match config.backend {
+        Backend::Sqlite => Arc::new(SqliteRepository::connect(&config).await?),
+        Backend::Postgres => Arc::new(PgRepository::connect(&config).await?),
+        Backend::Memory => {
+            let repository = MemoryRepository::new();
+
+            if let Some(key) = config.memory_api_key.as_ref() {
+                let client_id = repository.seed_client("Demo", key, chrono::Duration::days(365));
+                info!("Seeded in-memory client {} with MEMORY_API_KEY", client_id);
+            }
+
+            Arc::new(repository)
+        }
+    };
+
+    Ok(storage)
+}
+
+/// Pattern matching `value` anywhere in a column, for use with `LIKE ... ESCAPE '\'`.
+fn like_pattern(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+
+    format!("%{}%", escaped)
+}
+
+/// Whether a write expecting a resource at `expected_version` has to fail, the resource being at
+/// `version` now.
+fn is_modified(version: i64, expected_version: Option<i64>) -> bool {
+    expected_version
+        .map(|expected| expected != version)
+        .unwrap_or(false)
+}
+
+/// Comma-separated IDs to be embedded into an SQL `IN` list.
+fn id_list(ids: &[i64]) -> String {
+    ids.iter()
+        .map(|id| id.to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
+}
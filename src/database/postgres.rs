@@ -0,0 +1,2140 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, NaiveDate, Utc};
+use sqlx::{Executor, FromRow, PgPool};
+
+use super::migrations::{self, Migration, Migrator};
+use super::repository::{
+    ClientRepository, DescribeEvent, DescribeTodo, EventRepository, ReminderRepository,
+    SearchRepository, SyncRepository, TagRepository, TodoRepository, WebhookRepository,
+    WorkListRepository,
+};
+use super::{is_modified, like_pattern, PoolConfig};
+use crate::error::{Resource, WebError};
+use crate::model::{
+    ApiKey, DeliveryAttempt, DeliveryStatus, Due, DueCondition, Event, EventKind, KeyHash,
+    NewEvent, NewTodo, PendingDelivery, Permissions, Priority, Reminder, SearchKind, SearchQuery,
+    SearchResult, Tag, Todo, TodoCascade, TodoChanges, TodoQuery, TodoSort, Tombstone,
+    TombstoneKind, Webhook, WebhookDelivery, WorkList, WorkListQuery, WorkListSort, MATCH_END,
+    MATCH_START, POSITION_STEP,
+};
+use crate::web_app::Client;
+
+pub struct PgRepository {
+    pool: PgPool,
+}
+
+impl PgRepository {
+    pub async fn connect(config: &PoolConfig) -> Result<Self> {
+        let pool = PgPool::builder()
+            .connect_timeout(config.conn_timeout)
+            .idle_timeout(config.idle_timeout)
+            .max_lifetime(config.max_lifetime)
+            .max_size(config.pool_size.0)
+            .min_size(config.pool_size.1)
+            .build(&config.url)
+            .await
+            .map_err(|err| anyhow::Error::new(err))?;
+
+        Ok(Self { pool })
+    }
+}
+
+#[derive(FromRow)]
+struct ApiKeyRow {
+    id: i64,
+    client_id: i64,
+    prefix: String,
+    label: Option<String>,
+    valid_to: DateTime<Utc>,
+    last_used_at: Option<DateTime<Utc>>,
+    scopes: Option<String>,
+    work_list_ids: Option<String>,
+    salt: Option<String>,
+    key_hash: Option<String>,
+}
+
+impl ApiKeyRow {
+    fn key_hash(&self) -> Option<KeyHash> {
+        match (self.salt.as_ref(), self.key_hash.as_ref()) {
+            (Some(salt), Some(hash)) => Some(KeyHash {
+                salt: salt.clone(),
+                hash: hash.clone(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl From<ApiKeyRow> for ApiKey {
+    fn from(row: ApiKeyRow) -> Self {
+        ApiKey::new(
+            row.id,
+            row.client_id,
+            row.prefix,
+            row.label,
+            row.valid_to,
+            row.last_used_at,
+            Permissions::from_columns(row.scopes.as_deref(), row.work_list_ids.as_deref()),
+        )
+    }
+}
+
+const API_KEY_COLUMNS: &str = "id, client_id, COALESCE(prefix, '') AS prefix, label, valid_to, last_used_at, scopes, work_list_ids, salt, key_hash";
+
+#[derive(FromRow)]
+struct TodoRow {
+    id: i64,
+    content: String,
+    completed: bool,
+    work_list_id: i64,
+    parent_id: Option<i64>,
+    due_date: Option<NaiveDate>,
+    due_at: Option<DateTime<Utc>>,
+    priority: i32,
+    position: i64,
+    recurrence: Option<String>,
+    series_id: Option<i64>,
+    occurrence: i32,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+    completed_at: Option<DateTime<Utc>>,
+    version: i64,
+}
+
+impl From<TodoRow> for Todo {
+    fn from(row: TodoRow) -> Self {
+        Todo {
+            id: row.id,
+            content: row.content,
+            completed: row.completed,
+            work_list_id: row.work_list_id,
+            parent_id: row.parent_id,
+            due_at: Due::from_columns(row.due_date, row.due_at),
+            priority: Priority::from_column(row.priority),
+            position: row.position,
+            created_at: row.created_at,
+            updated_at: row.updated_at,
+            completed_at: row.completed_at,
+            version: row.version,
+            tags: vec![],
+            recurrence: row.recurrence.and_then(|rule| rule.parse().ok()),
+            series_id: row.series_id,
+            occurrence: row.occurrence as u32,
+            subtasks: None,
+            progress: None,
+        }
+    }
+}
+
+#[derive(FromRow)]
+struct ReminderRow {
+    id: i64,
+    todo_id: i64,
+    remind_at: DateTime<Utc>,
+    sent_at: Option<DateTime<Utc>>,
+    failed_at: Option<DateTime<Utc>>,
+    attempts: i32,
+}
+
+impl From<ReminderRow> for Reminder {
+    fn from(row: ReminderRow) -> Self {
+        Reminder {
+            id: row.id,
+            todo_id: row.todo_id,
+            remind_at: row.remind_at,
+            sent_at: row.sent_at,
+            failed_at: row.failed_at,
+            attempts: row.attempts as u32,
+        }
+    }
+}
+
+#[derive(FromRow)]
+struct LeasedReminderRow {
+    id: i64,
+    todo_id: i64,
+    remind_at: DateTime<Utc>,
+    attempts: i32,
+    client_id: i64,
+}
+
+impl From<LeasedReminderRow> for (Reminder, i64) {
+    fn from(row: LeasedReminderRow) -> Self {
+        let reminder = ReminderRow {
+            id: row.id,
+            todo_id: row.todo_id,
+            remind_at: row.remind_at,
+            sent_at: None,
+            failed_at: None,
+            attempts: row.attempts,
+        };
+
+        (reminder.into(), row.client_id)
+    }
+}
+
+const REMINDER_COLUMNS: &str = "id, todo_id, remind_at, sent_at, failed_at, attempts";
+
+#[derive(FromRow)]
+struct WebhookRow {
+    id: i64,
+    url: String,
+    secret: String,
+    events: Option<String>,
+    created_at: DateTime<Utc>,
+}
+
+impl From<WebhookRow> for Webhook {
+    fn from(row: WebhookRow) -> Self {
+        Webhook {
+            id: row.id,
+            url: row.url,
+            secret: row.secret,
+            events: Webhook::events_from_column(row.events.as_deref()),
+            created_at: row.created_at,
+        }
+    }
+}
+
+const WEBHOOK_COLUMNS: &str = "id, url, secret, events, created_at";
+
+#[derive(FromRow)]
+struct WebhookDeliveryRow {
+    id: i64,
+    event_id: i64,
+    kind: String,
+    status: String,
+    attempts: i32,
+    response_status: Option<i32>,
+    error: Option<String>,
+    created_at: DateTime<Utc>,
+    last_attempt_at: Option<DateTime<Utc>>,
+    next_attempt_at: DateTime<Utc>,
+}
+
+impl WebhookDeliveryRow {
+    /// Deliveries of events unknown to this version are skipped.
+    fn into_delivery(self) -> Option<WebhookDelivery> {
+        let status = DeliveryStatus::from_column(&self.status);
+
+        Some(WebhookDelivery {
+            id: self.id,
+            event_id: self.event_id,
+            event: EventKind::parse(&self.kind)?,
+            status,
+            attempts: self.attempts as u32,
+            response_status: self.response_status.map(|status| status as u16),
+            error: self.error,
+            created_at: self.created_at,
+            last_attempt_at: self.last_attempt_at,
+            next_attempt_at: Some(self.next_attempt_at)
+                .filter(|_| status == DeliveryStatus::Pending),
+        })
+    }
+}
+
+#[derive(FromRow)]
+struct PendingDeliveryRow {
+    id: i64,
+    attempts: i32,
+    url: String,
+    secret: String,
+    event_id: i64,
+    client_id: i64,
+    kind: String,
+    data: String,
+    created_at: DateTime<Utc>,
+}
+
+impl PendingDeliveryRow {
+    fn into_delivery(self) -> Option<PendingDelivery> {
+        Some(PendingDelivery {
+            id: self.id,
+            attempts: self.attempts as u32,
+            url: self.url,
+            secret: self.secret,
+            event: Event {
+                id: self.event_id,
+                client_id: self.client_id,
+                kind: EventKind::parse(&self.kind)?,
+                created_at: self.created_at,
+                data: serde_json::from_str(&self.data).unwrap_or_default(),
+            },
+        })
+    }
+}
+
+#[derive(FromRow)]
+struct EventRow {
+    id: i64,
+    client_id: i64,
+    kind: String,
+    data: String,
+    created_at: DateTime<Utc>,
+}
+
+impl EventRow {
+    /// Events unknown to this version are skipped.
+    fn into_event(self) -> Option<Event> {
+        Some(Event {
+            id: self.id,
+            client_id: self.client_id,
+            kind: EventKind::parse(&self.kind)?,
+            created_at: self.created_at,
+            data: serde_json::from_str(&self.data).unwrap_or_default(),
+        })
+    }
+}
+
+#[derive(FromRow)]
+struct TombstoneRow {
+    kind: String,
+    resource_id: i64,
+    work_list_id: Option<i64>,
+    version: i64,
+    deleted_at: DateTime<Utc>,
+}
+
+impl TombstoneRow {
+    /// Tombstones unknown to this version are skipped.
+    fn into_tombstone(self) -> Option<Tombstone> {
+        Some(Tombstone {
+            kind: TombstoneKind::parse(&self.kind)?,
+            id: self.resource_id,
+            work_list_id: self.work_list_id,
+            version: self.version,
+            deleted_at: self.deleted_at,
+        })
+    }
+}
+
+/// Records an event in the transaction of the write it describes and queues its delivery to
+/// webhooks of the client subscribed to it. Evaluates to the recorded event.
+macro_rules! record_event {
+    ($tx:ident, $event:expr) => {{
+        let event: NewEvent = $event;
+
+        let id: (i64,) = sqlx::query_as("INSERT INTO events (client_id, kind, data, created_at) VALUES ($1, $2, $3, $4) RETURNING id")
+            .bind(event.client_id)
+            .bind(event.kind.as_str())
+            .bind(event.data.to_string())
+            .bind(event.created_at)
+            .fetch_one(&mut $tx)
+            .await?;
+
+        // Subscribed events are stored space-separated, see `Webhook::events_column`.
+        sqlx::query("INSERT INTO webhook_deliveries (webhook_id, event_id, next_attempt_at) SELECT id, $1, $2 FROM webhooks WHERE client_id = $3 AND (events IS NULL OR $4 = ANY(string_to_array(events, ' ')))")
+            .bind(id.0)
+            .bind(event.created_at)
+            .bind(event.client_id)
+            .bind(event.kind.as_str())
+            .execute(&mut $tx)
+            .await?;
+
+        Event {
+            id: id.0,
+            client_id: event.client_id,
+            kind: event.kind,
+            created_at: event.created_at,
+            data: event.data,
+        }
+    }};
+}
+
+/// Updates a todo and replaces its tags in the transaction of the write, checking it is still at
+/// `expected_version` as described by `TodoRepository::update_todo`. Evaluates to the new version
+/// and position of the todo.
+macro_rules! update_todo_row {
+    ($tx:ident, $id:expr, $changes:expr, $expected_version:expr) => {{
+        let id: i64 = $id;
+        let changes: &TodoChanges = $changes;
+        let expected_version: Option<i64> = $expected_version;
+
+        // Positions at the end of a work list are taken one writer at a time.
+        if let Some(work_list_id) = changes.work_list_id {
+            sqlx::query("SELECT lock_client_changes(client_id) FROM work_lists WHERE id = $1")
+                .bind(work_list_id)
+                .execute(&mut $tx)
+                .await?;
+        }
+
+        let mut set_list = Vec::with_capacity(13);
+
+        if changes.content.is_some() {
+            set_list.push(format!("content = ${}", set_list.len() + 1));
+        }
+
+        if changes.completed.is_some() {
+            set_list.push(format!("completed = ${}", set_list.len() + 1));
+        }
+
+        if changes.due_at.is_some() {
+            set_list.push(format!("due_date = ${}", set_list.len() + 1));
+            set_list.push(format!("due_at = ${}", set_list.len() + 1));
+        }
+
+        if changes.priority.is_some() {
+            set_list.push(format!("priority = ${}", set_list.len() + 1));
+        }
+
+        if changes.work_list_id.is_some() {
+            set_list.push(format!("work_list_id = ${}", set_list.len() + 1));
+        }
+
+        if changes.parent_id.is_some() {
+            set_list.push(format!("parent_id = ${}", set_list.len() + 1));
+        }
+
+        // Moved todos go to the end of the target list.
+        if changes.work_list_id.is_some() {
+            set_list.push(format!(
+                "position = (SELECT COALESCE(MAX(position) + {}, 0) FROM todos WHERE work_list_id = ${})",
+                POSITION_STEP,
+                set_list.len() + 1
+            ));
+        } else if changes.position.is_some() {
+            set_list.push(format!("position = ${}", set_list.len() + 1));
+        }
+
+        if changes.recurrence.is_some() {
+            set_list.push(format!("recurrence = ${}", set_list.len() + 1));
+        }
+
+        if changes.series_id.is_some() {
+            set_list.push(format!("series_id = ${}", set_list.len() + 1));
+        }
+
+        if changes.occurrence.is_some() {
+            set_list.push(format!("occurrence = ${}", set_list.len() + 1));
+        }
+
+        if changes.updated_at.is_some() {
+            set_list.push(format!("updated_at = ${}", set_list.len() + 1));
+        }
+
+        if changes.completed_at.is_some() {
+            set_list.push(format!("completed_at = ${}", set_list.len() + 1));
+        }
+
+        let sql = if set_list.is_empty() {
+            "SELECT version, position FROM todos WHERE id = $1 AND ($2::BIGINT IS NULL OR version = $2)"
+                .to_string()
+        } else {
+            format!(
+                "UPDATE todos SET {} WHERE id = ${id} AND (${version}::BIGINT IS NULL OR version = ${version}) RETURNING version, position",
+                set_list.join(", "),
+                id = set_list.len() + 1,
+                version = set_list.len() + 2
+            )
+        };
+        let mut q = sqlx::query_as::<_, (i64, i64)>(&sql);
+
+        if let Some(content) = changes.content.as_deref() {
+            q = q.bind(content);
+        }
+
+        if let Some(completed) = changes.completed {
+            q = q.bind(completed);
+        }
+
+        if let Some(due_at) = changes.due_at {
+            q = q
+                .bind(due_at.and_then(Due::date))
+                .bind(due_at.and_then(Due::time));
+        }
+
+        if let Some(priority) = changes.priority {
+            q = q.bind(priority.to_column());
+        }
+
+        if let Some(work_list_id) = changes.work_list_id {
+            q = q.bind(work_list_id);
+        }
+
+        if let Some(parent_id) = changes.parent_id {
+            q = q.bind(parent_id);
+        }
+
+        if let Some(work_list_id) = changes.work_list_id {
+            q = q.bind(work_list_id);
+        } else if let Some(position) = changes.position {
+            q = q.bind(position);
+        }
+
+        if let Some(recurrence) = changes.recurrence.as_ref() {
+            q = q.bind(recurrence.as_ref().map(|rule| rule.to_string()));
+        }
+
+        if let Some(series_id) = changes.series_id {
+            q = q.bind(series_id);
+        }
+
+        if let Some(occurrence) = changes.occurrence {
+            q = q.bind(occurrence as i32);
+        }
+
+        if let Some(updated_at) = changes.updated_at {
+            q = q.bind(updated_at);
+        }
+
+        if let Some(completed_at) = changes.completed_at {
+            q = q.bind(completed_at);
+        }
+
+        let stored = match q
+            .bind(id)
+            .bind(expected_version)
+            .fetch_optional(&mut $tx)
+            .await?
+        {
+            Some(stored) => stored,
+            None => {
+                let exists: (bool,) =
+                    sqlx::query_as("SELECT EXISTS (SELECT 1 FROM todos WHERE id = $1)")
+                        .bind(id)
+                        .fetch_one(&mut $tx)
+                        .await?;
+
+                return Err(if exists.0 {
+                    WebError::PreconditionFailed
+                } else {
+                    WebError::NotFound(Resource::Todo)
+                });
+            }
+        };
+
+        if let Some(tags) = changes.tags.as_ref() {
+            let tag_ids: Vec<i64> = tags.iter().map(|tag| tag.id).collect();
+
+            sqlx::query("DELETE FROM todo_tags WHERE todo_id = $1")
+                .bind(id)
+                .execute(&mut $tx)
+                .await?;
+            sqlx::query("INSERT INTO todo_tags (todo_id, tag_id) SELECT $1, UNNEST($2::BIGINT[])")
+                .bind(id)
+                .bind(&tag_ids)
+                .execute(&mut $tx)
+                .await?;
+        }
+
+        stored
+    }};
+}
+
+/// Inserts a todo with its tag assignments at the end of its work list in the transaction of the
+/// write. Evaluates to the todo as stored.
+macro_rules! insert_todo {
+    ($tx:ident, $todo:expr) => {{
+        let todo: &NewTodo = $todo;
+
+        // Positions at the end of a work list are taken one writer at a time.
+        sqlx::query("SELECT lock_client_changes(client_id) FROM work_lists WHERE id = $1")
+            .bind(todo.work_list_id)
+            .execute(&mut $tx)
+            .await?;
+
+        let stored: (i64, i64, i64) = sqlx::query_as(
+            "INSERT INTO todos (content, completed, work_list_id, parent_id, due_date, due_at, priority, position, recurrence, series_id, occurrence, created_at, updated_at) VALUES ($1, false, $2, $3, $4, $5, $6, (SELECT COALESCE(MAX(position) + $7, 0) FROM todos WHERE work_list_id = $2), $8, $9, $10, $11, $11) RETURNING id, version, position",
+        )
+        .bind(todo.content.as_str())
+        .bind(todo.work_list_id)
+        .bind(todo.parent_id)
+        .bind(todo.due_at.and_then(Due::date))
+        .bind(todo.due_at.and_then(Due::time))
+        .bind(todo.priority.to_column())
+        .bind(POSITION_STEP)
+        .bind(todo.recurrence.as_ref().map(|rule| rule.to_string()))
+        .bind(todo.series_id)
+        .bind(todo.occurrence as i32)
+        .bind(todo.created_at)
+        .fetch_one(&mut $tx)
+        .await?;
+        let (id, mut version, position) = stored;
+
+        // A new series is identified by its first todo.
+        if todo.series_id(id) != todo.series_id {
+            let updated: (i64,) =
+                sqlx::query_as("UPDATE todos SET series_id = id WHERE id = $1 RETURNING version")
+                    .bind(id)
+                    .fetch_one(&mut $tx)
+                    .await?;
+            version = updated.0;
+        }
+
+        let tag_ids: Vec<i64> = todo.tags.iter().map(|tag| tag.id).collect();
+        sqlx::query("INSERT INTO todo_tags (todo_id, tag_id) SELECT $1, UNNEST($2::BIGINT[])")
+            .bind(id)
+            .bind(&tag_ids)
+            .execute(&mut $tx)
+            .await?;
+
+        if let Some(mutation_id) = todo.mutation_id.as_deref() {
+            sqlx::query("INSERT INTO applied_mutations (client_id, mutation_id, kind, resource_id, applied_at) SELECT client_id, $1, 'todo', $2, $3 FROM work_lists WHERE id = $4")
+                .bind(mutation_id)
+                .bind(id)
+                .bind(todo.created_at)
+                .bind(todo.work_list_id)
+                .execute(&mut $tx)
+                .await?;
+        }
+
+        todo.clone().into_todo(id, version, position)
+    }};
+}
+
+#[async_trait]
+impl Migrator for PgRepository {
+    fn migrations(&self) -> &'static [Migration] {
+        migrations::POSTGRES
+    }
+
+    async fn applied_versions(&self) -> Result<Vec<i64>> {
+        let mut tx = self.pool.begin().await?;
+
+        // Concurrent `CREATE TABLE IF NOT EXISTS` may fail, see `apply`.
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtext('schema_migrations'))")
+            .execute(&mut tx)
+            .await?;
+        sqlx::query("CREATE TABLE IF NOT EXISTS schema_migrations (version BIGINT PRIMARY KEY NOT NULL, name TEXT NOT NULL, applied_at TIMESTAMP WITH TIME ZONE NOT NULL DEFAULT NOW())")
+            .execute(&mut tx)
+            .await?;
+
+        let rows: Vec<(i64,)> =
+            sqlx::query_as("SELECT version FROM schema_migrations ORDER BY version")
+                .fetch_all(&mut tx)
+                .await?;
+
+        tx.commit().await?;
+        Ok(rows.into_iter().map(|(version,)| version).collect())
+    }
+
+    async fn apply(&self, migration: &Migration) -> Result<bool> {
+        let mut tx = self.pool.begin().await?;
+
+        // Processes starting at the same time migrate one at a time. Whoever comes second finds
+        // the migration recorded.
+        sqlx::query("SELECT pg_advisory_xact_lock(hashtext('schema_migrations'))")
+            .execute(&mut tx)
+            .await?;
+
+        let recorded: u64 = sqlx::query(
+            "INSERT INTO schema_migrations (version, name) VALUES ($1, $2) ON CONFLICT (version) DO NOTHING",
+        )
+        .bind(migration.version)
+        .bind(migration.name)
+        .execute(&mut tx)
+        .await?;
+
+        if recorded == 0 {
+            return Ok(false);
+        }
+
+        tx.execute(migration.sql).await?;
+        tx.commit().await?;
+        Ok(true)
+    }
+}
+
+#[async_trait]
+impl ClientRepository for PgRepository {
+    async fn find_client(&self, id: i64) -> Result<Option<Client>, WebError> {
+        let row: Option<(i64, String, String)> =
+            sqlx::query_as("SELECT id, display_name, time_zone FROM clients WHERE id = $1")
+                .bind(id)
+                .fetch_optional(&self.pool)
+                .await?;
+
+        Ok(row.map(|(id, display_name, time_zone)| Client::new(id, display_name, &time_zone)))
+    }
+
+    async fn find_api_keys_by_prefix(
+        &self,
+        prefix: &str,
+    ) -> Result<Vec<(ApiKey, KeyHash)>, WebError> {
+        let sql = format!(
+            "SELECT {} FROM client_api_keys WHERE prefix = $1 AND key_hash IS NOT NULL",
+            API_KEY_COLUMNS
+        );
+        let rows: Vec<ApiKeyRow> = sqlx::query_as(&sql)
+            .bind(prefix)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| row.key_hash().map(|key_hash| (row.into(), key_hash)))
+            .collect())
+    }
+
+    async fn touch_api_key(&self, id: i64) -> Result<(), WebError> {
+        sqlx::query("UPDATE client_api_keys SET last_used_at = NOW() WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn create_client(&self, display_name: &str) -> Result<Client, WebError> {
+        let id: (i64,) =
+            sqlx::query_as("INSERT INTO clients (display_name) VALUES ($1) RETURNING id")
+                .bind(display_name)
+                .fetch_one(&self.pool)
+                .await?;
+
+        Ok(Client::new(id.0, display_name.to_owned(), "UTC"))
+    }
+
+    async fn list_clients(&self) -> Result<Vec<Client>, WebError> {
+        let rows: Vec<(i64, String, String)> =
+            sqlx::query_as("SELECT id, display_name, time_zone FROM clients ORDER BY id")
+                .fetch_all(&self.pool)
+                .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(id, display_name, time_zone)| Client::new(id, display_name, &time_zone))
+            .collect())
+    }
+
+    async fn rename_client(&self, id: i64, display_name: &str) -> Result<bool, WebError> {
+        let rows_affected: u64 = sqlx::query("UPDATE clients SET display_name = $1 WHERE id = $2")
+            .bind(display_name)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(rows_affected > 0)
+    }
+
+    async fn set_client_time_zone(&self, id: i64, time_zone: &str) -> Result<bool, WebError> {
+        let rows_affected: u64 = sqlx::query("UPDATE clients SET time_zone = $1 WHERE id = $2")
+            .bind(time_zone)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(rows_affected > 0)
+    }
+
+    async fn delete_client(&self, id: i64) -> Result<bool, WebError> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query(
+            "DELETE FROM todo_tags WHERE tag_id IN (SELECT id FROM tags WHERE client_id = $1)",
+        )
+        .bind(id)
+        .execute(&mut tx)
+        .await?;
+        sqlx::query("DELETE FROM tags WHERE client_id = $1")
+            .bind(id)
+            .execute(&mut tx)
+            .await?;
+        sqlx::query("DELETE FROM webhooks WHERE client_id = $1")
+            .bind(id)
+            .execute(&mut tx)
+            .await?;
+        sqlx::query("DELETE FROM events WHERE client_id = $1")
+            .bind(id)
+            .execute(&mut tx)
+            .await?;
+        sqlx::query("DELETE FROM tombstones WHERE client_id = $1")
+            .bind(id)
+            .execute(&mut tx)
+            .await?;
+        sqlx::query("DELETE FROM applied_mutations WHERE client_id = $1")
+            .bind(id)
+            .execute(&mut tx)
+            .await?;
+        sqlx::query("DELETE FROM todos WHERE work_list_id IN (SELECT id FROM work_lists WHERE client_id = $1)")
+            .bind(id)
+            .execute(&mut tx)
+            .await?;
+        sqlx::query("DELETE FROM work_lists WHERE client_id = $1")
+            .bind(id)
+            .execute(&mut tx)
+            .await?;
+        sqlx::query("DELETE FROM client_api_keys WHERE client_id = $1")
+            .bind(id)
+            .execute(&mut tx)
+            .await?;
+        let rows_affected: u64 = sqlx::query("DELETE FROM clients WHERE id = $1")
+            .bind(id)
+            .execute(&mut tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(rows_affected > 0)
+    }
+
+    async fn create_api_key(
+        &self,
+        client_id: i64,
+        label: Option<&str>,
+        prefix: &str,
+        key_hash: &KeyHash,
+        valid_to: DateTime<Utc>,
+        permissions: &Permissions,
+    ) -> Result<ApiKey, WebError> {
+        let id: (i64,) = sqlx::query_as(
+            "INSERT INTO client_api_keys (valid_to, prefix, label, salt, key_hash, scopes, work_list_ids, client_id) VALUES ($1, $2, $3, $4, $5, $6, $7, $8) RETURNING id",
+        )
+        .bind(valid_to)
+        .bind(prefix)
+        .bind(label)
+        .bind(&key_hash.salt)
+        .bind(&key_hash.hash)
+        .bind(permissions.scopes_column())
+        .bind(permissions.work_list_ids_column())
+        .bind(client_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(ApiKey::new(
+            id.0,
+            client_id,
+            prefix.to_owned(),
+            label.map(str::to_owned),
+            valid_to,
+            None,
+            permissions.clone(),
+        ))
+    }
+
+    async fn find_api_key(&self, id: i64) -> Result<Option<ApiKey>, WebError> {
+        let sql = format!(
+            "SELECT {} FROM client_api_keys WHERE id = $1",
+            API_KEY_COLUMNS
+        );
+        let row: Option<ApiKeyRow> = sqlx::query_as(&sql)
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row.map(ApiKey::from))
+    }
+
+    async fn list_api_keys(&self, client_id: i64) -> Result<Vec<ApiKey>, WebError> {
+        let sql = format!(
+            "SELECT {} FROM client_api_keys WHERE client_id = $1 ORDER BY id",
+            API_KEY_COLUMNS
+        );
+        let rows: Vec<ApiKeyRow> = sqlx::query_as(&sql)
+            .bind(client_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows.into_iter().map(ApiKey::from).collect())
+    }
+
+    async fn revoke_api_key(&self, id: i64) -> Result<bool, WebError> {
+        let rows_affected: u64 = sqlx::query("DELETE FROM client_api_keys WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(rows_affected > 0)
+    }
+
+    async fn plaintext_api_keys(&self) -> Result<Vec<(i64, String)>, WebError> {
+        sqlx::query_as("SELECT id, key FROM client_api_keys WHERE key IS NOT NULL")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|err| err.into())
+    }
+
+    async fn hash_plaintext_api_key(
+        &self,
+        id: i64,
+        prefix: &str,
+        key_hash: &KeyHash,
+    ) -> Result<(), WebError> {
+        sqlx::query("UPDATE client_api_keys SET prefix = $1, salt = $2, key_hash = $3, key = NULL WHERE id = $4")
+            .bind(prefix)
+            .bind(&key_hash.salt)
+            .bind(&key_hash.hash)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl WorkListRepository for PgRepository {
+    async fn work_list_exists(&self, id: i64, client_id: i64) -> Result<bool, WebError> {
+        let result: (bool,) = sqlx::query_as(
+            "SELECT EXISTS (SELECT 1 FROM work_lists WHERE id = $1 AND client_id = $2)",
+        )
+        .bind(id)
+        .bind(client_id)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(result.0)
+    }
+
+    async fn create_work_list(
+        &self,
+        name: &str,
+        client_id: i64,
+        created_at: DateTime<Utc>,
+        mutation_id: Option<&str>,
+        event: &DescribeEvent<'_>,
+    ) -> Result<(WorkList, Event), WebError> {
+        let mut tx = self.pool.begin().await?;
+
+        let row: (i64, i64) = sqlx::query_as(
+            "INSERT INTO work_lists (name, client_id, created_at) VALUES ($1, $2, $3) RETURNING id, version",
+        )
+        .bind(name)
+        .bind(client_id)
+        .bind(created_at)
+        .fetch_one(&mut tx)
+        .await?;
+
+        if let Some(mutation_id) = mutation_id {
+            sqlx::query("INSERT INTO applied_mutations (client_id, mutation_id, kind, resource_id, applied_at) VALUES ($1, $2, 'work_list', $3, $4)")
+                .bind(client_id)
+                .bind(mutation_id)
+                .bind(row.0)
+                .bind(created_at)
+                .execute(&mut tx)
+                .await?;
+        }
+
+        let event = record_event!(tx, event(row.0, row.1));
+        tx.commit().await?;
+
+        let work_list = WorkList::new(row.0, name.to_owned(), created_at, row.1, vec![]);
+        Ok((work_list, event))
+    }
+
+    async fn list_work_lists(
+        &self,
+        client_id: i64,
+        query: &WorkListQuery,
+    ) -> Result<Vec<WorkList>, WebError> {
+        use WorkListSort::*;
+
+        let mut conditions = vec!["client_id = $1".to_string()];
+        let mut arg = 1;
+        let mut next_arg = || {
+            arg += 1;
+            format!("${}", arg)
+        };
+
+        if query.name.is_some() {
+            conditions.push(format!("name ILIKE {} ESCAPE '\\'", next_arg()));
+        }
+
+        if query.ids.is_some() {
+            conditions.push(format!("id = ANY({})", next_arg()));
+        }
+
+        if query.tag.is_some() {
+            conditions.push(format!("EXISTS (SELECT 1 FROM todos JOIN todo_tags ON todo_tags.todo_id = todos.id JOIN tags ON tags.id = todo_tags.tag_id WHERE todos.work_list_id = work_lists.id AND tags.name = {})", next_arg()));
+        }
+
+        let (direction, comparison) = if query.sort.is_descending() {
+            ("DESC", "<")
+        } else {
+            ("ASC", ">")
+        };
+        let column = match query.sort {
+            IdAsc | IdDesc => None,
+            NameAsc | NameDesc => Some("name"),
+            CreatedAtAsc | CreatedAtDesc => Some("created_at"),
+        };
+
+        if query.after.is_some() {
+            conditions.push(match column {
+                Some(column) => format!(
+                    "({column} {cmp} {value} OR ({column} = {value} AND id {cmp} {id}))",
+                    column = column,
+                    cmp = comparison,
+                    value = next_arg(),
+                    id = next_arg()
+                ),
+                None => format!("id {} {}", comparison, next_arg()),
+            });
+        }
+
+        let order = match column {
+            Some(column) => format!("{} {}, id {}", column, direction, direction),
+            None => format!("id {}", direction),
+        };
+        let sql = format!(
+            "SELECT id, name, created_at, version FROM work_lists WHERE {} ORDER BY {} LIMIT {}",
+            conditions.join(" AND "),
+            order,
+            next_arg()
+        );
+
+        let mut rows = sqlx::query_as::<_, (i64, String, DateTime<Utc>, i64)>(&sql).bind(client_id);
+
+        if let Some(name) = query.name.as_deref() {
+            rows = rows.bind(like_pattern(name));
+        }
+
+        if let Some(ids) = query.ids.as_ref() {
+            rows = rows.bind(ids.clone());
+        }
+
+        if let Some(tag) = query.tag.as_deref() {
+            rows = rows.bind(tag);
+        }
+
+        if let Some(after) = query.after.as_ref() {
+            rows = match query.sort {
+                IdAsc | IdDesc => rows,
+                NameAsc | NameDesc => rows.bind(after.name.clone()),
+                CreatedAtAsc | CreatedAtDesc => rows.bind(after.created_at),
+            }
+            .bind(after.id);
+        }
+
+        let rows = rows.bind(query.limit as i64).fetch_all(&self.pool).await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(id, name, created_at, version)| {
+                WorkList::new(id, name, created_at, version, vec![])
+            })
+            .collect())
+    }
+
+    async fn find_work_list(&self, id: i64, client_id: i64) -> Result<Option<WorkList>, WebError> {
+        let row: Option<(i64, String, DateTime<Utc>, i64)> = sqlx::query_as(
+            "SELECT id, name, created_at, version FROM work_lists WHERE work_lists.id = $1 AND work_lists.client_id = $2",
+        )
+        .bind(id)
+        .bind(client_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|(id, name, created_at, version)| {
+            WorkList::new(id, name, created_at, version, vec![])
+        }))
+    }
+
+    async fn update_work_list(
+        &self,
+        id: i64,
+        client_id: i64,
+        name: &str,
+        expected_version: Option<i64>,
+        event: &DescribeEvent<'_>,
+    ) -> Result<Option<(i64, Event)>, WebError> {
+        let mut tx = self.pool.begin().await?;
+
+        let version: Option<(i64,)> = sqlx::query_as("UPDATE work_lists SET name = $1 WHERE id = $2 AND client_id = $3 AND ($4::BIGINT IS NULL OR version = $4) RETURNING version")
+            .bind(name)
+            .bind(id)
+            .bind(client_id)
+            .bind(expected_version)
+            .fetch_optional(&mut tx)
+            .await?;
+
+        let version = match version {
+            Some(version) => version.0,
+            None => {
+                let exists: (bool,) = sqlx::query_as(
+                    "SELECT EXISTS (SELECT 1 FROM work_lists WHERE id = $1 AND client_id = $2)",
+                )
+                .bind(id)
+                .bind(client_id)
+                .fetch_one(&mut tx)
+                .await?;
+
+                return if exists.0 {
+                    Err(WebError::PreconditionFailed)
+                } else {
+                    Ok(None)
+                };
+            }
+        };
+
+        let event = record_event!(tx, event(id, version));
+        tx.commit().await?;
+
+        Ok(Some((version, event)))
+    }
+
+    async fn delete_work_list(
+        &self,
+        id: i64,
+        client_id: i64,
+        expected_version: Option<i64>,
+        event: &DescribeEvent<'_>,
+    ) -> Result<Option<Event>, WebError> {
+        let mut tx = self.pool.begin().await?;
+
+        let version: Option<(i64,)> =
+            sqlx::query_as("SELECT version FROM work_lists WHERE id = $1 AND client_id = $2")
+                .bind(id)
+                .bind(client_id)
+                .fetch_optional(&mut tx)
+                .await?;
+
+        match version {
+            None => return Ok(None),
+            Some(version) if is_modified(version.0, expected_version) => {
+                return Err(WebError::PreconditionFailed)
+            }
+            Some(_) => {}
+        }
+
+        // Tag assignments and reminders of todos are removed by foreign keys.
+        sqlx::query("DELETE FROM todos WHERE work_list_id IN (SELECT id FROM work_lists WHERE id = $1 AND client_id = $2)")
+            .bind(id)
+            .bind(client_id)
+            .execute(&mut tx)
+            .await?;
+
+        // The version is checked again in case the work list changed since it was read. Deleted
+        // todos are restored by the rollback.
+        let rows_affected: u64 = sqlx::query("DELETE FROM work_lists WHERE id = $1 AND client_id = $2 AND ($3::BIGINT IS NULL OR version = $3)")
+            .bind(id)
+            .bind(client_id)
+            .bind(expected_version)
+            .execute(&mut tx)
+            .await?;
+
+        if rows_affected == 0 {
+            return Err(WebError::PreconditionFailed);
+        }
+
+        let version: (i64,) = sqlx::query_as("INSERT INTO tombstones (client_id, kind, resource_id, deleted_at) VALUES ($1, 'work_list', $2, $3) RETURNING version")
+            .bind(client_id)
+            .bind(id)
+            .bind(Utc::now())
+            .fetch_one(&mut tx)
+            .await?;
+
+        let event = record_event!(tx, event(id, version.0));
+        tx.commit().await?;
+
+        Ok(Some(event))
+    }
+}
+
+#[async_trait]
+impl TodoRepository for PgRepository {
+    async fn find_todo(&self, id: i64, client_id: i64) -> Result<Option<Todo>, WebError> {
+        let row: Option<TodoRow> = sqlx::query_as("SELECT todos.* FROM todos JOIN work_lists ON work_lists.id = todos.work_list_id WHERE todos.id = $1 AND work_lists.client_id = $2")
+            .bind(id)
+            .bind(client_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row.map(|row| row.into()))
+    }
+
+    async fn todos_in_work_lists(
+        &self,
+        work_list_ids: &[i64],
+        per_list: Option<u32>,
+    ) -> Result<Vec<Todo>, WebError> {
+        if work_list_ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let rows: Vec<TodoRow> = match per_list {
+            Some(per_list) => sqlx::query_as(
+                "SELECT * FROM (SELECT todos.*, ROW_NUMBER() OVER (PARTITION BY work_list_id ORDER BY position, id) AS todo_rank FROM todos WHERE todos.work_list_id = ANY($1)) ranked WHERE todo_rank <= $2 ORDER BY position, id",
+            )
+            .bind(work_list_ids)
+            .bind(per_list as i64)
+            .fetch_all(&self.pool)
+            .await,
+            None => sqlx::query_as("SELECT * FROM todos WHERE todos.work_list_id = ANY($1) ORDER BY position, id")
+                .bind(work_list_ids)
+                .fetch_all(&self.pool)
+                .await,
+        }?;
+
+        Ok(rows.into_iter().map(|row| row.into()).collect())
+    }
+
+    async fn search_todos(&self, client_id: i64, query: &TodoQuery) -> Result<Vec<Todo>, WebError> {
+        use TodoSort::*;
+
+        let mut conditions = vec!["work_lists.client_id = $1".to_string()];
+        let mut arg = 1;
+        let mut next_arg = || {
+            arg += 1;
+            format!("${}", arg)
+        };
+
+        if query.completed.is_some() {
+            conditions.push(format!("todos.completed = {}", next_arg()));
+        }
+
+        if query.work_list_ids.is_some() {
+            conditions.push(format!("todos.work_list_id = ANY({})", next_arg()));
+        }
+
+        if query.content.is_some() {
+            conditions.push(format!("todos.content ILIKE {} ESCAPE '\\'", next_arg()));
+        }
+
+        match query.due {
+            Some(DueCondition::Within { .. }) => conditions.push(format!(
+                "(todos.due_date = {} OR (todos.due_at >= {} AND todos.due_at < {}))",
+                next_arg(),
+                next_arg(),
+                next_arg()
+            )),
+            Some(DueCondition::Before { .. }) => conditions.push(format!(
+                "NOT todos.completed AND (todos.due_date < {} OR todos.due_at < {})",
+                next_arg(),
+                next_arg()
+            )),
+            None => {}
+        }
+
+        if query.tag.is_some() {
+            conditions.push(format!("EXISTS (SELECT 1 FROM todo_tags JOIN tags ON tags.id = todo_tags.tag_id WHERE todo_tags.todo_id = todos.id AND tags.name = {})", next_arg()));
+        }
+
+        let (direction, comparison) = if query.sort.is_descending() {
+            ("DESC", "<")
+        } else {
+            ("ASC", ">")
+        };
+
+        if query.after.is_some() {
+            conditions.push(match query.sort {
+                ContentAsc | ContentDesc => format!(
+                    "(todos.content {cmp} {value} OR (todos.content = {value} AND todos.id {cmp} {id}))",
+                    cmp = comparison,
+                    value = next_arg(),
+                    id = next_arg()
+                ),
+                IdAsc | IdDesc => format!("todos.id {} {}", comparison, next_arg()),
+            });
+        }
+
+        let order = match query.sort {
+            ContentAsc | ContentDesc => {
+                format!("todos.content {}, todos.id {}", direction, direction)
+            }
+            IdAsc | IdDesc => format!("todos.id {}", direction),
+        };
+        let sql = format!(
+            "SELECT todos.* FROM todos JOIN work_lists ON work_lists.id = todos.work_list_id WHERE {} ORDER BY {} LIMIT {}",
+            conditions.join(" AND "),
+            order,
+            next_arg()
+        );
+
+        let mut rows = sqlx::query_as::<_, TodoRow>(&sql).bind(client_id);
+
+        if let Some(completed) = query.completed {
+            rows = rows.bind(completed);
+        }
+
+        if let Some(ids) = query.work_list_ids.as_ref() {
+            rows = rows.bind(ids.clone());
+        }
+
+        if let Some(content) = query.content.as_deref() {
+            rows = rows.bind(like_pattern(content));
+        }
+
+        match query.due {
+            Some(DueCondition::Within { date, from, to }) => {
+                rows = rows.bind(date).bind(from).bind(to);
+            }
+            Some(DueCondition::Before { date, time }) => {
+                rows = rows.bind(date).bind(time);
+            }
+            None => {}
+        }
+
+        if let Some(tag) = query.tag.as_deref() {
+            rows = rows.bind(tag);
+        }
+
+        if let Some(after) = query.after.as_ref() {
+            rows = match query.sort {
+                ContentAsc | ContentDesc => rows.bind(after.content.clone()),
+                IdAsc | IdDesc => rows,
+            }
+            .bind(after.id);
+        }
+
+        let rows = rows.bind(query.limit as i64).fetch_all(&self.pool).await?;
+
+        Ok(rows.into_iter().map(|row| row.into()).collect())
+    }
+
+    async fn create_todo(
+        &self,
+        todo: &NewTodo,
+        event: &DescribeTodo<'_>,
+    ) -> Result<(Todo, Event), WebError> {
+        let mut tx = self.pool.begin().await?;
+        let todo = insert_todo!(tx, todo);
+        let event = record_event!(tx, event(EventKind::TodoCreated, &todo));
+        tx.commit().await?;
+
+        Ok((todo, event))
+    }
+
+    async fn update_todo(
+        &self,
+        id: i64,
+        changes: &TodoChanges,
+        expected_version: Option<i64>,
+        event: &DescribeEvent<'_>,
+    ) -> Result<(i64, Event), WebError> {
+        let mut tx = self.pool.begin().await?;
+        let (version, _) = update_todo_row!(tx, id, changes, expected_version);
+        let event = record_event!(tx, event(id, version));
+        tx.commit().await?;
+
+        Ok((version, event))
+    }
+
+    async fn update_todo_cascade(
+        &self,
+        cascade: &TodoCascade,
+        expected_version: Option<i64>,
+        event: &DescribeTodo<'_>,
+    ) -> Result<(Todo, Vec<Event>), WebError> {
+        let mut tx = self.pool.begin().await?;
+
+        let write = &cascade.todo;
+        let (version, position) =
+            update_todo_row!(tx, write.todo.id, &write.changes, expected_version);
+        let todo = write.stored(version, position);
+        let mut events = vec![record_event!(tx, event(write.kind, &todo))];
+
+        // Subtasks follow their todo, they are not checked.
+        for write in cascade.subtasks.iter() {
+            let (version, position) = update_todo_row!(tx, write.todo.id, &write.changes, None);
+            let todo = write.stored(version, position);
+            events.push(record_event!(tx, event(write.kind, &todo)));
+        }
+
+        if let Some(next) = cascade.next_occurrence.as_ref() {
+            let next = insert_todo!(tx, next);
+
+            sqlx::query("INSERT INTO reminders (todo_id, remind_at) SELECT $1, remind_at + $2::BIGINT * INTERVAL '1 second' FROM reminders WHERE todo_id = $3")
+                .bind(next.id)
+                .bind(cascade.reminder_offset.num_seconds())
+                .bind(cascade.todo.todo.id)
+                .execute(&mut tx)
+                .await?;
+
+            events.push(record_event!(tx, event(EventKind::TodoCreated, &next)));
+        }
+
+        tx.commit().await?;
+
+        Ok((todo, events))
+    }
+
+    async fn delete_todo(
+        &self,
+        id: i64,
+        expected_version: Option<i64>,
+        event: &DescribeEvent<'_>,
+    ) -> Result<Vec<Event>, WebError> {
+        let mut tx = self.pool.begin().await?;
+
+        let version: (i64,) = sqlx::query_as("SELECT version FROM todos WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&mut tx)
+            .await?
+            .ok_or(WebError::NotFound(Resource::Todo))?;
+
+        if is_modified(version.0, expected_version) {
+            return Err(WebError::PreconditionFailed);
+        }
+
+        let subtasks: Vec<(i64,)> = sqlx::query_as("WITH RECURSIVE subtree(id) AS (SELECT id FROM todos WHERE parent_id = $1 UNION ALL SELECT todos.id FROM todos JOIN subtree ON todos.parent_id = subtree.id) SELECT todos.id FROM todos JOIN subtree ON subtree.id = todos.id ORDER BY todos.position, todos.id")
+            .bind(id)
+            .fetch_all(&mut tx)
+            .await?;
+        let ids: Vec<i64> = subtasks
+            .into_iter()
+            .map(|subtask| subtask.0)
+            .chain(Some(id))
+            .collect();
+
+        // Tombstones are left before anything is deleted, subtasks go away with their todo.
+        let mut tombstones = Vec::with_capacity(ids.len());
+        for todo_id in ids.iter() {
+            let version: (i64,) = sqlx::query_as("INSERT INTO tombstones (client_id, kind, resource_id, work_list_id, deleted_at) SELECT work_lists.client_id, 'todo', todos.id, todos.work_list_id, $2 FROM todos JOIN work_lists ON work_lists.id = todos.work_list_id WHERE todos.id = $1 RETURNING version")
+                .bind(todo_id)
+                .bind(Utc::now())
+                .fetch_one(&mut tx)
+                .await?;
+            tombstones.push((*todo_id, version.0));
+        }
+
+        // The version is checked again in case the todo changed since it was read. Subtasks, tag
+        // assignments and reminders are removed by foreign keys.
+        let rows_affected: u64 =
+            sqlx::query("DELETE FROM todos WHERE id = $1 AND ($2::BIGINT IS NULL OR version = $2)")
+                .bind(id)
+                .bind(expected_version)
+                .execute(&mut tx)
+                .await?;
+
+        if rows_affected == 0 {
+            return Err(WebError::PreconditionFailed);
+        }
+
+        let mut events = Vec::with_capacity(tombstones.len());
+        for (todo_id, version) in tombstones {
+            events.push(record_event!(tx, event(todo_id, version)));
+        }
+
+        tx.commit().await?;
+        Ok(events)
+    }
+
+    async fn find_subtasks(&self, id: i64) -> Result<Vec<Todo>, WebError> {
+        let rows: Vec<TodoRow> = sqlx::query_as("WITH RECURSIVE subtree(id) AS (SELECT id FROM todos WHERE parent_id = $1 UNION ALL SELECT todos.id FROM todos JOIN subtree ON todos.parent_id = subtree.id) SELECT todos.* FROM todos JOIN subtree ON subtree.id = todos.id ORDER BY todos.position, todos.id")
+            .bind(id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows.into_iter().map(|row| row.into()).collect())
+    }
+
+    async fn adjacent_todo_position(
+        &self,
+        work_list_id: i64,
+        position: i64,
+        after: bool,
+        exclude_id: i64,
+    ) -> Result<Option<i64>, WebError> {
+        let sql = if after {
+            "SELECT MIN(position) FROM todos WHERE work_list_id = $1 AND position > $2 AND id != $3"
+        } else {
+            "SELECT MAX(position) FROM todos WHERE work_list_id = $1 AND position < $2 AND id != $3"
+        };
+
+        let row: (Option<i64>,) = sqlx::query_as(sql)
+            .bind(work_list_id)
+            .bind(position)
+            .bind(exclude_id)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(row.0)
+    }
+
+    async fn move_todos(
+        &self,
+        moves: &[(i64, i64, i64)],
+        event: &DescribeEvent<'_>,
+    ) -> Result<Vec<Event>, WebError> {
+        let mut tx = self.pool.begin().await?;
+
+        let mut events = Vec::with_capacity(moves.len());
+        for &(id, position, expected_version) in moves {
+            let version: (i64,) = sqlx::query_as(
+                "UPDATE todos SET position = $1 WHERE id = $2 AND version = $3 RETURNING version",
+            )
+            .bind(position)
+            .bind(id)
+            .bind(expected_version)
+            .fetch_optional(&mut tx)
+            .await?
+            .ok_or(WebError::PreconditionFailed)?;
+
+            events.push(record_event!(tx, event(id, version.0)));
+        }
+
+        tx.commit().await?;
+
+        Ok(events)
+    }
+}
+
+#[async_trait]
+impl TagRepository for PgRepository {
+    async fn list_tags(
+        &self,
+        client_id: i64,
+        work_list_ids: Option<&[i64]>,
+    ) -> Result<Vec<Tag>, WebError> {
+        let work_list_filter = match work_list_ids {
+            Some(_) => " AND todos.work_list_id = ANY($2)",
+            None => "",
+        };
+        let sql = format!(
+            "SELECT tags.id, tags.name, tags.color, COUNT(todos.id) FROM tags LEFT JOIN todo_tags ON todo_tags.tag_id = tags.id LEFT JOIN todos ON todos.id = todo_tags.todo_id{} WHERE tags.client_id = $1 GROUP BY tags.id, tags.name, tags.color ORDER BY tags.name",
+            work_list_filter
+        );
+
+        let mut rows =
+            sqlx::query_as::<_, (i64, String, Option<String>, i64)>(&sql).bind(client_id);
+
+        if let Some(ids) = work_list_ids {
+            rows = rows.bind(ids);
+        }
+
+        let rows = rows.fetch_all(&self.pool).await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(id, name, color, todos_count)| Tag {
+                id,
+                name,
+                color,
+                todos_count: Some(todos_count),
+            })
+            .collect())
+    }
+
+    async fn find_tag(&self, id: i64, client_id: i64) -> Result<Option<Tag>, WebError> {
+        let row: Option<(i64, String, Option<String>)> =
+            sqlx::query_as("SELECT id, name, color FROM tags WHERE id = $1 AND client_id = $2")
+                .bind(id)
+                .bind(client_id)
+                .fetch_optional(&self.pool)
+                .await?;
+
+        Ok(row.map(|(id, name, color)| Tag {
+            id,
+            name,
+            color,
+            todos_count: None,
+        }))
+    }
+
+    async fn create_tag(
+        &self,
+        client_id: i64,
+        name: &str,
+        color: Option<&str>,
+    ) -> Result<Tag, WebError> {
+        let id: (i64,) = sqlx::query_as(
+            "INSERT INTO tags (client_id, name, color) VALUES ($1, $2, $3) RETURNING id",
+        )
+        .bind(client_id)
+        .bind(name)
+        .bind(color)
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(Tag {
+            id: id.0,
+            name: name.to_string(),
+            color: color.map(str::to_string),
+            todos_count: None,
+        })
+    }
+
+    async fn update_tag(
+        &self,
+        id: i64,
+        client_id: i64,
+        name: Option<&str>,
+        color: Option<Option<&str>>,
+    ) -> Result<bool, WebError> {
+        let mut set_list = Vec::with_capacity(2);
+
+        if name.is_some() {
+            set_list.push(format!("name = ${}", set_list.len() + 1));
+        }
+
+        if color.is_some() {
+            set_list.push(format!("color = ${}", set_list.len() + 1));
+        }
+
+        if set_list.is_empty() {
+            return Ok(self.find_tag(id, client_id).await?.is_some());
+        }
+
+        let sql = format!(
+            "UPDATE tags SET {} WHERE id = ${} AND client_id = ${}",
+            set_list.join(", "),
+            set_list.len() + 1,
+            set_list.len() + 2
+        );
+        let mut q = sqlx::query(&sql);
+
+        if let Some(name) = name {
+            q = q.bind(name);
+        }
+
+        if let Some(color) = color {
+            q = q.bind(color);
+        }
+
+        let mut tx = self.pool.begin().await?;
+        let rows_affected: u64 = q.bind(id).bind(client_id).execute(&mut tx).await?;
+
+        // Renamed tags change the todos carrying them.
+        if rows_affected > 0 && name.is_some() {
+            sqlx::query("UPDATE todos SET version = version WHERE id IN (SELECT todo_id FROM todo_tags WHERE tag_id = $1)")
+                .bind(id)
+                .execute(&mut tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(rows_affected > 0)
+    }
+
+    async fn delete_tag(&self, id: i64, client_id: i64) -> Result<bool, WebError> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("UPDATE todos SET version = version WHERE id IN (SELECT todo_id FROM todo_tags JOIN tags ON tags.id = todo_tags.tag_id WHERE tags.id = $1 AND tags.client_id = $2)")
+            .bind(id)
+            .bind(client_id)
+            .execute(&mut tx)
+            .await?;
+        // Assignments are removed by the foreign key.
+        let rows_affected: u64 = sqlx::query("DELETE FROM tags WHERE id = $1 AND client_id = $2")
+            .bind(id)
+            .bind(client_id)
+            .execute(&mut tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(rows_affected > 0)
+    }
+
+    async fn ensure_tags(&self, client_id: i64, names: &[String]) -> Result<Vec<Tag>, WebError> {
+        if names.is_empty() {
+            return Ok(vec![]);
+        }
+
+        sqlx::query("INSERT INTO tags (client_id, name) SELECT $1, UNNEST($2::TEXT[]) ON CONFLICT (client_id, name) DO NOTHING")
+            .bind(client_id)
+            .bind(names)
+            .execute(&self.pool)
+            .await?;
+
+        let rows: Vec<(i64, String, Option<String>)> = sqlx::query_as(
+            "SELECT id, name, color FROM tags WHERE client_id = $1 AND name = ANY($2) ORDER BY name",
+        )
+        .bind(client_id)
+        .bind(names)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(id, name, color)| Tag {
+                id,
+                name,
+                color,
+                todos_count: None,
+            })
+            .collect())
+    }
+
+    async fn todo_tag_names(&self, todo_ids: &[i64]) -> Result<Vec<(i64, String)>, WebError> {
+        Ok(sqlx::query_as(
+            "SELECT todo_tags.todo_id, tags.name FROM todo_tags JOIN tags ON tags.id = todo_tags.tag_id WHERE todo_tags.todo_id = ANY($1) ORDER BY tags.name",
+        )
+        .bind(todo_ids)
+        .fetch_all(&self.pool)
+        .await?)
+    }
+}
+
+#[async_trait]
+impl ReminderRepository for PgRepository {
+    async fn list_reminders(&self, todo_id: i64) -> Result<Vec<Reminder>, WebError> {
+        let sql = format!(
+            "SELECT {} FROM reminders WHERE todo_id = $1 ORDER BY remind_at, id",
+            REMINDER_COLUMNS
+        );
+        let rows: Vec<ReminderRow> = sqlx::query_as(&sql)
+            .bind(todo_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows.into_iter().map(|row| row.into()).collect())
+    }
+
+    async fn create_reminder(
+        &self,
+        todo_id: i64,
+        remind_at: DateTime<Utc>,
+    ) -> Result<Reminder, WebError> {
+        let sql = format!(
+            "INSERT INTO reminders (todo_id, remind_at) VALUES ($1, $2) RETURNING {}",
+            REMINDER_COLUMNS
+        );
+        let row: ReminderRow = sqlx::query_as(&sql)
+            .bind(todo_id)
+            .bind(remind_at)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(row.into())
+    }
+
+    async fn delete_reminder(&self, id: i64, todo_id: i64) -> Result<bool, WebError> {
+        let rows_affected: u64 =
+            sqlx::query("DELETE FROM reminders WHERE id = $1 AND todo_id = $2")
+                .bind(id)
+                .bind(todo_id)
+                .execute(&self.pool)
+                .await?;
+
+        Ok(rows_affected > 0)
+    }
+
+    async fn lease_due_reminders(
+        &self,
+        owner: &str,
+        now: DateTime<Utc>,
+        lease_until: DateTime<Utc>,
+        limit: u32,
+    ) -> Result<Vec<(Reminder, i64)>, WebError> {
+        // Rows being leased by another instance at the same time are skipped rather than waited
+        // for, they will not be due for us anymore once its transaction commits.
+        let rows: Vec<LeasedReminderRow> = sqlx::query_as("WITH leased AS (UPDATE reminders SET locked_by = $1, locked_until = $2 WHERE id IN (SELECT reminders.id FROM reminders JOIN todos ON todos.id = reminders.todo_id WHERE reminders.sent_at IS NULL AND reminders.failed_at IS NULL AND reminders.remind_at <= $3 AND (reminders.locked_until IS NULL OR reminders.locked_until <= $3) AND NOT todos.completed ORDER BY reminders.remind_at, reminders.id LIMIT $4 FOR UPDATE OF reminders SKIP LOCKED) RETURNING id, todo_id, remind_at, attempts) SELECT leased.*, work_lists.client_id FROM leased JOIN todos ON todos.id = leased.todo_id JOIN work_lists ON work_lists.id = todos.work_list_id ORDER BY leased.remind_at, leased.id")
+            .bind(owner)
+            .bind(lease_until)
+            .bind(now)
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows.into_iter().map(|row| row.into()).collect())
+    }
+
+    async fn finish_reminder(
+        &self,
+        id: i64,
+        owner: &str,
+        sent: bool,
+        at: DateTime<Utc>,
+    ) -> Result<bool, WebError> {
+        let sql = format!(
+            "UPDATE reminders SET {} = $1, locked_by = NULL, locked_until = NULL WHERE id = $2 AND locked_by = $3",
+            if sent { "sent_at" } else { "failed_at" }
+        );
+        let rows_affected: u64 = sqlx::query(&sql)
+            .bind(at)
+            .bind(id)
+            .bind(owner)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(rows_affected > 0)
+    }
+
+    async fn release_reminder(
+        &self,
+        id: i64,
+        owner: &str,
+        retry_at: DateTime<Utc>,
+    ) -> Result<bool, WebError> {
+        let rows_affected: u64 = sqlx::query("UPDATE reminders SET attempts = attempts + 1, locked_by = NULL, locked_until = $1 WHERE id = $2 AND locked_by = $3")
+            .bind(retry_at)
+            .bind(id)
+            .bind(owner)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(rows_affected > 0)
+    }
+}
+
+#[async_trait]
+impl EventRepository for PgRepository {
+    async fn list_events(
+        &self,
+        client_id: i64,
+        after_id: i64,
+        limit: u32,
+    ) -> Result<Vec<Event>, WebError> {
+        let rows: Vec<EventRow> = sqlx::query_as("SELECT id, client_id, kind, data, created_at FROM events WHERE client_id = $1 AND id > $2 ORDER BY id LIMIT $3")
+            .bind(client_id)
+            .bind(after_id)
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows.into_iter().filter_map(EventRow::into_event).collect())
+    }
+
+    async fn last_event_id(&self, client_id: i64) -> Result<Option<i64>, WebError> {
+        let id: (Option<i64>,) = sqlx::query_as("SELECT MAX(id) FROM events WHERE client_id = $1")
+            .bind(client_id)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(id.0)
+    }
+
+    async fn first_event_id(&self, client_id: i64) -> Result<Option<i64>, WebError> {
+        let id: (Option<i64>,) = sqlx::query_as("SELECT MIN(id) FROM events WHERE client_id = $1")
+            .bind(client_id)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(id.0)
+    }
+
+    async fn prune_events(&self, before: DateTime<Utc>) -> Result<(), WebError> {
+        sqlx::query("DELETE FROM events WHERE created_at < $1 AND NOT EXISTS (SELECT 1 FROM webhook_deliveries WHERE webhook_deliveries.event_id = events.id)")
+            .bind(before)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl WebhookRepository for PgRepository {
+    async fn list_webhooks(&self, client_id: i64) -> Result<Vec<Webhook>, WebError> {
+        let sql = format!(
+            "SELECT {} FROM webhooks WHERE client_id = $1 ORDER BY id",
+            WEBHOOK_COLUMNS
+        );
+        let rows: Vec<WebhookRow> = sqlx::query_as(&sql)
+            .bind(client_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows.into_iter().map(|row| row.into()).collect())
+    }
+
+    async fn find_webhook(&self, id: i64, client_id: i64) -> Result<Option<Webhook>, WebError> {
+        let sql = format!(
+            "SELECT {} FROM webhooks WHERE id = $1 AND client_id = $2",
+            WEBHOOK_COLUMNS
+        );
+        let row: Option<WebhookRow> = sqlx::query_as(&sql)
+            .bind(id)
+            .bind(client_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row.map(|row| row.into()))
+    }
+
+    async fn create_webhook(
+        &self,
+        client_id: i64,
+        url: &str,
+        secret: &str,
+        events: Option<&[EventKind]>,
+        created_at: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<Option<Webhook>, WebError> {
+        let mut tx = self.pool.begin().await?;
+
+        // Concurrent creates of the client wait on its row, so each one counts the webhooks
+        // committed before it.
+        sqlx::query("SELECT id FROM clients WHERE id = $1 FOR UPDATE")
+            .bind(client_id)
+            .execute(&mut tx)
+            .await?;
+
+        let sql = format!(
+            "INSERT INTO webhooks (client_id, url, secret, events, created_at) SELECT $1, $2, $3, $4, $5 WHERE (SELECT COUNT(*) FROM webhooks WHERE client_id = $1) < $6 RETURNING {}",
+            WEBHOOK_COLUMNS
+        );
+        let row: Option<WebhookRow> = sqlx::query_as(&sql)
+            .bind(client_id)
+            .bind(url)
+            .bind(secret)
+            .bind(events.map(Webhook::events_column))
+            .bind(created_at)
+            .bind(limit as i64)
+            .fetch_optional(&mut tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(row.map(Into::into))
+    }
+
+    async fn update_webhook(
+        &self,
+        id: i64,
+        client_id: i64,
+        url: Option<&str>,
+        secret: Option<&str>,
+        events: Option<Option<&[EventKind]>>,
+    ) -> Result<bool, WebError> {
+        let mut set_list = Vec::with_capacity(3);
+
+        if url.is_some() {
+            set_list.push(format!("url = ${}", set_list.len() + 1));
+        }
+
+        if secret.is_some() {
+            set_list.push(format!("secret = ${}", set_list.len() + 1));
+        }
+
+        if events.is_some() {
+            set_list.push(format!("events = ${}", set_list.len() + 1));
+        }
+
+        if set_list.is_empty() {
+            return Ok(self.find_webhook(id, client_id).await?.is_some());
+        }
+
+        let sql = format!(
+            "UPDATE webhooks SET {} WHERE id = ${} AND client_id = ${}",
+            set_list.join(", "),
+            set_list.len() + 1,
+            set_list.len() + 2
+        );
+        let mut q = sqlx::query(&sql);
+
+        if let Some(url) = url {
+            q = q.bind(url);
+        }
+
+        if let Some(secret) = secret {
+            q = q.bind(secret);
+        }
+
+        if let Some(events) = events {
+            q = q.bind(events.map(Webhook::events_column));
+        }
+
+        let rows_affected: u64 = q.bind(id).bind(client_id).execute(&self.pool).await?;
+
+        Ok(rows_affected > 0)
+    }
+
+    async fn delete_webhook(&self, id: i64, client_id: i64) -> Result<bool, WebError> {
+        // Deliveries are removed by the foreign key.
+        let rows_affected: u64 =
+            sqlx::query("DELETE FROM webhooks WHERE id = $1 AND client_id = $2")
+                .bind(id)
+                .bind(client_id)
+                .execute(&self.pool)
+                .await?;
+
+        Ok(rows_affected > 0)
+    }
+
+    async fn list_webhook_deliveries(
+        &self,
+        webhook_id: i64,
+        limit: u32,
+    ) -> Result<Vec<WebhookDelivery>, WebError> {
+        let rows: Vec<WebhookDeliveryRow> = sqlx::query_as("SELECT webhook_deliveries.id, webhook_deliveries.event_id, events.kind, webhook_deliveries.status, webhook_deliveries.attempts, webhook_deliveries.response_status, webhook_deliveries.error, events.created_at, webhook_deliveries.last_attempt_at, webhook_deliveries.next_attempt_at FROM webhook_deliveries JOIN events ON events.id = webhook_deliveries.event_id WHERE webhook_deliveries.webhook_id = $1 ORDER BY webhook_deliveries.id DESC LIMIT $2")
+            .bind(webhook_id)
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(WebhookDeliveryRow::into_delivery)
+            .collect())
+    }
+
+    async fn lease_webhook_deliveries(
+        &self,
+        owner: &str,
+        now: DateTime<Utc>,
+        lease_until: DateTime<Utc>,
+        limit: u32,
+    ) -> Result<Vec<PendingDelivery>, WebError> {
+        let rows: Vec<PendingDeliveryRow> = sqlx::query_as("WITH leased AS (UPDATE webhook_deliveries SET locked_by = $1, locked_until = $2 WHERE id IN (SELECT id FROM webhook_deliveries WHERE status = 'pending' AND next_attempt_at <= $3 AND (locked_until IS NULL OR locked_until <= $3) ORDER BY next_attempt_at, id LIMIT $4 FOR UPDATE SKIP LOCKED) RETURNING id, attempts, webhook_id, event_id, next_attempt_at) SELECT leased.id, leased.attempts, webhooks.url, webhooks.secret, events.id AS event_id, events.client_id, events.kind, events.data, events.created_at FROM leased JOIN webhooks ON webhooks.id = leased.webhook_id JOIN events ON events.id = leased.event_id ORDER BY leased.next_attempt_at, leased.id")
+            .bind(owner)
+            .bind(lease_until)
+            .bind(now)
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(PendingDeliveryRow::into_delivery)
+            .collect())
+    }
+
+    async fn record_delivery_attempt(
+        &self,
+        id: i64,
+        owner: &str,
+        attempt: &DeliveryAttempt,
+    ) -> Result<bool, WebError> {
+        let rows_affected: u64 = sqlx::query("UPDATE webhook_deliveries SET status = $1, attempts = attempts + 1, last_attempt_at = $2, response_status = $3, error = $4, next_attempt_at = COALESCE($5, next_attempt_at), locked_by = NULL, locked_until = NULL WHERE id = $6 AND locked_by = $7")
+            .bind(attempt.status.as_str())
+            .bind(attempt.at)
+            .bind(attempt.response_status.map(|status| status as i32))
+            .bind(attempt.error.as_deref())
+            .bind(attempt.next_attempt_at)
+            .bind(id)
+            .bind(owner)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(rows_affected > 0)
+    }
+
+    async fn prune_webhook_deliveries(&self, before: DateTime<Utc>) -> Result<(), WebError> {
+        sqlx::query(
+            "DELETE FROM webhook_deliveries WHERE status <> 'pending' AND last_attempt_at < $1",
+        )
+        .bind(before)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SearchRepository for PgRepository {
+    async fn search(
+        &self,
+        client_id: i64,
+        query: &SearchQuery,
+    ) -> Result<Vec<SearchResult>, WebError> {
+        // Terms are plain words, so they can't break tsquery syntax. Every one matches as a prefix.
+        let tsquery = query
+            .terms
+            .iter()
+            .map(|term| format!("{}:*", term))
+            .collect::<Vec<String>>()
+            .join(" & ");
+        let headline_options = format!(
+            "StartSel={}, StopSel={}, MaxWords=16, MinWords=6",
+            MATCH_START, MATCH_END
+        );
+
+        let (work_list_filter, limit_arg) = match query.work_list_ids {
+            Some(_) => (" AND work_lists.id = ANY($4)", "$5"),
+            None => ("", "$4"),
+        };
+
+        let selects: Vec<String> = query
+            .kinds
+            .iter()
+            .map(|kind| match kind {
+                SearchKind::Todo => format!(
+                    "SELECT 'todo' AS kind, todos.id AS id, todos.work_list_id AS work_list_id, ts_headline('simple', todos.content, query, $2) AS snippet, ts_rank(to_tsvector('simple', todos.content), query)::float8 AS score FROM todos JOIN work_lists ON work_lists.id = todos.work_list_id CROSS JOIN to_tsquery('simple', $1) AS query WHERE to_tsvector('simple', todos.content) @@ query AND work_lists.client_id = $3{}",
+                    work_list_filter
+                ),
+                SearchKind::WorkList => format!(
+                    "SELECT 'work_list' AS kind, work_lists.id AS id, work_lists.id AS work_list_id, ts_headline('simple', work_lists.name, query, $2) AS snippet, ts_rank(to_tsvector('simple', work_lists.name), query)::float8 AS score FROM work_lists CROSS JOIN to_tsquery('simple', $1) AS query WHERE to_tsvector('simple', work_lists.name) @@ query AND work_lists.client_id = $3{}",
+                    work_list_filter
+                ),
+            })
+            .collect();
+        let sql = format!(
+            "{} ORDER BY score DESC, id LIMIT {}",
+            selects.join(" UNION ALL "),
+            limit_arg
+        );
+
+        let mut rows = sqlx::query_as::<_, (String, i64, i64, String, f64)>(&sql)
+            .bind(tsquery)
+            .bind(headline_options)
+            .bind(client_id);
+
+        if let Some(ids) = query.work_list_ids.as_ref() {
+            rows = rows.bind(ids.clone());
+        }
+
+        let rows = rows.bind(query.limit as i64).fetch_all(&self.pool).await?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|(kind, id, work_list_id, snippet, score)| {
+                SearchKind::parse(&kind).map(|kind| SearchResult {
+                    kind,
+                    id,
+                    work_list_id,
+                    snippet,
+                    score,
+                })
+            })
+            .collect())
+    }
+}
+
+#[async_trait]
+impl SyncRepository for PgRepository {
+    async fn changed_work_lists(
+        &self,
+        client_id: i64,
+        since: i64,
+        limit: u32,
+    ) -> Result<Vec<WorkList>, WebError> {
+        let rows: Vec<(i64, String, DateTime<Utc>, i64)> = sqlx::query_as("SELECT id, name, created_at, version FROM work_lists WHERE client_id = $1 AND version > $2 ORDER BY version LIMIT $3")
+            .bind(client_id)
+            .bind(since)
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(id, name, created_at, version)| {
+                WorkList::new(id, name, created_at, version, vec![])
+            })
+            .collect())
+    }
+
+    async fn changed_todos(
+        &self,
+        client_id: i64,
+        since: i64,
+        limit: u32,
+    ) -> Result<Vec<Todo>, WebError> {
+        let rows: Vec<TodoRow> = sqlx::query_as("SELECT todos.* FROM todos JOIN work_lists ON work_lists.id = todos.work_list_id WHERE work_lists.client_id = $1 AND todos.version > $2 ORDER BY todos.version LIMIT $3")
+            .bind(client_id)
+            .bind(since)
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows.into_iter().map(|row| row.into()).collect())
+    }
+
+    async fn list_tombstones(
+        &self,
+        client_id: i64,
+        since: i64,
+        limit: u32,
+    ) -> Result<Vec<Tombstone>, WebError> {
+        let rows: Vec<TombstoneRow> = sqlx::query_as("SELECT kind, resource_id, work_list_id, version, deleted_at FROM tombstones WHERE client_id = $1 AND version > $2 ORDER BY version LIMIT $3")
+            .bind(client_id)
+            .bind(since)
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(TombstoneRow::into_tombstone)
+            .collect())
+    }
+
+    async fn find_mutation(
+        &self,
+        client_id: i64,
+        mutation_id: &str,
+    ) -> Result<Option<(TombstoneKind, i64)>, WebError> {
+        let row: Option<(String, i64)> = sqlx::query_as(
+            "SELECT kind, resource_id FROM applied_mutations WHERE client_id = $1 AND mutation_id = $2",
+        )
+        .bind(client_id)
+        .bind(mutation_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.and_then(|(kind, id)| Some((TombstoneKind::parse(&kind)?, id))))
+    }
+}
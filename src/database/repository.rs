@@ -0,0 +1,413 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+
+use super::migrations::Migrator;
+use crate::error::WebError;
+use crate::model::{
+    ApiKey, DeliveryAttempt, Event, EventKind, KeyHash, NewEvent, NewTodo, PendingDelivery,
+    Permissions, Reminder, SearchQuery, SearchResult, Tag, Todo, TodoCascade, TodoChanges,
+    TodoQuery, Tombstone, TombstoneKind, Webhook, WebhookDelivery, WorkList, WorkListQuery,
+};
+use crate::web_app::Client;
+
+/// Describes a write as an event, given the ID and the new version of the changed todo or work
+/// list. Storage records the event in the transaction of the write, so that no change is left
+/// without its event and no event is left without its change.
+pub type DescribeEvent<'a> = dyn Fn(i64, i64) -> NewEvent + Send + Sync + 'a;
+
+/// Describes a write as an event of the given kind, given the todo as stored. Used for writes
+/// where storage decides part of the todo, such as its position.
+pub type DescribeTodo<'a> = dyn Fn(EventKind, &Todo) -> NewEvent + Send + Sync + 'a;
+
+/// Storage of API clients and their credentials.
+#[async_trait]
+pub trait ClientRepository {
+    async fn find_client(&self, id: i64) -> Result<Option<Client>, WebError>;
+    /// Returns keys with given prefix, together with their hashes. Expired keys are included,
+    /// so callers can tell an expired token from an unknown one.
+    async fn find_api_keys_by_prefix(
+        &self,
+        prefix: &str,
+    ) -> Result<Vec<(ApiKey, KeyHash)>, WebError>;
+    /// Records that the key has just been used.
+    async fn touch_api_key(&self, id: i64) -> Result<(), WebError>;
+    async fn create_client(&self, display_name: &str) -> Result<Client, WebError>;
+    async fn list_clients(&self) -> Result<Vec<Client>, WebError>;
+    /// Returns `false` if there was no such client.
+    async fn rename_client(&self, id: i64, display_name: &str) -> Result<bool, WebError>;
+    /// Returns `false` if there was no such client. The name is not validated here.
+    async fn set_client_time_zone(&self, id: i64, time_zone: &str) -> Result<bool, WebError>;
+    /// Removes client together with its API keys, work lists and todos.
+    /// Returns `false` if there was no such client.
+    async fn delete_client(&self, id: i64) -> Result<bool, WebError>;
+    async fn create_api_key(
+        &self,
+        client_id: i64,
+        label: Option<&str>,
+        prefix: &str,
+        key_hash: &KeyHash,
+        valid_to: DateTime<Utc>,
+        permissions: &Permissions,
+    ) -> Result<ApiKey, WebError>;
+    async fn find_api_key(&self, id: i64) -> Result<Option<ApiKey>, WebError>;
+    async fn list_api_keys(&self, client_id: i64) -> Result<Vec<ApiKey>, WebError>;
+    /// Returns `false` if there was no such key.
+    async fn revoke_api_key(&self, id: i64) -> Result<bool, WebError>;
+    /// Keys stored in plaintext before hashing was introduced.
+    async fn plaintext_api_keys(&self) -> Result<Vec<(i64, String)>, WebError>;
+    /// Replaces plaintext key with its prefix and hash.
+    async fn hash_plaintext_api_key(
+        &self,
+        id: i64,
+        prefix: &str,
+        key_hash: &KeyHash,
+    ) -> Result<(), WebError>;
+}
+
+/// Storage of work lists. Todos are not loaded here - see `TodoRepository`.
+#[async_trait]
+pub trait WorkListRepository {
+    async fn work_list_exists(&self, id: i64, client_id: i64) -> Result<bool, WebError>;
+    /// `mutation_id` of a sync mutation is remembered with the work list. Fails with
+    /// `WebError::Conflict` if the client has already used it.
+    async fn create_work_list(
+        &self,
+        name: &str,
+        client_id: i64,
+        created_at: DateTime<Utc>,
+        mutation_id: Option<&str>,
+        event: &DescribeEvent<'_>,
+    ) -> Result<(WorkList, Event), WebError>;
+    /// Returns at most `query.limit` work lists matching the query, without todos.
+    async fn list_work_lists(
+        &self,
+        client_id: i64,
+        query: &WorkListQuery,
+    ) -> Result<Vec<WorkList>, WebError>;
+    async fn find_work_list(&self, id: i64, client_id: i64) -> Result<Option<WorkList>, WebError>;
+    /// Returns the new version, `None` if there was no such work list. Conditional writes with
+    /// `expected_version` fail with `WebError::PreconditionFailed` if the work list is no longer
+    /// at that version.
+    async fn update_work_list(
+        &self,
+        id: i64,
+        client_id: i64,
+        name: &str,
+        expected_version: Option<i64>,
+        event: &DescribeEvent<'_>,
+    ) -> Result<Option<(i64, Event)>, WebError>;
+    /// Deletes work list together with its todos, leaving a tombstone of the work list.
+    /// Returns `None` if there was no such work list. `expected_version` is checked as by
+    /// `update_work_list`.
+    async fn delete_work_list(
+        &self,
+        id: i64,
+        client_id: i64,
+        expected_version: Option<i64>,
+        event: &DescribeEvent<'_>,
+    ) -> Result<Option<Event>, WebError>;
+}
+
+/// Storage of todos. Ownership checks are performed by `model::Todo`.
+#[async_trait]
+pub trait TodoRepository {
+    async fn find_todo(&self, id: i64, client_id: i64) -> Result<Option<Todo>, WebError>;
+    /// Returns todos of given work lists ordered by position, at most `per_list` of each one.
+    async fn todos_in_work_lists(
+        &self,
+        work_list_ids: &[i64],
+        per_list: Option<u32>,
+    ) -> Result<Vec<Todo>, WebError>;
+    /// Returns at most `query.limit` todos of the client matching the query.
+    async fn search_todos(&self, client_id: i64, query: &TodoQuery) -> Result<Vec<Todo>, WebError>;
+    /// Creates todo together with its tag assignments at the end of its work list. A recurring
+    /// todo without a series starts a new one.
+    async fn create_todo(
+        &self,
+        todo: &NewTodo,
+        event: &DescribeTodo<'_>,
+    ) -> Result<(Todo, Event), WebError>;
+    /// Returns the new version of the todo. Conditional writes with `expected_version` fail with
+    /// `WebError::PreconditionFailed` if the todo is no longer at that version.
+    async fn update_todo(
+        &self,
+        id: i64,
+        changes: &TodoChanges,
+        expected_version: Option<i64>,
+        event: &DescribeEvent<'_>,
+    ) -> Result<(i64, Event), WebError>;
+    /// Stores all writes of the cascade in one transaction and returns the todo as stored
+    /// together with the events, in the order of the writes. Only the todo itself is checked
+    /// against `expected_version`, as by `update_todo`.
+    async fn update_todo_cascade(
+        &self,
+        cascade: &TodoCascade,
+        expected_version: Option<i64>,
+        event: &DescribeTodo<'_>,
+    ) -> Result<(Todo, Vec<Event>), WebError>;
+    /// Deletes todo together with its subtasks at any depth, their tag assignments and
+    /// reminders, leaving a tombstone of each of them. `event` describes every deleted todo,
+    /// subtasks come first. Only the todo itself is checked against `expected_version`, as by
+    /// `update_todo`.
+    async fn delete_todo(
+        &self,
+        id: i64,
+        expected_version: Option<i64>,
+        event: &DescribeEvent<'_>,
+    ) -> Result<Vec<Event>, WebError>;
+    /// Returns subtasks of a todo at any depth, ordered by position.
+    async fn find_subtasks(&self, id: i64) -> Result<Vec<Todo>, WebError>;
+    /// Position of the nearest todo after (or before, if `after` is `false`) given position in
+    /// a work list, ignoring todo `exclude_id`.
+    async fn adjacent_todo_position(
+        &self,
+        work_list_id: i64,
+        position: i64,
+        after: bool,
+        exclude_id: i64,
+    ) -> Result<Option<i64>, WebError>;
+    /// Moves todos to new positions, all or none. `moves` are triples of todo ID, position and the
+    /// version the todo was read at; if any of the todos has changed since, nothing is moved and
+    /// `WebError::PreconditionFailed` is returned. `event` describes every moved todo.
+    async fn move_todos(
+        &self,
+        moves: &[(i64, i64, i64)],
+        event: &DescribeEvent<'_>,
+    ) -> Result<Vec<Event>, WebError>;
+}
+
+/// Tags of clients and their assignment to todos. Ownership of todos is checked by `model::Todo`.
+#[async_trait]
+pub trait TagRepository {
+    /// Returns tags of the client ordered by name, with numbers of tagged todos. Only todos of
+    /// given work lists are counted, `None` means all of them.
+    async fn list_tags(
+        &self,
+        client_id: i64,
+        work_list_ids: Option<&[i64]>,
+    ) -> Result<Vec<Tag>, WebError>;
+    async fn find_tag(&self, id: i64, client_id: i64) -> Result<Option<Tag>, WebError>;
+    async fn create_tag(
+        &self,
+        client_id: i64,
+        name: &str,
+        color: Option<&str>,
+    ) -> Result<Tag, WebError>;
+    /// `None` leaves a field as it is. Returns `false` if there was no such tag.
+    async fn update_tag(
+        &self,
+        id: i64,
+        client_id: i64,
+        name: Option<&str>,
+        color: Option<Option<&str>>,
+    ) -> Result<bool, WebError>;
+    /// Detaches tag from todos and deletes it. Returns `false` if there was no such tag.
+    async fn delete_tag(&self, id: i64, client_id: i64) -> Result<bool, WebError>;
+    /// Returns tags of the client with given names, creating missing ones.
+    async fn ensure_tags(&self, client_id: i64, names: &[String]) -> Result<Vec<Tag>, WebError>;
+    /// Returns `(todo ID, tag name)` pairs for given todos, ordered by tag name.
+    async fn todo_tag_names(&self, todo_ids: &[i64]) -> Result<Vec<(i64, String)>, WebError>;
+}
+
+/// Reminders of todos and their delivery. Ownership of todos is checked by `model::Todo`.
+///
+/// Reminders due for delivery are leased, so that API instances sharing the database never
+/// deliver the same reminder at once. A lease which is neither finished nor released expires at
+/// `lease_until` and the reminder can be leased again.
+#[async_trait]
+pub trait ReminderRepository {
+    /// Returns reminders of a todo ordered by time.
+    async fn list_reminders(&self, todo_id: i64) -> Result<Vec<Reminder>, WebError>;
+    async fn create_reminder(
+        &self,
+        todo_id: i64,
+        remind_at: DateTime<Utc>,
+    ) -> Result<Reminder, WebError>;
+    /// Returns `false` if the todo has no such reminder.
+    async fn delete_reminder(&self, id: i64, todo_id: i64) -> Result<bool, WebError>;
+    /// Leases at most `limit` pending reminders due at `now` to `owner`, earliest first.
+    /// Reminders of completed todos are skipped. Returns leased reminders together with IDs of
+    /// clients owning their todos.
+    async fn lease_due_reminders(
+        &self,
+        owner: &str,
+        now: DateTime<Utc>,
+        lease_until: DateTime<Utc>,
+        limit: u32,
+    ) -> Result<Vec<(Reminder, i64)>, WebError>;
+    /// Marks a leased reminder as sent, or as failed if `sent` is `false`. Returns `false` if
+    /// `owner` no longer holds the lease.
+    async fn finish_reminder(
+        &self,
+        id: i64,
+        owner: &str,
+        sent: bool,
+        at: DateTime<Utc>,
+    ) -> Result<bool, WebError>;
+    /// Releases a leased reminder after a failed delivery attempt. It will not be leased again
+    /// before `retry_at`. Returns `false` if `owner` no longer holds the lease.
+    async fn release_reminder(
+        &self,
+        id: i64,
+        owner: &str,
+        retry_at: DateTime<Utc>,
+    ) -> Result<bool, WebError>;
+}
+
+/// Log of changes of clients' data. Events are recorded by writes they describe, together with
+/// their deliveries to webhooks subscribed to them.
+#[async_trait]
+pub trait EventRepository {
+    /// Events of the client recorded after the given one, oldest first.
+    async fn list_events(
+        &self,
+        client_id: i64,
+        after_id: i64,
+        limit: u32,
+    ) -> Result<Vec<Event>, WebError>;
+
+    async fn last_event_id(&self, client_id: i64) -> Result<Option<i64>, WebError>;
+    /// ID of the oldest event of the client still stored. Events before it may have been pruned.
+    async fn first_event_id(&self, client_id: i64) -> Result<Option<i64>, WebError>;
+    /// Deletes events recorded before `before`, except those still referenced by webhook
+    /// deliveries.
+    async fn prune_events(&self, before: DateTime<Utc>) -> Result<(), WebError>;
+}
+
+/// Webhooks of clients and the outbox of their deliveries.
+///
+/// Deliveries are leased the same way as reminders, see `ReminderRepository`.
+#[async_trait]
+pub trait WebhookRepository {
+    /// Returns webhooks of the client ordered by ID.
+    async fn list_webhooks(&self, client_id: i64) -> Result<Vec<Webhook>, WebError>;
+    async fn find_webhook(&self, id: i64, client_id: i64) -> Result<Option<Webhook>, WebError>;
+    /// Returns `None` if the client already has `limit` webhooks. The count is checked by the
+    /// insert itself, so concurrent creates can't go past the limit.
+    async fn create_webhook(
+        &self,
+        client_id: i64,
+        url: &str,
+        secret: &str,
+        events: Option<&[EventKind]>,
+        created_at: DateTime<Utc>,
+        limit: usize,
+    ) -> Result<Option<Webhook>, WebError>;
+    /// `None` leaves a field as it is. Returns `false` if there was no such webhook.
+    async fn update_webhook(
+        &self,
+        id: i64,
+        client_id: i64,
+        url: Option<&str>,
+        secret: Option<&str>,
+        events: Option<Option<&[EventKind]>>,
+    ) -> Result<bool, WebError>;
+    /// Deletes webhook together with its deliveries. Returns `false` if there was no such
+    /// webhook.
+    async fn delete_webhook(&self, id: i64, client_id: i64) -> Result<bool, WebError>;
+    /// Returns at most `limit` latest deliveries to a webhook, newest first.
+    async fn list_webhook_deliveries(
+        &self,
+        webhook_id: i64,
+        limit: u32,
+    ) -> Result<Vec<WebhookDelivery>, WebError>;
+    /// Leases at most `limit` pending deliveries due at `now` to `owner`, oldest first.
+    async fn lease_webhook_deliveries(
+        &self,
+        owner: &str,
+        now: DateTime<Utc>,
+        lease_until: DateTime<Utc>,
+        limit: u32,
+    ) -> Result<Vec<PendingDelivery>, WebError>;
+    /// Records outcome of an attempt and releases the lease. Returns `false` if `owner` no
+    /// longer holds the lease.
+    async fn record_delivery_attempt(
+        &self,
+        id: i64,
+        owner: &str,
+        attempt: &DeliveryAttempt,
+    ) -> Result<bool, WebError>;
+    /// Deletes finished deliveries last attempted before `before`. Their events are left to
+    /// `EventRepository::prune_events`.
+    async fn prune_webhook_deliveries(&self, before: DateTime<Utc>) -> Result<(), WebError>;
+}
+
+/// Changes for delta sync. Versions are taken from a single sequence by every write of a todo
+/// or work list and by every tombstone, so changes of all kinds can be merged by version.
+#[async_trait]
+pub trait SyncRepository {
+    /// Work lists of the client with version greater than `since`, lowest version first.
+    async fn changed_work_lists(
+        &self,
+        client_id: i64,
+        since: i64,
+        limit: u32,
+    ) -> Result<Vec<WorkList>, WebError>;
+    /// Todos of the client with version greater than `since`, lowest version first. Tags are not
+    /// loaded.
+    async fn changed_todos(
+        &self,
+        client_id: i64,
+        since: i64,
+        limit: u32,
+    ) -> Result<Vec<Todo>, WebError>;
+    async fn list_tombstones(
+        &self,
+        client_id: i64,
+        since: i64,
+        limit: u32,
+    ) -> Result<Vec<Tombstone>, WebError>;
+    /// Kind and ID of the item created by a sync mutation of the client.
+    async fn find_mutation(
+        &self,
+        client_id: i64,
+        mutation_id: &str,
+    ) -> Result<Option<(TombstoneKind, i64)>, WebError>;
+}
+
+/// Full-text search over todos and work lists. Indexes are kept up to date by the database.
+#[async_trait]
+pub trait SearchRepository {
+    /// Returns at most `query.limit` results, most relevant first.
+    async fn search(
+        &self,
+        client_id: i64,
+        query: &SearchQuery,
+    ) -> Result<Vec<SearchResult>, WebError>;
+}
+
+pub trait Repository:
+    ClientRepository
+    + WorkListRepository
+    + TodoRepository
+    + TagRepository
+    + ReminderRepository
+    + EventRepository
+    + WebhookRepository
+    + SearchRepository
+    + SyncRepository
+    + Migrator
+    + Send
+    + Sync
+{
+}
+
+impl<T> Repository for T where
+    T: ClientRepository
+        + WorkListRepository
+        + TodoRepository
+        + TagRepository
+        + ReminderRepository
+        + EventRepository
+        + WebhookRepository
+        + SearchRepository
+        + SyncRepository
+        + Migrator
+        + Send
+        + Sync
+{
+}
+
+/// Backend chosen at startup, shared between all workers.
+pub type Storage = Arc<dyn Repository>;
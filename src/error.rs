@@ -1,27 +1,141 @@
-use actix_web::{error, http::StatusCode, HttpResponse, ResponseError};
+use actix_web::{http::StatusCode, HttpResponse, ResponseError};
+use log::error;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
 use std::error::Error;
 use std::fmt::{self, Display};
-use validator::ValidationErrors;
+use validator::{ValidationErrors, ValidationErrorsKind};
 
-use serde_json::{json, Value};
+/// Problem types are identified by URNs built from the error code.
+const PROBLEM_TYPE_PREFIX: &str = "urn:todo-list-api:problem:";
+
+/// Kind of resource a `NotFound` error refers to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Resource {
+    Todo,
+    WorkList,
+    ApiKey,
+    Tag,
+    Reminder,
+    Webhook,
+    /// Lookup failed somewhere the resource kind is not known.
+    Unknown,
+}
+
+/// Reason an API token was rejected.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AuthError {
+    MissingToken,
+    InvalidToken,
+    KeyExpired,
+}
 
 #[derive(Debug)]
 pub enum WebError {
     ValidationError(ValidationErrors),
+    /// Internal failure. Details are logged, never sent to the client.
     DatabaseError(sqlx::Error),
-    ActixError(error::Error),
-    Unauthorized,
+    /// Internal failure outside of the database, e.g. a response that could not be serialized.
+    /// Details are logged, never sent to the client.
+    Internal(String),
+    /// Request body could not be parsed.
+    BadRequest(String),
+    NotFound(Resource),
+    Unauthorized(AuthError),
+    Forbidden,
+    Conflict,
+    /// Todos of a work list were reordered by another request while a todo was being moved.
+    ReorderConflict,
+    /// `If-Match` header did not match the current version of the resource.
+    PreconditionFailed,
 }
 
-impl From<sqlx::Error> for WebError {
-    fn from(error: sqlx::Error) -> Self {
-        Self::DatabaseError(error)
+/// Error response body, as described in RFC 7807.
+#[derive(Debug, Serialize)]
+struct Problem<'a> {
+    #[serde(rename = "type")]
+    problem_type: String,
+    title: &'static str,
+    status: u16,
+    detail: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    instance: Option<&'a str>,
+    /// Machine-readable error code, stable across releases.
+    code: &'static str,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    errors: Vec<FieldError>,
+}
+
+/// Single validation failure of a request field.
+#[derive(Debug, Serialize)]
+struct FieldError {
+    /// Path to the field, e.g. `label` or `work_list_ids[2]`.
+    field: String,
+    code: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    params: HashMap<String, Value>,
+}
+
+fn flatten_validation_errors(
+    errors: &ValidationErrors,
+    path: Option<&str>,
+    flattened: &mut Vec<FieldError>,
+) {
+    for (field, kind) in errors.errors() {
+        let field = match path {
+            Some(path) => format!("{}.{}", path, field),
+            None => field.to_string(),
+        };
+
+        match kind {
+            ValidationErrorsKind::Field(errors) => flattened.extend(errors.iter().map(|error| {
+                FieldError {
+                    field: field.clone(),
+                    code: error.code.to_string(),
+                    message: error.message.as_ref().map(|message| message.to_string()),
+                    params: error
+                        .params
+                        .iter()
+                        .filter(|(name, _)| *name != "value")
+                        .map(|(name, value)| (name.to_string(), value.clone()))
+                        .collect(),
+                }
+            })),
+            ValidationErrorsKind::Struct(errors) => {
+                flatten_validation_errors(errors, Some(&field), flattened)
+            }
+            ValidationErrorsKind::List(items) => {
+                for (idx, errors) in items {
+                    let item = format!("{}[{}]", field, idx);
+                    flatten_validation_errors(errors, Some(&item), flattened);
+                }
+            }
+        }
     }
 }
 
-impl From<error::Error> for WebError {
-    fn from(error: error::Error) -> Self {
-        Self::ActixError(error)
+fn is_unique_violation(error: &sqlx::Error) -> bool {
+    match error {
+        sqlx::Error::Database(err) => {
+            let code = err.code().map(|code| code.to_string());
+
+            // 23505 is PostgreSQL unique_violation, SQLite reports it only in the message.
+            code.as_deref() == Some("23505") || err.message().contains("UNIQUE constraint failed")
+        }
+        _ => false,
+    }
+}
+
+impl From<sqlx::Error> for WebError {
+    fn from(error: sqlx::Error) -> Self {
+        match error {
+            sqlx::Error::RowNotFound => Self::NotFound(Resource::Unknown),
+            error if is_unique_violation(&error) => Self::Conflict,
+            error => Self::DatabaseError(error),
+        }
     }
 }
 
@@ -30,7 +144,7 @@ impl Error for WebError {
         use WebError::*;
 
         match self {
-            ActixError(err) => Some(err),
+            DatabaseError(err) => Some(err),
             _ => None,
         }
     }
@@ -38,37 +152,129 @@ impl Error for WebError {
 
 impl Display for WebError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        use WebError::*;
-        let identifier = match self {
-            ValidationError(_) => "ValidationError",
-            DatabaseError(_) => "DatabaseError",
-            ActixError(_) => "InternalError",
-            Unauthorized => "Unauthorized",
-        };
-
-        write!(f, "{}", identifier)
+        write!(f, "{}", self.code())
     }
 }
 
 impl WebError {
-    fn populate_error_map(&self, error_map: &mut Value) {
+    /// Machine-readable code of the error. Clients should match on it rather than on titles.
+    pub fn code(&self) -> &'static str {
         use WebError::*;
-        error_map["type"] = json!(self.to_string());
 
         match self {
-            ValidationError(errors) => {
-                error_map["details"] = serde_json::to_value(errors)
-                    .unwrap_or(json!({ "error": "unknown validation error" }));
+            ValidationError(_) => "request.invalid",
+            DatabaseError(_) | Internal(_) => "internal_error",
+            BadRequest(_) => "request.malformed",
+            NotFound(Resource::Todo) => "todo.not_found",
+            NotFound(Resource::WorkList) => "work_list.not_found",
+            NotFound(Resource::ApiKey) => "api_key.not_found",
+            NotFound(Resource::Tag) => "tag.not_found",
+            NotFound(Resource::Reminder) => "reminder.not_found",
+            NotFound(Resource::Webhook) => "webhook.not_found",
+            NotFound(Resource::Unknown) => "resource.not_found",
+            Unauthorized(AuthError::MissingToken) => "auth.missing_token",
+            Unauthorized(AuthError::InvalidToken) => "auth.invalid_token",
+            Unauthorized(AuthError::KeyExpired) => "auth.key_expired",
+            Forbidden => "auth.forbidden",
+            Conflict => "resource.conflict",
+            ReorderConflict => "todo.reorder_conflict",
+            PreconditionFailed => "resource.modified",
+        }
+    }
+
+    fn title(&self) -> &'static str {
+        use WebError::*;
+
+        match self {
+            ValidationError(_) => "Validation failed",
+            DatabaseError(_) | Internal(_) => "Internal server error",
+            BadRequest(_) => "Malformed request",
+            NotFound(Resource::Todo) => "Todo not found",
+            NotFound(Resource::WorkList) => "Work list not found",
+            NotFound(Resource::ApiKey) => "API key not found",
+            NotFound(Resource::Tag) => "Tag not found",
+            NotFound(Resource::Reminder) => "Reminder not found",
+            NotFound(Resource::Webhook) => "Webhook not found",
+            NotFound(Resource::Unknown) => "Resource not found",
+            Unauthorized(AuthError::MissingToken) => "Missing API token",
+            Unauthorized(AuthError::InvalidToken) => "Invalid API token",
+            Unauthorized(AuthError::KeyExpired) => "API key expired",
+            Forbidden => "Insufficient permissions",
+            Conflict => "Resource already exists",
+            ReorderConflict => "Work list reordered meanwhile",
+            PreconditionFailed => "Resource has changed",
+        }
+    }
+
+    fn detail(&self) -> String {
+        use WebError::*;
+
+        match self {
+            ValidationError(_) => "One or more fields of the request are invalid.".to_string(),
+            DatabaseError(_) | Internal(_) => "The request could not be completed.".to_string(),
+            BadRequest(message) => message.clone(),
+            NotFound(_) => {
+                "The requested resource does not exist or is not accessible by this client."
+                    .to_string()
+            }
+            Unauthorized(AuthError::MissingToken) => {
+                "Requests must carry an `Authorization: Token <token>` header.".to_string()
             }
-            DatabaseError(err) => {
-                error_map["details"] = json!({ "message": err.to_string() });
+            Unauthorized(AuthError::InvalidToken) => "The API token is not recognized.".to_string(),
+            Unauthorized(AuthError::KeyExpired) => {
+                "The API key has expired. Issue a new one to continue.".to_string()
             }
-            ActixError(err) => {
-                error_map["details"] = json!({ "message": err.to_string() });
+            Forbidden => "The API key does not grant access to this operation.".to_string(),
+            Conflict => "The resource conflicts with an existing one.".to_string(),
+            ReorderConflict => {
+                "Todos of the work list were reordered by another request. Retry the move."
+                    .to_string()
+            }
+            PreconditionFailed => {
+                "The resource has changed since it was fetched. Fetch it again and retry."
+                    .to_string()
             }
-            Unauthorized => {}
         }
     }
+
+    fn problem<'a>(&self, instance: Option<&'a str>) -> Problem<'a> {
+        let mut errors = vec![];
+        if let WebError::ValidationError(validation_errors) = self {
+            flatten_validation_errors(validation_errors, None, &mut errors);
+            errors.sort_by(|a, b| a.field.cmp(&b.field));
+        }
+
+        Problem {
+            problem_type: format!("{}{}", PROBLEM_TYPE_PREFIX, self.code()),
+            title: self.title(),
+            status: self.status_code().as_u16(),
+            detail: self.detail(),
+            instance,
+            code: self.code(),
+            errors,
+        }
+    }
+
+    /// Problem body of the error, for errors reported within a successful response.
+    pub fn problem_json(&self) -> Value {
+        serde_json::to_value(self.problem(None)).unwrap_or_default()
+    }
+
+    /// Builds `application/problem+json` response. `instance` is the path of the request which
+    /// caused the error, if known.
+    pub fn problem_response(&self, instance: Option<&str>) -> HttpResponse {
+        let problem = self.problem(instance);
+
+        let mut response = HttpResponse::build(self.status_code());
+
+        if let WebError::Unauthorized(_) = self {
+            response.header("WWW-Authenticate", "Token");
+        }
+
+        response
+            .content_type("application/problem+json")
+            .body(serde_json::to_string(&problem).unwrap_or_default())
+    }
 }
 
 impl ResponseError for WebError {
@@ -77,16 +283,23 @@ impl ResponseError for WebError {
 
         match self {
             ValidationError(_) => StatusCode::UNPROCESSABLE_ENTITY,
-            _ => StatusCode::INTERNAL_SERVER_ERROR,
+            DatabaseError(_) | Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            BadRequest(_) => StatusCode::BAD_REQUEST,
+            NotFound(_) => StatusCode::NOT_FOUND,
+            Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            Forbidden => StatusCode::FORBIDDEN,
+            Conflict | ReorderConflict => StatusCode::CONFLICT,
+            PreconditionFailed => StatusCode::PRECONDITION_FAILED,
         }
     }
 
     fn error_response(&self) -> HttpResponse {
-        let mut error_map = json!({});
-        self.populate_error_map(&mut error_map);
+        match self {
+            WebError::DatabaseError(err) => error!("Database error: {:?}", err),
+            WebError::Internal(message) => error!("Internal error: {}", message),
+            _ => {}
+        }
 
-        HttpResponse::build(self.status_code())
-            .content_type("application/json")
-            .json(error_map)
+        self.problem_response(None)
     }
 }
@@ -0,0 +1,25 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use validator::{Validate, ValidationError};
+
+use crate::model::Scope;
+
+fn in_future(valid_to: &DateTime<Utc>) -> Result<(), ValidationError> {
+    if *valid_to > Utc::now() {
+        Ok(())
+    } else {
+        Err(ValidationError::new("in_future"))
+    }
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateApiKey {
+    #[validate(length(min = 1))]
+    pub label: Option<String>,
+    #[validate(custom = "in_future")]
+    pub valid_to: DateTime<Utc>,
+    /// Omitted means every scope.
+    pub scopes: Option<Vec<Scope>>,
+    /// Omitted means every work list.
+    pub work_list_ids: Option<Vec<i64>>,
+}
@@ -1,2 +1,20 @@
+use serde::{Deserialize, Deserializer};
+
+pub mod api_key;
+pub mod reminder;
+pub mod search;
+pub mod sync;
+pub mod tag;
 pub mod todo;
+pub mod webhook;
 pub mod work_list;
+
+/// Tells a field set to `null` (`Some(None)`) from a missing one (`None`). Use together with
+/// `#[serde(default)]`.
+fn nullable<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
@@ -0,0 +1,8 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use validator::Validate;
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateReminder {
+    pub remind_at: DateTime<Utc>,
+}
@@ -0,0 +1,14 @@
+use serde::Deserialize;
+use validator::Validate;
+
+use crate::model::SearchKind;
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct Search {
+    #[validate(length(min = 1, max = 200))]
+    pub q: String,
+    /// Omitted means both todos and work lists.
+    pub kind: Option<SearchKind>,
+    #[validate(range(min = 1, max = 100))]
+    pub limit: Option<u32>,
+}
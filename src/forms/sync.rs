@@ -0,0 +1,56 @@
+use serde::Deserialize;
+use validator::Validate;
+
+use super::todo::{CreateTodo, UpdateTodo};
+use super::work_list::{CreateWorkList, UpdateWorkList};
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct SyncQuery {
+    /// Token returned by the previous sync. Everything is sent when missing.
+    pub since: Option<String>,
+    #[validate(range(min = 1, max = 1000))]
+    pub limit: Option<u32>,
+}
+
+/// Change made by a client while offline. Updates and deletes carrying `base_version` are
+/// applied only if the item has not changed since.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Mutation {
+    CreateWorkList {
+        /// Chosen by the client. A create pushed again with the same ID is applied only once.
+        mutation_id: Option<String>,
+        data: CreateWorkList,
+    },
+    UpdateWorkList {
+        id: i64,
+        base_version: Option<i64>,
+        data: UpdateWorkList,
+    },
+    DeleteWorkList {
+        id: i64,
+        base_version: Option<i64>,
+    },
+    CreateTodo {
+        /// Chosen by the client. A create pushed again with the same ID is applied only once.
+        mutation_id: Option<String>,
+        data: CreateTodo,
+    },
+    UpdateTodo {
+        id: i64,
+        base_version: Option<i64>,
+        data: UpdateTodo,
+    },
+    DeleteTodo {
+        id: i64,
+        base_version: Option<i64>,
+    },
+}
+
+/// Mutations are applied in order. Their data is validated one by one, so that an invalid
+/// mutation does not reject the whole batch.
+#[derive(Debug, Deserialize, Validate)]
+pub struct PushMutations {
+    #[validate(length(min = 1, max = 100))]
+    pub mutations: Vec<Mutation>,
+}
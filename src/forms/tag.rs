@@ -0,0 +1,51 @@
+use serde::Deserialize;
+use validator::{Validate, ValidationError};
+
+use super::nullable;
+
+const MAX_NAME_LENGTH: usize = 50;
+
+fn tag_name(name: &str) -> Result<(), ValidationError> {
+    let length = name.chars().count();
+
+    if length >= 1 && length <= MAX_NAME_LENGTH {
+        Ok(())
+    } else {
+        Err(ValidationError::new("tag_name"))
+    }
+}
+
+/// Every name of a list has to be a valid tag name.
+pub(super) fn tag_names(names: &[String]) -> Result<(), ValidationError> {
+    names.iter().map(|name| tag_name(name)).collect()
+}
+
+/// Colors are `#rrggbb` hex codes.
+fn color(color: &str) -> Result<(), ValidationError> {
+    if color.len() == 7
+        && color.starts_with('#')
+        && color[1..].chars().all(|c| c.is_ascii_hexdigit())
+    {
+        Ok(())
+    } else {
+        Err(ValidationError::new("color"))
+    }
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateTag {
+    #[validate(custom = "tag_name")]
+    pub name: String,
+    #[validate(custom = "color")]
+    pub color: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct UpdateTag {
+    #[validate(custom = "tag_name")]
+    pub name: Option<String>,
+    /// `null` removes the color.
+    #[serde(default, deserialize_with = "nullable")]
+    #[validate(custom = "color")]
+    pub color: Option<Option<String>>,
+}
@@ -1,11 +1,25 @@
 use serde::Deserialize;
-use validator::Validate;
+use validator::{Validate, ValidationError};
+
+use super::nullable;
+use super::tag::tag_names;
+use crate::model::{Due, DueView, Priority, Recurrence, TodoSort};
 
 #[derive(Debug, Deserialize, Validate)]
 pub struct CreateTodo {
     #[validate(length(min = 1))]
     pub content: String,
     pub work_list_id: i64,
+    pub due_at: Option<Due>,
+    /// `normal` by default.
+    pub priority: Option<Priority>,
+    /// Makes the todo a subtask of another todo of the same work list.
+    pub parent_id: Option<i64>,
+    /// Names of tags. Missing tags are created.
+    #[validate(custom = "tag_names", length(max = 20))]
+    pub tags: Option<Vec<String>>,
+    /// Makes the todo repeat. Requires `due_at`.
+    pub recurrence: Option<Recurrence>,
 }
 
 #[derive(Debug, Deserialize, Validate)]
@@ -13,4 +27,66 @@ pub struct UpdateTodo {
     #[validate(length(min = 1))]
     pub content: Option<String>,
     pub completed: Option<bool>,
+    /// `null` removes the due date.
+    #[serde(default, deserialize_with = "nullable")]
+    pub due_at: Option<Option<Due>>,
+    pub priority: Option<Priority>,
+    /// Moves the todo together with its subtasks to the end of another work list of the client.
+    /// The todo stops being a subtask, unless `parent_id` is given too.
+    pub work_list_id: Option<i64>,
+    /// `null` makes the todo a top-level one.
+    #[serde(default, deserialize_with = "nullable")]
+    pub parent_id: Option<Option<i64>>,
+    /// Together with `"completed": true` completes all subtasks as well.
+    pub complete_subtasks: Option<bool>,
+    /// Replaces tags of the todo. Missing tags are created.
+    #[validate(custom = "tag_names", length(max = 20))]
+    pub tags: Option<Vec<String>>,
+}
+
+/// Changes the rule of a recurring todo, or makes a todo recurring.
+#[derive(Debug, Deserialize, Validate)]
+pub struct SetRecurrence {
+    pub rule: Recurrence,
+}
+
+/// Duplicates todos at the end of a work list, in the order given.
+#[derive(Debug, Deserialize, Validate)]
+pub struct CopyTodos {
+    #[validate(length(min = 1, max = 100))]
+    pub todo_ids: Vec<i64>,
+    pub work_list_id: i64,
+}
+
+fn single_anchor(form: &MoveTodo) -> Result<(), ValidationError> {
+    if form.before.is_some() != form.after.is_some() {
+        Ok(())
+    } else {
+        Err(ValidationError::new("single_anchor"))
+    }
+}
+
+/// Places a todo right before or right after another todo of the same work list.
+#[derive(Debug, Deserialize, Validate)]
+#[validate(schema(function = "single_anchor"))]
+pub struct MoveTodo {
+    pub before: Option<i64>,
+    pub after: Option<i64>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ListTodos {
+    pub completed: Option<bool>,
+    pub work_list_id: Option<i64>,
+    /// Case-insensitive substring of the content.
+    #[validate(length(min = 1))]
+    pub content: Option<String>,
+    pub due: Option<DueView>,
+    /// Name of a tag todos have to carry.
+    pub tag: Option<String>,
+    pub sort: Option<TodoSort>,
+    #[validate(range(min = 1, max = 100))]
+    pub limit: Option<u32>,
+    /// Cursor returned as `next_cursor` of the previous page.
+    pub after: Option<String>,
 }
@@ -0,0 +1,59 @@
+use serde::Deserialize;
+use validator::{Validate, ValidationError};
+
+use super::nullable;
+use crate::model::EventKind;
+
+const MAX_URL_LENGTH: usize = 2000;
+
+/// Webhooks are called over HTTP(S) only.
+fn webhook_url(url: &str) -> Result<(), ValidationError> {
+    let lowercase = url.to_lowercase();
+
+    if url.len() <= MAX_URL_LENGTH
+        && (lowercase.starts_with("http://") || lowercase.starts_with("https://"))
+        && validator::validate_url(url)
+    {
+        Ok(())
+    } else {
+        Err(ValidationError::new("webhook_url"))
+    }
+}
+
+/// A webhook subscribed to no events would never be called.
+fn event_list(events: &[EventKind]) -> Result<(), ValidationError> {
+    if events.is_empty() {
+        Err(ValidationError::new("event_list"))
+    } else {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateWebhook {
+    #[validate(custom = "webhook_url")]
+    pub url: String,
+    #[validate(length(min = 16, max = 200))]
+    pub secret: String,
+    /// Omitted means every event.
+    #[validate(custom = "event_list")]
+    pub events: Option<Vec<EventKind>>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct UpdateWebhook {
+    #[validate(custom = "webhook_url")]
+    pub url: Option<String>,
+    #[validate(length(min = 16, max = 200))]
+    pub secret: Option<String>,
+    /// `null` subscribes to every event.
+    #[serde(default, deserialize_with = "nullable")]
+    #[validate(custom = "event_list")]
+    pub events: Option<Option<Vec<EventKind>>>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ListDeliveries {
+    #[validate(range(min = 1, max = 100))]
+    pub limit: Option<u32>,
+}
@@ -1,5 +1,8 @@
 use serde::Deserialize;
 use validator::Validate;
+
+use crate::model::WorkListSort;
+
 #[derive(Debug, Validate, Deserialize)]
 pub struct CreateWorkList {
     #[validate(length(min = 1))]
@@ -11,3 +14,19 @@ pub struct UpdateWorkList {
     #[validate(length(min = 1))]
     pub name: String,
 }
+
+#[derive(Debug, Validate, Deserialize)]
+pub struct ListWorkLists {
+    #[validate(range(min = 1, max = 100))]
+    pub limit: Option<u32>,
+    /// Cursor returned as `next_cursor` of the previous page.
+    pub after: Option<String>,
+    pub sort: Option<WorkListSort>,
+    #[validate(length(min = 1))]
+    pub name: Option<String>,
+    /// Name of a tag carried by at least one todo of the work list.
+    pub tag: Option<String>,
+    /// Maximum number of todos embedded in every work list, `0` omits them. All by default.
+    #[validate(range(min = 0, max = 1000))]
+    pub todos_limit: Option<u32>,
+}
@@ -0,0 +1,10 @@
+#[macro_use]
+extern crate validator_derive;
+
+pub mod controller;
+pub mod database;
+pub mod error;
+pub mod forms;
+pub mod model;
+pub mod scheduler;
+pub mod web_app;
@@ -1,17 +1,8 @@
-#[macro_use]
-extern crate validator_derive;
-
 use actix_web::{middleware, web, App, HttpServer};
 use anyhow::Result;
 use dotenv::dotenv;
 use std::env;
-
-mod controller;
-mod database;
-mod error;
-mod forms;
-mod model;
-mod web_app;
+use todo_list_api::{controller, database, model::ChangeFeed, scheduler, web_app};
 
 #[actix_rt::main]
 async fn main() -> Result<()> {
@@ -20,14 +11,30 @@ async fn main() -> Result<()> {
 
     let bind_host = env::var("BIND_HOST").unwrap_or("127.0.0.1:8080".to_string());
     let db_cfg = web::block(|| database::PoolConfig::from_env()).await?;
-    let db_pool = database::pool(db_cfg).await?;
+    let storage = database::connect(db_cfg).await?;
+    database::migrations::prepare(&storage).await?;
+
+    scheduler::spawn(storage.clone()).await?;
+    let changes = ChangeFeed::new();
 
     HttpServer::new(move || {
         App::new()
+            .wrap_fn(web_app::problem_instance)
             .wrap(middleware::Logger::default())
+            .app_data(web_app::path_config())
+            .app_data(web_app::query_config())
+            .service(web::scope("/api_keys").configure(controller::api_keys::init))
+            .service(web::scope("/changes").configure(controller::changes::init))
+            .service(web::scope("/events").configure(controller::events::init))
+            .service(web::scope("/search").configure(controller::search::init))
+            .service(web::scope("/sync").configure(controller::sync::init))
+            .service(web::scope("/tags").configure(controller::tags::init))
             .service(web::scope("/todos").configure(controller::todos::init))
+            .service(web::scope("/webhooks").configure(controller::webhooks::init))
             .service(web::scope("/work_lists").configure(controller::work_lists::init))
-            .data(db_pool.clone())
+            .default_service(web::route().to(web_app::not_found))
+            .data(storage.clone())
+            .data(changes.clone())
     })
     .bind(bind_host)?
     .run()
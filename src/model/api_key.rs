@@ -0,0 +1,322 @@
+use chrono::{DateTime, Utc};
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use serde::Serialize;
+
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+
+use super::Permissions;
+use crate::database::{ClientRepository, Storage};
+use crate::error::{Resource, WebError};
+use crate::forms::api_key::CreateApiKey;
+use crate::web_app::Client;
+
+const PREFIX_LENGTH: usize = 8;
+const SECRET_LENGTH: usize = 32;
+const SALT_LENGTH: usize = 16;
+/// `last_used_at` is refreshed at most this often, so that requests don't write every time.
+const TOUCH_INTERVAL_SECS: i64 = 300;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiKey {
+    pub id: i64,
+    pub client_id: i64,
+    /// Public part of the token, used to look the key up.
+    pub prefix: String,
+    pub label: Option<String>,
+    pub valid_to: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    #[serde(flatten)]
+    pub permissions: Permissions,
+}
+
+/// Freshly issued key. This is the only moment its token is available.
+#[derive(Debug, Serialize)]
+pub struct IssuedApiKey {
+    #[serde(flatten)]
+    pub api_key: ApiKey,
+    pub token: String,
+}
+
+/// Salted SHA-256 of a token. Tokens themselves are never persisted.
+#[derive(Debug, Clone)]
+pub struct KeyHash {
+    pub salt: String,
+    pub hash: String,
+}
+
+fn random_string(length: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(length)
+        .collect()
+}
+
+impl KeyHash {
+    pub fn new(token: &str) -> Self {
+        let salt = random_string(SALT_LENGTH);
+        let hash = Self::digest(&salt, token);
+
+        Self { salt, hash }
+    }
+
+    fn digest(salt: &str, token: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(salt.as_bytes());
+        hasher.update(token.as_bytes());
+        hex::encode(hasher.finalize())
+    }
+
+    /// Compares in constant time so response timing does not leak the stored hash.
+    pub fn verify(&self, token: &str) -> bool {
+        let candidate = Self::digest(&self.salt, token);
+        candidate.as_bytes().ct_eq(self.hash.as_bytes()).into()
+    }
+}
+
+impl ApiKey {
+    pub(crate) fn new(
+        id: i64,
+        client_id: i64,
+        prefix: String,
+        label: Option<String>,
+        valid_to: DateTime<Utc>,
+        last_used_at: Option<DateTime<Utc>>,
+        permissions: Permissions,
+    ) -> Self {
+        Self {
+            id,
+            client_id,
+            prefix,
+            label,
+            valid_to,
+            last_used_at,
+            permissions,
+        }
+    }
+
+    /// Keys with privileges the requesting key lacks are left out.
+    pub async fn list(client: &Client, storage: &Storage) -> Result<Vec<Self>, WebError> {
+        let mut api_keys = storage.list_api_keys(client.id()).await?;
+        api_keys.retain(|api_key| client.permissions().covers(&api_key.permissions));
+
+        Ok(api_keys)
+    }
+
+    pub async fn find(id: i64, client: &Client, storage: &Storage) -> Result<Self, WebError> {
+        storage
+            .find_api_key(id)
+            .await?
+            .filter(|api_key| api_key.client_id == client.id())
+            .ok_or(WebError::NotFound(Resource::ApiKey))
+    }
+
+    pub async fn create(
+        form: CreateApiKey,
+        client: &Client,
+        storage: &Storage,
+    ) -> Result<IssuedApiKey, WebError> {
+        let permissions = Permissions {
+            scopes: form.scopes,
+            work_list_ids: form.work_list_ids,
+        };
+
+        // A key can't be used to issue one with more privileges than itself, or one outliving it.
+        if !client.permissions().covers(&permissions) {
+            return Err(WebError::Forbidden);
+        }
+
+        let valid_to = match client.key_valid_to() {
+            Some(key_valid_to) => form.valid_to.min(key_valid_to),
+            None => form.valid_to,
+        };
+
+        Self::issue(
+            client.id(),
+            form.label.as_deref(),
+            valid_to,
+            &permissions,
+            storage,
+        )
+        .await
+    }
+
+    /// Generates a new token for given client and stores its hash.
+    pub async fn issue(
+        client_id: i64,
+        label: Option<&str>,
+        valid_to: DateTime<Utc>,
+        permissions: &Permissions,
+        storage: &Storage,
+    ) -> Result<IssuedApiKey, WebError> {
+        let token = Self::generate_token();
+        let api_key = storage
+            .create_api_key(
+                client_id,
+                label,
+                Self::token_prefix(&token),
+                &KeyHash::new(&token),
+                valid_to,
+                permissions,
+            )
+            .await?;
+
+        Ok(IssuedApiKey { api_key, token })
+    }
+
+    pub async fn revoke(self, client: &Client, storage: &Storage) -> Result<(), WebError> {
+        // Like issuing, revoking is limited to keys with no more privileges than the caller.
+        if self.client_id == client.id() && !client.permissions().covers(&self.permissions) {
+            return Err(WebError::Forbidden);
+        }
+
+        if self.client_id == client.id() && storage.revoke_api_key(self.id).await? {
+            Ok(())
+        } else {
+            Err(WebError::NotFound(Resource::ApiKey))
+        }
+    }
+
+    /// Generates token in `<prefix>.<secret>` form.
+    pub fn generate_token() -> String {
+        format!(
+            "{}.{}",
+            random_string(PREFIX_LENGTH),
+            random_string(SECRET_LENGTH)
+        )
+    }
+
+    /// Extracts lookup prefix of a token. Keys issued before hashing was introduced have no
+    /// separator - their first characters are used instead.
+    pub fn token_prefix(token: &str) -> &str {
+        match token.find('.') {
+            Some(idx) => &token[..idx],
+            None => token
+                .char_indices()
+                .nth(PREFIX_LENGTH)
+                .map(|(idx, _)| &token[..idx])
+                .unwrap_or(token),
+        }
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.valid_to <= Utc::now()
+    }
+
+    pub fn needs_touch(&self) -> bool {
+        self.last_used_at
+            .map(|at| at <= Utc::now() - chrono::Duration::seconds(TOUCH_INTERVAL_SECS))
+            .unwrap_or(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::MemoryRepository;
+    use crate::model::Scope;
+    use chrono::Duration;
+    use std::sync::Arc;
+
+    /// Client authorized by a key with given permissions, valid for `days`.
+    async fn authorized(
+        storage: &Storage,
+        client_id: i64,
+        permissions: Permissions,
+        days: i64,
+    ) -> Client {
+        let issued = ApiKey::issue(
+            client_id,
+            None,
+            Utc::now() + Duration::days(days),
+            &permissions,
+            storage,
+        )
+        .await
+        .unwrap();
+
+        Client::authorize(&issued.token, storage).await.unwrap()
+    }
+
+    fn form(valid_to: DateTime<Utc>, scopes: Option<Vec<Scope>>) -> CreateApiKey {
+        CreateApiKey {
+            label: None,
+            valid_to,
+            scopes,
+            work_list_ids: None,
+        }
+    }
+
+    fn todos_only() -> Permissions {
+        Permissions {
+            scopes: Some(vec![Scope::TodosWrite, Scope::ApiKeysWrite]),
+            work_list_ids: None,
+        }
+    }
+
+    #[actix_rt::test]
+    async fn creates_keys_within_the_callers_privileges() {
+        let repository = MemoryRepository::new();
+        let client_id = repository.seed_client("Keys", "seed.token", Duration::days(1));
+        let storage: Storage = Arc::new(repository);
+        let caller = authorized(&storage, client_id, todos_only(), 10).await;
+        let valid_to = Utc::now() + Duration::days(5);
+
+        let wider = ApiKey::create(form(valid_to, None), &caller, &storage).await;
+        assert!(matches!(wider, Err(WebError::Forbidden)));
+
+        let other_scope = Some(vec![Scope::WebhooksRead]);
+        let other = ApiKey::create(form(valid_to, other_scope), &caller, &storage).await;
+        assert!(matches!(other, Err(WebError::Forbidden)));
+
+        let narrower = Some(vec![Scope::TodosRead]);
+        let issued = ApiKey::create(form(valid_to, narrower), &caller, &storage)
+            .await
+            .unwrap();
+        assert_eq!(issued.api_key.valid_to, valid_to);
+    }
+
+    #[actix_rt::test]
+    async fn caps_expiry_at_the_callers_key() {
+        let repository = MemoryRepository::new();
+        let client_id = repository.seed_client("Keys", "seed.token", Duration::days(1));
+        let storage: Storage = Arc::new(repository);
+        let caller = authorized(&storage, client_id, Permissions::full(), 10).await;
+        let caller_valid_to = caller.key_valid_to().unwrap();
+
+        let form = form(Utc::now() + Duration::days(365), None);
+        let issued = ApiKey::create(form, &caller, &storage).await.unwrap();
+        assert_eq!(issued.api_key.valid_to, caller_valid_to);
+    }
+
+    #[actix_rt::test]
+    async fn lists_and_revokes_keys_within_the_callers_privileges() {
+        let repository = MemoryRepository::new();
+        let client_id = repository.seed_client("Keys", "seed.token", Duration::days(1));
+        let storage: Storage = Arc::new(repository);
+        let caller = authorized(&storage, client_id, todos_only(), 10).await;
+
+        // The seeded key and the caller's own key are listed only when covered.
+        let listed = ApiKey::list(&caller, &storage).await.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].permissions, todos_only());
+
+        let full = ApiKey::list(&Client::new(client_id, "Keys".to_string(), "UTC"), &storage)
+            .await
+            .unwrap();
+        let seeded = full
+            .into_iter()
+            .find(|api_key| api_key.permissions == Permissions::full())
+            .unwrap();
+        let revoked = seeded.clone().revoke(&caller, &storage).await;
+        assert!(matches!(revoked, Err(WebError::Forbidden)));
+
+        let other = Client::new(client_id + 1000, "Other".to_string(), "UTC");
+        let revoked = seeded.clone().revoke(&other, &storage).await;
+        assert!(matches!(revoked, Err(WebError::NotFound(_))));
+
+        let owner = authorized(&storage, client_id, Permissions::full(), 10).await;
+        seeded.revoke(&owner, &storage).await.unwrap();
+    }
+}
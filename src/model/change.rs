@@ -0,0 +1,44 @@
+use std::fmt;
+use tokio::sync::broadcast;
+
+use super::Event;
+
+/// Events kept for streams which fall behind. Slower streams skip them and are told so.
+const FEED_CAPACITY: usize = 1024;
+
+/// Broadcasts events of all clients to the streams open on this instance as soon as they are
+/// recorded.
+#[derive(Clone)]
+pub struct ChangeFeed {
+    sender: broadcast::Sender<Event>,
+}
+
+impl ChangeFeed {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(FEED_CAPACITY);
+        Self { sender }
+    }
+
+    pub fn publish(&self, event: Event) {
+        // Sending fails only when no stream is open.
+        let _ = self.sender.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.sender.subscribe()
+    }
+}
+
+impl Default for ChangeFeed {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for ChangeFeed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChangeFeed")
+            .field("streams", &self.sender.receiver_count())
+            .finish()
+    }
+}
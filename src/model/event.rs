@@ -0,0 +1,128 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{Permissions, Scope};
+use crate::web_app::Client;
+
+/// Change of a todo or work list webhooks can subscribe to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EventKind {
+    #[serde(rename = "todo.created")]
+    TodoCreated,
+    /// Any change of a todo, except for completing it.
+    #[serde(rename = "todo.updated")]
+    TodoUpdated,
+    #[serde(rename = "todo.completed")]
+    TodoCompleted,
+    #[serde(rename = "todo.deleted")]
+    TodoDeleted,
+    #[serde(rename = "work_list.created")]
+    WorkListCreated,
+    #[serde(rename = "work_list.updated")]
+    WorkListUpdated,
+    #[serde(rename = "work_list.deleted")]
+    WorkListDeleted,
+}
+
+impl EventKind {
+    const ALL: [EventKind; 7] = [
+        EventKind::TodoCreated,
+        EventKind::TodoUpdated,
+        EventKind::TodoCompleted,
+        EventKind::TodoDeleted,
+        EventKind::WorkListCreated,
+        EventKind::WorkListUpdated,
+        EventKind::WorkListDeleted,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        use EventKind::*;
+
+        match self {
+            TodoCreated => "todo.created",
+            TodoUpdated => "todo.updated",
+            TodoCompleted => "todo.completed",
+            TodoDeleted => "todo.deleted",
+            WorkListCreated => "work_list.created",
+            WorkListUpdated => "work_list.updated",
+            WorkListDeleted => "work_list.deleted",
+        }
+    }
+
+    pub fn parse(kind: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|candidate| candidate.as_str() == kind)
+    }
+}
+
+/// Event to be stored. Storage assigns its ID.
+#[derive(Debug)]
+pub struct NewEvent {
+    pub client_id: i64,
+    pub kind: EventKind,
+    pub data: Value,
+    pub created_at: DateTime<Utc>,
+}
+
+impl NewEvent {
+    pub fn new<T: Serialize>(kind: EventKind, client_id: i64, data: &T) -> Self {
+        Self {
+            client_id,
+            kind,
+            data: serde_json::to_value(data).unwrap_or_default(),
+            created_at: Utc::now(),
+        }
+    }
+}
+
+/// Recorded change of client's data. IDs grow with time.
+#[derive(Debug, Clone, Serialize)]
+pub struct Event {
+    pub id: i64,
+    #[serde(skip)]
+    pub client_id: i64,
+    #[serde(rename = "event")]
+    pub kind: EventKind,
+    pub created_at: DateTime<Utc>,
+    /// The todo or work list after the change, or just its IDs once it is deleted.
+    pub data: Value,
+}
+
+impl Event {
+    pub fn work_list_id(&self) -> Option<i64> {
+        use EventKind::*;
+
+        let field = match self.kind {
+            TodoCreated | TodoUpdated | TodoCompleted | TodoDeleted => "work_list_id",
+            WorkListCreated | WorkListUpdated | WorkListDeleted => "id",
+        };
+
+        self.data.get(field).and_then(Value::as_i64)
+    }
+
+    /// Whether an API key with given permissions may read the changed todo or work list.
+    pub fn is_visible(&self, permissions: &Permissions) -> bool {
+        use EventKind::*;
+
+        let scope = match self.kind {
+            TodoCreated | TodoUpdated | TodoCompleted | TodoDeleted => Scope::TodosRead,
+            WorkListCreated | WorkListUpdated | WorkListDeleted => Scope::WorkListsRead,
+        };
+
+        permissions.allows(scope)
+            && self
+                .work_list_id()
+                .map(|id| permissions.allows_work_list(id))
+                .unwrap_or(false)
+    }
+
+    /// Pushes an event recorded by storage to open change streams.
+    pub fn publish(self, client: &Client) {
+        if let Some(changes) = client.changes() {
+            changes.publish(self);
+        }
+    }
+}
@@ -1,5 +1,30 @@
+mod api_key;
+mod change;
+mod event;
+mod page;
+mod permissions;
+mod recurrence;
+mod reminder;
+mod search;
+mod sync;
+mod tag;
 mod todo;
+mod webhook;
 mod work_list;
 
-pub use todo::Todo;
-pub use work_list::WorkList;
+pub use api_key::{ApiKey, IssuedApiKey, KeyHash};
+pub use change::ChangeFeed;
+pub use event::{Event, EventKind, NewEvent};
+pub use page::Page;
+pub use permissions::{Permissions, Scope};
+pub use recurrence::{Frequency, Recurrence};
+pub use reminder::Reminder;
+pub use search::{SearchKind, SearchQuery, SearchResult, MATCH_END, MATCH_START};
+pub use sync::{MutationResult, SyncChanges, SyncItem, Tombstone, TombstoneKind};
+pub use tag::Tag;
+pub use todo::{
+    Due, DueCondition, DueView, NewTodo, Priority, Progress, Todo, TodoCascade, TodoChanges,
+    TodoPosition, TodoQuery, TodoSort, TodoWrite, POSITION_STEP,
+};
+pub use webhook::{DeliveryAttempt, DeliveryStatus, PendingDelivery, Webhook, WebhookDelivery};
+pub use work_list::{WorkList, WorkListPosition, WorkListQuery, WorkListSort};
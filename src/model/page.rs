@@ -0,0 +1,81 @@
+use actix_web::{error::Error, http::header, HttpRequest, HttpResponse, Responder};
+use futures::future::{ready, Ready};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::error::WebError;
+
+pub const DEFAULT_PAGE_SIZE: u32 = 50;
+
+/// Slice of a collection. `next_cursor` is set when there are more items to fetch.
+#[derive(Debug, Serialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+}
+
+impl<T> Page<T> {
+    /// Builds a page out of up to `limit + 1` fetched items - the extra one only tells that
+    /// there is a next page. `position` returns the cursor pointing past given item.
+    pub fn new(mut items: Vec<T>, limit: u32, position: impl FnOnce(&T) -> String) -> Self {
+        let limit = limit as usize;
+        let next_cursor = if items.len() > limit {
+            items.truncate(limit);
+            items.last().map(position)
+        } else {
+            None
+        };
+
+        Self { items, next_cursor }
+    }
+}
+
+/// Cursors are opaque to clients. Internally they are hex-encoded JSON of the last seen position.
+pub fn encode_cursor<P: Serialize>(position: &P) -> String {
+    hex::encode(serde_json::to_vec(position).unwrap_or_default())
+}
+
+pub fn decode_cursor<P: DeserializeOwned>(cursor: &str) -> Result<P, WebError> {
+    hex::decode(cursor)
+        .ok()
+        .and_then(|json| serde_json::from_slice(&json).ok())
+        .ok_or(WebError::BadRequest("Invalid cursor".to_string()))
+}
+
+/// Same request with `after` set to the given cursor.
+fn next_page_url(req: &HttpRequest, cursor: &str) -> String {
+    let mut params: Vec<&str> = req
+        .query_string()
+        .split('&')
+        .filter(|param| !param.is_empty() && !param.starts_with("after="))
+        .collect();
+    let after = format!("after={}", cursor);
+    params.push(&after);
+
+    format!("{}?{}", req.path(), params.join("&"))
+}
+
+impl<T: Serialize> Responder for Page<T> {
+    type Error = Error;
+    type Future = Ready<Result<HttpResponse, Error>>;
+
+    fn respond_to(self, req: &HttpRequest) -> Self::Future {
+        let body = match serde_json::to_string(&self) {
+            Ok(body) => body,
+            Err(err) => {
+                let message = format!("Failed to serialize response: {}", err);
+                return ready(Err(WebError::Internal(message).into()));
+            }
+        };
+        let mut response = HttpResponse::Ok();
+
+        if let Some(cursor) = self.next_cursor.as_ref() {
+            response.header(
+                header::LINK,
+                format!("<{}>; rel=\"next\"", next_page_url(req, cursor)),
+            );
+        }
+
+        ready(Ok(response.content_type("application/json").body(body)))
+    }
+}
@@ -0,0 +1,250 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Scope {
+    #[serde(rename = "work_lists:read")]
+    WorkListsRead,
+    #[serde(rename = "work_lists:write")]
+    WorkListsWrite,
+    #[serde(rename = "todos:read")]
+    TodosRead,
+    #[serde(rename = "todos:write")]
+    TodosWrite,
+    #[serde(rename = "api_keys:read")]
+    ApiKeysRead,
+    #[serde(rename = "api_keys:write")]
+    ApiKeysWrite,
+    #[serde(rename = "webhooks:read")]
+    WebhooksRead,
+    #[serde(rename = "webhooks:write")]
+    WebhooksWrite,
+}
+
+impl Scope {
+    const ALL: [Scope; 8] = [
+        Scope::WorkListsRead,
+        Scope::WorkListsWrite,
+        Scope::TodosRead,
+        Scope::TodosWrite,
+        Scope::ApiKeysRead,
+        Scope::ApiKeysWrite,
+        Scope::WebhooksRead,
+        Scope::WebhooksWrite,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        use Scope::*;
+
+        match self {
+            WorkListsRead => "work_lists:read",
+            WorkListsWrite => "work_lists:write",
+            TodosRead => "todos:read",
+            TodosWrite => "todos:write",
+            ApiKeysRead => "api_keys:read",
+            ApiKeysWrite => "api_keys:write",
+            WebhooksRead => "webhooks:read",
+            WebhooksWrite => "webhooks:write",
+        }
+    }
+
+    pub fn parse(scope: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|candidate| candidate.as_str() == scope)
+    }
+
+    /// Write access to a resource implies read access to it.
+    fn grants(self, requested: Scope) -> bool {
+        use Scope::*;
+
+        self == requested
+            || match (self, requested) {
+                (WorkListsWrite, WorkListsRead) => true,
+                (TodosWrite, TodosRead) => true,
+                (ApiKeysWrite, ApiKeysRead) => true,
+                (WebhooksWrite, WebhooksRead) => true,
+                _ => false,
+            }
+    }
+}
+
+/// What an API key is allowed to do. Keys without restrictions have full access.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Permissions {
+    /// `None` grants every scope.
+    pub scopes: Option<Vec<Scope>>,
+    /// `None` grants access to every work list of the client.
+    pub work_list_ids: Option<Vec<i64>>,
+}
+
+impl Permissions {
+    pub fn full() -> Self {
+        Self::default()
+    }
+
+    /// Parses space-separated scopes and comma-separated work list IDs, as stored in database.
+    /// Unknown scopes are ignored, so they never grant anything.
+    pub fn from_columns(scopes: Option<&str>, work_list_ids: Option<&str>) -> Self {
+        Self {
+            scopes: scopes
+                .map(|scopes| scopes.split_whitespace().filter_map(Scope::parse).collect()),
+            work_list_ids: work_list_ids.map(|ids| {
+                ids.split(',')
+                    .filter_map(|id| id.trim().parse().ok())
+                    .collect()
+            }),
+        }
+    }
+
+    pub fn scopes_column(&self) -> Option<String> {
+        self.scopes.as_ref().map(|scopes| {
+            scopes
+                .iter()
+                .map(|scope| scope.as_str())
+                .collect::<Vec<&str>>()
+                .join(" ")
+        })
+    }
+
+    pub fn work_list_ids_column(&self) -> Option<String> {
+        self.work_list_ids.as_ref().map(|ids| {
+            ids.iter()
+                .map(|id| id.to_string())
+                .collect::<Vec<String>>()
+                .join(",")
+        })
+    }
+
+    pub fn allows(&self, scope: Scope) -> bool {
+        self.scopes
+            .as_ref()
+            .map(|scopes| scopes.iter().any(|granted| granted.grants(scope)))
+            .unwrap_or(true)
+    }
+
+    pub fn allows_work_list(&self, work_list_id: i64) -> bool {
+        self.work_list_ids
+            .as_ref()
+            .map(|ids| ids.contains(&work_list_id))
+            .unwrap_or(true)
+    }
+
+    pub fn is_restricted_to_work_lists(&self) -> bool {
+        self.work_list_ids.is_some()
+    }
+
+    /// Checks whether `other` grants nothing beyond these permissions.
+    pub fn covers(&self, other: &Permissions) -> bool {
+        let scopes_covered = match other.scopes.as_ref() {
+            Some(scopes) => scopes.iter().all(|scope| self.allows(*scope)),
+            None => self.scopes.is_none(),
+        };
+        let work_lists_covered = match other.work_list_ids.as_ref() {
+            Some(ids) => ids.iter().all(|id| self.allows_work_list(*id)),
+            None => self.work_list_ids.is_none(),
+        };
+
+        scopes_covered && work_lists_covered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Scope::*;
+
+    fn permissions(scopes: Option<&[Scope]>, work_list_ids: Option<&[i64]>) -> Permissions {
+        Permissions {
+            scopes: scopes.map(|scopes| scopes.to_vec()),
+            work_list_ids: work_list_ids.map(|ids| ids.to_vec()),
+        }
+    }
+
+    #[test]
+    fn write_scopes_allow_reading() {
+        let writer = permissions(Some(&[TodosWrite]), None);
+        assert!(writer.allows(TodosWrite));
+        assert!(writer.allows(TodosRead));
+        assert!(!writer.allows(WorkListsRead));
+
+        let reader = permissions(Some(&[TodosRead]), None);
+        assert!(reader.allows(TodosRead));
+        assert!(!reader.allows(TodosWrite));
+
+        let nothing = permissions(Some(&[]), None);
+        assert!(Scope::ALL.iter().all(|scope| !nothing.allows(*scope)));
+        assert!(Scope::ALL
+            .iter()
+            .all(|scope| Permissions::full().allows(*scope)));
+    }
+
+    #[test]
+    fn restricts_work_lists() {
+        let restricted = permissions(None, Some(&[1, 2]));
+        assert!(restricted.allows_work_list(1));
+        assert!(!restricted.allows_work_list(3));
+        assert!(restricted.is_restricted_to_work_lists());
+
+        assert!(Permissions::full().allows_work_list(3));
+        assert!(!Permissions::full().is_restricted_to_work_lists());
+        assert!(!permissions(None, Some(&[])).allows_work_list(1));
+    }
+
+    #[test]
+    fn covers_subsets_of_scopes() {
+        let writer = permissions(Some(&[TodosWrite, WorkListsRead]), None);
+
+        assert!(writer.covers(&writer));
+        assert!(writer.covers(&permissions(Some(&[TodosRead]), None)));
+        assert!(writer.covers(&permissions(Some(&[TodosWrite, TodosRead]), None)));
+        assert!(writer.covers(&permissions(Some(&[]), None)));
+        assert!(!writer.covers(&permissions(Some(&[WorkListsWrite]), None)));
+        assert!(!writer.covers(&permissions(Some(&[TodosRead, ApiKeysRead]), None)));
+        // Keys without scopes grant every scope, including ones added later.
+        assert!(!writer.covers(&Permissions::full()));
+        assert!(!permissions(Some(&Scope::ALL), None).covers(&Permissions::full()));
+        assert!(Permissions::full().covers(&writer));
+    }
+
+    #[test]
+    fn covers_subsets_of_work_lists() {
+        let restricted = permissions(None, Some(&[1, 2]));
+
+        assert!(restricted.covers(&permissions(None, Some(&[1]))));
+        assert!(restricted.covers(&permissions(None, Some(&[2, 1]))));
+        assert!(restricted.covers(&permissions(None, Some(&[]))));
+        assert!(!restricted.covers(&permissions(None, Some(&[1, 3]))));
+        // Unrestricted keys reach work lists created later.
+        assert!(!restricted.covers(&Permissions::full()));
+        assert!(Permissions::full().covers(&restricted));
+    }
+
+    #[test]
+    fn covers_scopes_and_work_lists_together() {
+        let caller = permissions(Some(&[TodosWrite]), Some(&[1]));
+
+        assert!(caller.covers(&permissions(Some(&[TodosRead]), Some(&[1]))));
+        assert!(!caller.covers(&permissions(Some(&[TodosRead]), None)));
+        assert!(!caller.covers(&permissions(None, Some(&[1]))));
+        assert!(!caller.covers(&permissions(Some(&[TodosRead]), Some(&[2]))));
+    }
+
+    #[test]
+    fn reads_columns() {
+        let read =
+            Permissions::from_columns(Some("todos:write unknown todos:read"), Some("3, x,1"));
+        assert_eq!(
+            read,
+            permissions(Some(&[TodosWrite, TodosRead]), Some(&[3, 1]))
+        );
+        assert_eq!(
+            read.scopes_column().as_deref(),
+            Some("todos:write todos:read")
+        );
+        assert_eq!(read.work_list_ids_column().as_deref(), Some("3,1"));
+
+        assert_eq!(Permissions::from_columns(None, None), Permissions::full());
+        assert_eq!(Permissions::full().scopes_column(), None);
+    }
+}
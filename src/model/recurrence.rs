@@ -0,0 +1,485 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDate, TimeZone, Utc, Weekday};
+use serde::de::{self, Deserializer};
+use serde::{Deserialize, Serialize, Serializer};
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use super::Due;
+
+/// Monthly and yearly rules look at most this many periods ahead for a month having the day of
+/// the previous occurrence. Enough for the 29th of February.
+const MAX_PERIODS_AHEAD: u32 = 12;
+const MAX_INTERVAL: u32 = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+/// Repetition of a todo, described by a subset of RFC 5545 RRULE: `FREQ`, `INTERVAL`, `BYDAY`
+/// (weekly rules only), `COUNT` and `UNTIL`, e.g. `FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,TH`.
+/// `daily`, `weekly`, `monthly` and `yearly` are accepted as shorthands.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Recurrence {
+    pub frequency: Frequency,
+    pub interval: u32,
+    /// Days of week of weekly rules. Empty means the day of the previous occurrence.
+    pub by_day: Vec<Weekday>,
+    /// Number of occurrences in the whole series.
+    pub count: Option<u32>,
+    /// Last day an occurrence may fall on.
+    pub until: Option<NaiveDate>,
+}
+
+const WEEKDAYS: [(&str, Weekday); 7] = [
+    ("MO", Weekday::Mon),
+    ("TU", Weekday::Tue),
+    ("WE", Weekday::Wed),
+    ("TH", Weekday::Thu),
+    ("FR", Weekday::Fri),
+    ("SA", Weekday::Sat),
+    ("SU", Weekday::Sun),
+];
+
+impl Frequency {
+    fn as_str(self) -> &'static str {
+        use Frequency::*;
+
+        match self {
+            Daily => "DAILY",
+            Weekly => "WEEKLY",
+            Monthly => "MONTHLY",
+            Yearly => "YEARLY",
+        }
+    }
+
+    fn parse(frequency: &str) -> Option<Self> {
+        use Frequency::*;
+
+        [Daily, Weekly, Monthly, Yearly]
+            .iter()
+            .copied()
+            .find(|candidate| candidate.as_str().eq_ignore_ascii_case(frequency))
+    }
+}
+
+fn parse_weekday(day: &str) -> Option<Weekday> {
+    WEEKDAYS
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(day))
+        .map(|(_, weekday)| *weekday)
+}
+
+fn weekday_name(weekday: Weekday) -> &'static str {
+    WEEKDAYS
+        .iter()
+        .find(|(_, candidate)| *candidate == weekday)
+        .map(|(name, _)| *name)
+        .unwrap_or("MO")
+}
+
+fn parse_positive(value: &str) -> Result<u32, String> {
+    value
+        .parse()
+        .ok()
+        .filter(|value| *value > 0 && *value <= MAX_INTERVAL)
+        .ok_or_else(|| {
+            format!(
+                "Expected a number from 1 to {}, got `{}`",
+                MAX_INTERVAL, value
+            )
+        })
+}
+
+/// `UNTIL` is a date (`20200501`) or a UTC timestamp (`20200501T170000Z`), of which only the
+/// date is used.
+fn parse_until(value: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(value.get(..8).unwrap_or(value), "%Y%m%d")
+        .map_err(|_| format!("Invalid UNTIL date `{}`", value))
+}
+
+/// Same day of month `months` months after given date, `None` if that month is too short.
+fn add_months(date: NaiveDate, months: u32) -> Option<NaiveDate> {
+    let month0 = date.month0() + months;
+    let year = date.year() + (month0 / 12) as i32;
+
+    NaiveDate::from_ymd_opt(year, month0 % 12 + 1, date.day())
+}
+
+impl Recurrence {
+    /// Date of the occurrence following the one on `date`, ignoring `COUNT` and `UNTIL`. `None`
+    /// past the last representable date.
+    fn next_date(&self, date: NaiveDate) -> Option<NaiveDate> {
+        use Frequency::*;
+
+        match self.frequency {
+            Daily => date.checked_add_signed(Duration::days(self.interval as i64)),
+            Weekly if self.by_day.is_empty() => {
+                date.checked_add_signed(Duration::weeks(self.interval as i64))
+            }
+            Weekly => {
+                let weekday = date.weekday().num_days_from_monday() as i64;
+                let monday = date.checked_sub_signed(Duration::days(weekday))?;
+                let next_monday = monday.checked_add_signed(Duration::weeks(self.interval as i64));
+
+                (weekday + 1..7)
+                    .filter_map(|offset| monday.checked_add_signed(Duration::days(offset)))
+                    .chain((0..7).filter_map(|offset| {
+                        next_monday?.checked_add_signed(Duration::days(offset))
+                    }))
+                    .find(|day| self.by_day.contains(&day.weekday()))
+            }
+            // Months and years without the day of the previous occurrence are skipped, as in
+            // RFC 5545.
+            Monthly => (1..=MAX_PERIODS_AHEAD)
+                .filter_map(|periods| add_months(date, periods * self.interval))
+                .next(),
+            Yearly => (1..=MAX_PERIODS_AHEAD)
+                .filter_map(|periods| add_months(date, periods * self.interval * 12))
+                .next(),
+        }
+    }
+
+    /// Due date of the occurrence following one due at `due`, or `None` once the series ends
+    /// according to `UNTIL` or runs past the last representable date. Times repeat at the same
+    /// local time of given time zone.
+    pub fn next_due<Tz: TimeZone>(&self, due: Due, time_zone: &Tz) -> Option<Due> {
+        let next = match due {
+            Due::On(date) => Due::On(self.next_date(date)?),
+            Due::At(time) => {
+                let local = time.with_timezone(time_zone).naive_local();
+                let date = self.next_date(local.date())?;
+                let next = date.and_time(local.time());
+
+                // Local times skipped by a DST change are moved past the gap.
+                let time = (0..3)
+                    .filter_map(|hour| {
+                        let shifted = next.checked_add_signed(Duration::hours(hour))?;
+                        time_zone.from_local_datetime(&shifted).earliest()
+                    })
+                    .next()
+                    .map(|time| time.with_timezone(&Utc))
+                    .unwrap_or_else(|| DateTime::from_utc(next, Utc));
+
+                Due::At(time)
+            }
+        };
+
+        let date = match next {
+            Due::On(date) => date,
+            Due::At(time) => time.with_timezone(time_zone).date().naive_local(),
+        };
+
+        match self.until {
+            Some(until) if date > until => None,
+            _ => Some(next),
+        }
+    }
+}
+
+impl FromStr for Recurrence {
+    type Err = String;
+
+    fn from_str(rule: &str) -> Result<Self, Self::Err> {
+        if let Some(frequency) = Frequency::parse(rule) {
+            return Ok(Recurrence {
+                frequency,
+                interval: 1,
+                by_day: vec![],
+                count: None,
+                until: None,
+            });
+        }
+
+        let rule = rule.trim_start_matches("RRULE:").trim_end_matches(';');
+        let mut frequency = None;
+        let mut recurrence = Recurrence {
+            frequency: Frequency::Daily,
+            interval: 1,
+            by_day: vec![],
+            count: None,
+            until: None,
+        };
+
+        for part in rule.split(';') {
+            let mut pair = part.splitn(2, '=');
+            let name = pair.next().unwrap_or("").to_uppercase();
+            let value = pair
+                .next()
+                .ok_or_else(|| format!("Expected NAME=VALUE, got `{}`", part))?;
+
+            match name.as_str() {
+                "FREQ" => {
+                    frequency = Some(
+                        Frequency::parse(value)
+                            .ok_or_else(|| format!("Unsupported FREQ `{}`", value))?,
+                    )
+                }
+                "INTERVAL" => recurrence.interval = parse_positive(value)?,
+                "COUNT" => recurrence.count = Some(parse_positive(value)?),
+                "UNTIL" => recurrence.until = Some(parse_until(value)?),
+                "BYDAY" => {
+                    recurrence.by_day = value
+                        .split(',')
+                        .map(|day| {
+                            parse_weekday(day).ok_or_else(|| format!("Invalid BYDAY `{}`", day))
+                        })
+                        .collect::<Result<Vec<Weekday>, String>>()?
+                }
+                _ => return Err(format!("Unsupported rule part `{}`", name)),
+            }
+        }
+
+        recurrence.frequency = frequency.ok_or_else(|| "FREQ is required".to_string())?;
+
+        if !recurrence.by_day.is_empty() && recurrence.frequency != Frequency::Weekly {
+            return Err("BYDAY is supported only with FREQ=WEEKLY".to_string());
+        }
+
+        if recurrence.count.is_some() && recurrence.until.is_some() {
+            return Err("COUNT and UNTIL can't be used together".to_string());
+        }
+
+        Ok(recurrence)
+    }
+}
+
+/// Normalized RRULE, e.g. `FREQ=WEEKLY;BYDAY=MO,TH`. Default values are omitted.
+impl Display for Recurrence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "FREQ={}", self.frequency.as_str())?;
+
+        if self.interval != 1 {
+            write!(f, ";INTERVAL={}", self.interval)?;
+        }
+
+        if !self.by_day.is_empty() {
+            let days: Vec<&str> = self.by_day.iter().map(|day| weekday_name(*day)).collect();
+            write!(f, ";BYDAY={}", days.join(","))?;
+        }
+
+        if let Some(count) = self.count {
+            write!(f, ";COUNT={}", count)?;
+        }
+
+        if let Some(until) = self.until {
+            write!(f, ";UNTIL={}", until.format("%Y%m%d"))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Serialize for Recurrence {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Recurrence {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let rule = String::deserialize(deserializer)?;
+        rule.parse().map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::naive::MAX_DATE;
+    use chrono_tz::Europe::Prague;
+
+    fn rule(rule: &str) -> Recurrence {
+        rule.parse().unwrap()
+    }
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd(year, month, day)
+    }
+
+    fn next_date(recurrence: &str, from: NaiveDate) -> Option<NaiveDate> {
+        rule(recurrence)
+            .next_due(Due::On(from), &Utc)
+            .and_then(Due::date)
+    }
+
+    /// Next due time of a todo due at given local time in Prague, as local time.
+    fn next_local_time(recurrence: &str, from: &str) -> Option<String> {
+        let local = Prague
+            .datetime_from_str(from, "%Y-%m-%d %H:%M")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        rule(recurrence)
+            .next_due(Due::At(local), &Prague)
+            .and_then(Due::time)
+            .map(|time| {
+                time.with_timezone(&Prague)
+                    .format("%Y-%m-%d %H:%M %Z")
+                    .to_string()
+            })
+    }
+
+    #[test]
+    fn parses_shorthands() {
+        assert_eq!(rule("daily").frequency, Frequency::Daily);
+        assert_eq!(rule("Weekly").frequency, Frequency::Weekly);
+        assert_eq!(rule("monthly").to_string(), "FREQ=MONTHLY");
+        assert_eq!(rule("yearly").interval, 1);
+    }
+
+    #[test]
+    fn parses_rules() {
+        let recurrence = rule("RRULE:freq=weekly;interval=2;byday=mo,TH;COUNT=5;");
+        assert_eq!(recurrence.frequency, Frequency::Weekly);
+        assert_eq!(recurrence.interval, 2);
+        assert_eq!(recurrence.by_day, vec![Weekday::Mon, Weekday::Thu]);
+        assert_eq!(recurrence.count, Some(5));
+        assert_eq!(
+            recurrence.to_string(),
+            "FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,TH;COUNT=5"
+        );
+
+        let recurrence = rule("FREQ=DAILY;UNTIL=20200501T170000Z");
+        assert_eq!(recurrence.until, Some(date(2020, 5, 1)));
+        assert_eq!(recurrence.to_string(), "FREQ=DAILY;UNTIL=20200501");
+    }
+
+    #[test]
+    fn rejects_invalid_rules() {
+        for invalid in &[
+            "",
+            "hourly",
+            "INTERVAL=2",
+            "FREQ=HOURLY",
+            "FREQ=DAILY;INTERVAL=0",
+            "FREQ=DAILY;INTERVAL=1001",
+            "FREQ=DAILY;COUNT=x",
+            "FREQ=DAILY;UNTIL=2020",
+            "FREQ=DAILY;BYDAY=MO",
+            "FREQ=WEEKLY;BYDAY=XX",
+            "FREQ=DAILY;COUNT=2;UNTIL=20200501",
+            "FREQ=DAILY;BYMONTH=1",
+            "FREQ",
+        ] {
+            assert!(invalid.parse::<Recurrence>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn repeats_by_frequency_and_interval() {
+        let from = date(2020, 5, 7);
+
+        assert_eq!(next_date("daily", from), Some(date(2020, 5, 8)));
+        assert_eq!(
+            next_date("FREQ=DAILY;INTERVAL=3", from),
+            Some(date(2020, 5, 10))
+        );
+        assert_eq!(next_date("weekly", from), Some(date(2020, 5, 14)));
+        assert_eq!(
+            next_date("FREQ=WEEKLY;INTERVAL=2", from),
+            Some(date(2020, 5, 21))
+        );
+        assert_eq!(next_date("monthly", from), Some(date(2020, 6, 7)));
+        assert_eq!(next_date("yearly", from), Some(date(2021, 5, 7)));
+    }
+
+    #[test]
+    fn repeats_on_days_of_week() {
+        let rule = "FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,TH";
+
+        // Thursday 7 May, the rest of the week has no matching day.
+        assert_eq!(next_date(rule, date(2020, 5, 7)), Some(date(2020, 5, 18)));
+        // Monday 4 May, Thursday of the same week follows.
+        assert_eq!(next_date(rule, date(2020, 5, 4)), Some(date(2020, 5, 7)));
+        // Sunday 10 May, not itself one of the days.
+        assert_eq!(
+            next_date("FREQ=WEEKLY;BYDAY=SA", date(2020, 5, 10)),
+            Some(date(2020, 5, 16))
+        );
+    }
+
+    #[test]
+    fn skips_months_without_the_day() {
+        assert_eq!(
+            next_date("monthly", date(2020, 1, 31)),
+            Some(date(2020, 3, 31))
+        );
+        assert_eq!(
+            next_date("monthly", date(2020, 12, 31)),
+            Some(date(2021, 1, 31))
+        );
+        assert_eq!(
+            next_date("FREQ=MONTHLY;INTERVAL=3", date(2020, 11, 30)),
+            Some(date(2021, 5, 30))
+        );
+        assert_eq!(
+            next_date("yearly", date(2020, 2, 29)),
+            Some(date(2024, 2, 29))
+        );
+    }
+
+    #[test]
+    fn ends_with_until() {
+        let rule = "FREQ=DAILY;UNTIL=20200502";
+
+        assert_eq!(next_date(rule, date(2020, 5, 1)), Some(date(2020, 5, 2)));
+        assert_eq!(next_date(rule, date(2020, 5, 2)), None);
+    }
+
+    #[test]
+    fn ignores_count() {
+        // Occurrences are counted by the todos of the series.
+        assert_eq!(
+            next_date("FREQ=DAILY;COUNT=1", date(2020, 5, 1)),
+            Some(date(2020, 5, 2))
+        );
+    }
+
+    #[test]
+    fn ends_past_the_last_date() {
+        assert_eq!(next_date("daily", MAX_DATE), None);
+        assert_eq!(next_date("weekly", MAX_DATE), None);
+        assert_eq!(next_date("FREQ=WEEKLY;BYDAY=MO", MAX_DATE), None);
+        assert_eq!(next_date("monthly", MAX_DATE), None);
+        assert_eq!(next_date("yearly", MAX_DATE), None);
+    }
+
+    #[test]
+    fn keeps_local_time_over_dst_changes() {
+        assert_eq!(
+            next_local_time("daily", "2020-03-28 09:00").as_deref(),
+            Some("2020-03-29 09:00 CEST")
+        );
+        assert_eq!(
+            next_local_time("daily", "2020-10-24 09:00").as_deref(),
+            Some("2020-10-25 09:00 CET")
+        );
+        // 2:30 does not exist on 29 March.
+        assert_eq!(
+            next_local_time("daily", "2020-03-28 02:30").as_deref(),
+            Some("2020-03-29 03:30 CEST")
+        );
+        // 2:30 happens twice on 25 October, the earlier one is used.
+        assert_eq!(
+            next_local_time("daily", "2020-10-24 02:30").as_deref(),
+            Some("2020-10-25 02:30 CEST")
+        );
+    }
+
+    #[test]
+    fn ends_with_until_in_local_time() {
+        // 23:30 on 1 May in Prague is still 1 May, though 21:30 UTC.
+        assert_eq!(
+            next_local_time("FREQ=DAILY;UNTIL=20200502", "2020-05-01 23:30").as_deref(),
+            Some("2020-05-02 23:30 CEST")
+        );
+        assert_eq!(
+            next_local_time("FREQ=DAILY;UNTIL=20200502", "2020-05-02 23:30"),
+            None
+        );
+    }
+}
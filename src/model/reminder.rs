@@ -0,0 +1,54 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use super::Todo;
+use crate::database::{ReminderRepository, Storage};
+use crate::error::{Resource, WebError};
+use crate::forms::reminder::CreateReminder;
+
+/// Reminders a single todo can have.
+const MAX_REMINDERS: usize = 10;
+
+/// Notification about a todo due at given time, delivered by the scheduler.
+#[derive(Debug, Clone, Serialize)]
+pub struct Reminder {
+    pub id: i64,
+    pub todo_id: i64,
+    pub remind_at: DateTime<Utc>,
+    pub sent_at: Option<DateTime<Utc>>,
+    /// Set when delivery was given up after repeated failures.
+    pub failed_at: Option<DateTime<Utc>>,
+    /// Failed delivery attempts so far.
+    #[serde(skip)]
+    pub attempts: u32,
+}
+
+impl Reminder {
+    /// Reminders of the todo ordered by time. Access to the todo is checked by the caller.
+    pub async fn list(todo: &Todo, storage: &Storage) -> Result<Vec<Self>, WebError> {
+        storage.list_reminders(todo.id).await
+    }
+
+    pub async fn create(
+        todo: &Todo,
+        form: CreateReminder,
+        storage: &Storage,
+    ) -> Result<Self, WebError> {
+        if storage.list_reminders(todo.id).await?.len() >= MAX_REMINDERS {
+            return Err(WebError::BadRequest(format!(
+                "A todo can have at most {} reminders",
+                MAX_REMINDERS
+            )));
+        }
+
+        storage.create_reminder(todo.id, form.remind_at).await
+    }
+
+    pub async fn delete(todo: &Todo, id: i64, storage: &Storage) -> Result<(), WebError> {
+        if storage.delete_reminder(id, todo.id).await? {
+            Ok(())
+        } else {
+            Err(WebError::NotFound(Resource::Reminder))
+        }
+    }
+}
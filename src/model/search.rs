@@ -0,0 +1,133 @@
+use serde::{Deserialize, Serialize};
+
+use super::Scope;
+use crate::database::{SearchRepository, Storage};
+use crate::error::WebError;
+use crate::forms::search::Search;
+use crate::web_app::Client;
+
+const DEFAULT_LIMIT: u32 = 20;
+
+/// Matched text is wrapped in these in result snippets.
+const HIGHLIGHT_START: &str = "<mark>";
+const HIGHLIGHT_END: &str = "</mark>";
+/// Storage wraps matched text in these. Snippets are HTML-escaped before they are turned into
+/// `HIGHLIGHT_START` and `HIGHLIGHT_END`, so that the text itself can't carry markup.
+pub const MATCH_START: &str = "\u{2}";
+pub const MATCH_END: &str = "\u{3}";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SearchKind {
+    #[serde(rename = "todo")]
+    Todo,
+    #[serde(rename = "work_list")]
+    WorkList,
+}
+
+impl SearchKind {
+    pub fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "todo" => Some(SearchKind::Todo),
+            "work_list" => Some(SearchKind::WorkList),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SearchResult {
+    pub kind: SearchKind,
+    /// ID of the matching todo or work list.
+    pub id: i64,
+    /// Work list the match belongs to. For work lists it is their own ID.
+    pub work_list_id: i64,
+    /// Fragment of the matching text as HTML, with matched terms highlighted. Storage returns
+    /// plain text with matches between `MATCH_START` and `MATCH_END`.
+    pub snippet: String,
+    /// Relevance, higher is better. Only comparable between results of the same search.
+    pub score: f64,
+}
+
+/// Criteria of a full-text search, as passed to storage.
+#[derive(Debug)]
+pub struct SearchQuery {
+    /// Words which all have to appear in the text. Every one matches as a prefix.
+    pub terms: Vec<String>,
+    pub kinds: Vec<SearchKind>,
+    /// `None` means every work list of the client.
+    pub work_list_ids: Option<Vec<i64>>,
+    pub limit: u32,
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Splits query into alphanumeric words, so that no search syntax reaches the database.
+fn terms(query: &str) -> Vec<String> {
+    query
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|term| !term.is_empty())
+        .map(|term| term.to_lowercase())
+        .collect()
+}
+
+impl SearchResult {
+    pub async fn search(
+        form: Search,
+        client: &Client,
+        storage: &Storage,
+    ) -> Result<Vec<Self>, WebError> {
+        let requested = match form.kind {
+            Some(kind) => vec![kind],
+            None => vec![SearchKind::Todo, SearchKind::WorkList],
+        };
+        let kinds: Vec<SearchKind> = requested
+            .iter()
+            .copied()
+            .filter(|kind| match kind {
+                SearchKind::Todo => client.permissions().allows(Scope::TodosRead),
+                SearchKind::WorkList => client.permissions().allows(Scope::WorkListsRead),
+            })
+            .collect();
+
+        if kinds.is_empty() {
+            return Err(WebError::Forbidden);
+        }
+
+        let terms = terms(&form.q);
+        if terms.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let query = SearchQuery {
+            terms,
+            kinds,
+            work_list_ids: client.permissions().work_list_ids.clone(),
+            limit: form.limit.unwrap_or(DEFAULT_LIMIT),
+        };
+
+        let mut results = storage.search(client.id(), &query).await?;
+
+        for result in results.iter_mut() {
+            result.snippet = escape_html(&result.snippet)
+                .replace(MATCH_START, HIGHLIGHT_START)
+                .replace(MATCH_END, HIGHLIGHT_END);
+        }
+
+        Ok(results)
+    }
+}
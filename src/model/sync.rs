@@ -0,0 +1,455 @@
+use chrono::{DateTime, Utc};
+use log::error;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use validator::Validate;
+
+use super::page::{decode_cursor, encode_cursor};
+use super::{Permissions, Scope, Todo, WorkList};
+use crate::database::{Storage, SyncRepository, WorkListRepository};
+use crate::error::{Resource, WebError};
+use crate::forms::sync::{Mutation, PushMutations, SyncQuery};
+use crate::web_app::Client;
+
+const DEFAULT_SYNC_LIMIT: u32 = 500;
+const MAX_MUTATION_ID_LENGTH: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TombstoneKind {
+    Todo,
+    WorkList,
+}
+
+impl TombstoneKind {
+    pub fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "todo" => Some(TombstoneKind::Todo),
+            "work_list" => Some(TombstoneKind::WorkList),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TombstoneKind::Todo => "todo",
+            TombstoneKind::WorkList => "work_list",
+        }
+    }
+}
+
+/// Trace of a deleted todo or work list, kept so that synced clients learn about the deletion.
+#[derive(Debug, Clone, Serialize)]
+pub struct Tombstone {
+    #[serde(rename = "type")]
+    pub kind: TombstoneKind,
+    pub id: i64,
+    /// Set for todos only. Todos deleted together with their work list have no tombstones.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub work_list_id: Option<i64>,
+    pub version: i64,
+    pub deleted_at: DateTime<Utc>,
+}
+
+impl Tombstone {
+    fn is_visible(&self, permissions: &Permissions) -> bool {
+        match self.kind {
+            TombstoneKind::Todo => {
+                permissions.allows(Scope::TodosRead)
+                    && self
+                        .work_list_id
+                        .map(|id| permissions.allows_work_list(id))
+                        .unwrap_or(false)
+            }
+            TombstoneKind::WorkList => {
+                permissions.allows(Scope::WorkListsRead) && permissions.allows_work_list(self.id)
+            }
+        }
+    }
+}
+
+/// Sync tokens are opaque to clients, like cursors.
+#[derive(Debug, Serialize, Deserialize)]
+struct SyncToken {
+    version: i64,
+}
+
+/// Work lists, todos and deletions since a sync token, up to a limit of changes.
+#[derive(Debug, Serialize)]
+pub struct SyncChanges {
+    /// Work lists as they are now, without their todos.
+    pub work_lists: Vec<WorkList>,
+    /// Todos as they are now, as a flat list.
+    pub todos: Vec<Todo>,
+    pub deleted: Vec<Tombstone>,
+    /// Token to sync from next time.
+    pub token: String,
+    /// Set when there are more changes, which should be fetched with `token` right away.
+    pub has_more: bool,
+}
+
+impl SyncChanges {
+    /// Changes are taken in order of their versions, so a sync cut at the limit continues where
+    /// it stopped. The first sync sends every work list and todo of the client.
+    pub async fn since(
+        form: SyncQuery,
+        client: &Client,
+        storage: &Storage,
+    ) -> Result<Self, WebError> {
+        let permissions = client.permissions();
+
+        if !permissions.allows(Scope::WorkListsRead) && !permissions.allows(Scope::TodosRead) {
+            return Err(WebError::Forbidden);
+        }
+
+        let since = match form.since.as_deref() {
+            Some(token) => Some(
+                decode_cursor::<SyncToken>(token)
+                    .map_err(|_| WebError::BadRequest("Invalid sync token".to_string()))?
+                    .version,
+            ),
+            None => None,
+        };
+        let after = since.unwrap_or(0);
+        let limit = form.limit.unwrap_or(DEFAULT_SYNC_LIMIT);
+
+        // The first `limit` changes overall are among the first `limit` of every kind. One more
+        // is fetched to tell whether anything is left.
+        let mut work_lists = storage
+            .changed_work_lists(client.id(), after, limit + 1)
+            .await?;
+        let mut todos = storage.changed_todos(client.id(), after, limit + 1).await?;
+        // Nothing to delete on a client which has never synced.
+        let mut deleted = match since {
+            Some(since) => {
+                storage
+                    .list_tombstones(client.id(), since, limit + 1)
+                    .await?
+            }
+            None => vec![],
+        };
+
+        let mut versions: Vec<i64> = work_lists
+            .iter()
+            .map(WorkList::version)
+            .chain(todos.iter().map(|todo| todo.version))
+            .chain(deleted.iter().map(|tombstone| tombstone.version))
+            .collect();
+        versions.sort_unstable();
+
+        let has_more = versions.len() > limit as usize;
+        versions.truncate(limit as usize);
+        let last = versions.last().copied().unwrap_or(after);
+
+        // Changes the key may not read still move the token forward.
+        work_lists.retain(|work_list| {
+            work_list.version() <= last
+                && permissions.allows(Scope::WorkListsRead)
+                && permissions.allows_work_list(work_list.id())
+        });
+        todos.retain(|todo| {
+            todo.version <= last
+                && permissions.allows(Scope::TodosRead)
+                && permissions.allows_work_list(todo.work_list_id)
+        });
+        deleted.retain(|tombstone| tombstone.version <= last && tombstone.is_visible(permissions));
+        Todo::load_tags(&mut todos, storage).await?;
+
+        Ok(Self {
+            work_lists: work_lists
+                .into_iter()
+                .map(WorkList::without_todos)
+                .collect(),
+            todos,
+            deleted,
+            token: encode_cursor(&SyncToken { version: last }),
+            has_more,
+        })
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum SyncItem {
+    WorkList(WorkList),
+    Todo(Todo),
+}
+
+/// Outcome of a single pushed mutation.
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum MutationResult {
+    /// Carries the created or updated item, nothing for deletions.
+    Applied {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        item: Option<SyncItem>,
+    },
+    /// The item has changed since `base_version`. Nothing was applied, the item is sent as it
+    /// is now, so that the client can resolve the conflict.
+    Conflict { current: SyncItem },
+    /// The mutation was not applied, see the problem details.
+    Rejected { error: Value },
+    /// The batch stopped on an internal error at this mutation or an earlier one. Nothing from
+    /// here on was applied, the mutations can be pushed again.
+    NotApplied,
+}
+
+impl MutationResult {
+    fn applied(item: SyncItem) -> Self {
+        MutationResult::Applied { item: Some(item) }
+    }
+
+    fn is_stale(base_version: Option<i64>, version: i64) -> bool {
+        base_version.map(|base| base != version).unwrap_or(false)
+    }
+
+    /// Applies mutations in order, with the same checks as the corresponding endpoints. A
+    /// rejected mutation does not stop the following ones. An internal error stops the batch,
+    /// the failed mutation and the rest are reported as not applied.
+    pub async fn push(
+        form: PushMutations,
+        client: &Client,
+        storage: &Storage,
+    ) -> Result<Vec<Self>, WebError> {
+        let mut results = Vec::with_capacity(form.mutations.len());
+        let mut mutations = form.mutations.into_iter();
+
+        while let Some(mutation) = mutations.next() {
+            let result = match Self::apply(mutation, client, storage).await {
+                Ok(result) => result,
+                Err(WebError::DatabaseError(err)) => {
+                    error!("Database error: {:?}", err);
+                    results.push(MutationResult::NotApplied);
+                    results.extend(mutations.map(|_| MutationResult::NotApplied));
+                    break;
+                }
+                Err(err) => MutationResult::Rejected {
+                    error: err.problem_json(),
+                },
+            };
+
+            results.push(result);
+        }
+
+        Ok(results)
+    }
+
+    async fn apply(
+        mutation: Mutation,
+        client: &Client,
+        storage: &Storage,
+    ) -> Result<Self, WebError> {
+        match mutation {
+            Mutation::CreateWorkList { mutation_id, data } => {
+                data.validate().map_err(WebError::ValidationError)?;
+                client.require(Scope::WorkListsWrite)?;
+
+                // Keys restricted to particular work lists would not be able to access a new one.
+                if client.permissions().is_restricted_to_work_lists() {
+                    return Err(WebError::Forbidden);
+                }
+
+                let kind = TombstoneKind::WorkList;
+                if let Some(result) =
+                    Self::find_applied(mutation_id.as_deref(), kind, client, storage).await?
+                {
+                    return Ok(result);
+                }
+
+                match WorkList::create(data, mutation_id.as_deref(), client, storage).await {
+                    Ok(work_list) => {
+                        Ok(Self::applied(SyncItem::WorkList(work_list.without_todos())))
+                    }
+                    // The same mutation was applied by a concurrent push.
+                    Err(WebError::Conflict) => {
+                        Self::find_applied(mutation_id.as_deref(), kind, client, storage)
+                            .await?
+                            .ok_or(WebError::Conflict)
+                    }
+                    Err(err) => Err(err),
+                }
+            }
+            Mutation::UpdateWorkList {
+                id,
+                base_version,
+                data,
+            } => {
+                data.validate().map_err(WebError::ValidationError)?;
+                client.require_work_list(Scope::WorkListsWrite, id)?;
+
+                let mut work_list = Self::find_work_list(id, client, storage).await?;
+                if Self::is_stale(base_version, work_list.version()) {
+                    return Ok(MutationResult::Conflict {
+                        current: SyncItem::WorkList(work_list),
+                    });
+                }
+
+                let result = work_list
+                    .update(client, data, base_version, storage)
+                    .await
+                    .map(|_| ());
+                match result {
+                    Ok(()) => Ok(Self::applied(SyncItem::WorkList(work_list))),
+                    // Changed after the check above.
+                    Err(WebError::PreconditionFailed) => Ok(MutationResult::Conflict {
+                        current: SyncItem::WorkList(
+                            Self::find_work_list(id, client, storage).await?,
+                        ),
+                    }),
+                    Err(err) => Err(err),
+                }
+            }
+            Mutation::DeleteWorkList { id, base_version } => {
+                client.require_work_list(Scope::WorkListsWrite, id)?;
+
+                let work_list = Self::find_work_list(id, client, storage).await?;
+                if Self::is_stale(base_version, work_list.version()) {
+                    return Ok(MutationResult::Conflict {
+                        current: SyncItem::WorkList(work_list),
+                    });
+                }
+
+                match work_list.delete(base_version, client, storage).await {
+                    Ok(()) => Ok(MutationResult::Applied { item: None }),
+                    Err(WebError::PreconditionFailed) => Ok(MutationResult::Conflict {
+                        current: SyncItem::WorkList(
+                            Self::find_work_list(id, client, storage).await?,
+                        ),
+                    }),
+                    Err(err) => Err(err),
+                }
+            }
+            Mutation::CreateTodo { mutation_id, data } => {
+                data.validate().map_err(WebError::ValidationError)?;
+                client.require_work_list(Scope::TodosWrite, data.work_list_id)?;
+
+                let kind = TombstoneKind::Todo;
+                if let Some(result) =
+                    Self::find_applied(mutation_id.as_deref(), kind, client, storage).await?
+                {
+                    return Ok(result);
+                }
+
+                match Todo::create(data, mutation_id.as_deref(), client, storage).await {
+                    Ok(todo) => Ok(Self::applied(SyncItem::Todo(todo))),
+                    // The same mutation was applied by a concurrent push.
+                    Err(WebError::Conflict) => {
+                        Self::find_applied(mutation_id.as_deref(), kind, client, storage)
+                            .await?
+                            .ok_or(WebError::Conflict)
+                    }
+                    Err(err) => Err(err),
+                }
+            }
+            Mutation::UpdateTodo {
+                id,
+                base_version,
+                data,
+            } => {
+                data.validate().map_err(WebError::ValidationError)?;
+
+                let mut todo = Todo::find(id, client, storage).await?;
+                client.require_work_list(Scope::TodosWrite, todo.work_list_id)?;
+
+                if let Some(work_list_id) = data.work_list_id {
+                    client.require_work_list(Scope::TodosWrite, work_list_id)?;
+                }
+
+                if Self::is_stale(base_version, todo.version) {
+                    return Ok(MutationResult::Conflict {
+                        current: SyncItem::Todo(todo),
+                    });
+                }
+
+                let result = todo
+                    .update(data, base_version, client, storage)
+                    .await
+                    .map(|_| ());
+                match result {
+                    Ok(()) => Ok(Self::applied(SyncItem::Todo(todo))),
+                    // Changed after the check above.
+                    Err(WebError::PreconditionFailed) => Ok(MutationResult::Conflict {
+                        current: SyncItem::Todo(Todo::find(id, client, storage).await?),
+                    }),
+                    Err(err) => Err(err),
+                }
+            }
+            Mutation::DeleteTodo { id, base_version } => {
+                let todo = Todo::find(id, client, storage).await?;
+                client.require_work_list(Scope::TodosWrite, todo.work_list_id)?;
+
+                if Self::is_stale(base_version, todo.version) {
+                    return Ok(MutationResult::Conflict {
+                        current: SyncItem::Todo(todo),
+                    });
+                }
+
+                match todo.delete(base_version, client, storage).await {
+                    Ok(()) => Ok(MutationResult::Applied { item: None }),
+                    Err(WebError::PreconditionFailed) => Ok(MutationResult::Conflict {
+                        current: SyncItem::Todo(Todo::find(id, client, storage).await?),
+                    }),
+                    Err(err) => Err(err),
+                }
+            }
+        }
+    }
+
+    /// Result of a create already applied with the same mutation ID, carrying the item as it is
+    /// now - or nothing if it has been deleted since.
+    async fn find_applied(
+        mutation_id: Option<&str>,
+        kind: TombstoneKind,
+        client: &Client,
+        storage: &Storage,
+    ) -> Result<Option<Self>, WebError> {
+        let mutation_id = match mutation_id {
+            Some(mutation_id) => mutation_id,
+            None => return Ok(None),
+        };
+
+        if mutation_id.is_empty() || mutation_id.chars().count() > MAX_MUTATION_ID_LENGTH {
+            return Err(WebError::BadRequest(format!(
+                "Mutation ID has to have 1 to {} characters",
+                MAX_MUTATION_ID_LENGTH
+            )));
+        }
+
+        let (applied_kind, id) = match storage.find_mutation(client.id(), mutation_id).await? {
+            Some(applied) => applied,
+            None => return Ok(None),
+        };
+
+        if applied_kind != kind {
+            return Err(WebError::BadRequest(
+                "Mutation ID was used by another op".to_string(),
+            ));
+        }
+
+        let item = match kind {
+            TombstoneKind::WorkList => Self::find_work_list(id, client, storage)
+                .await
+                .map(SyncItem::WorkList),
+            TombstoneKind::Todo => Todo::find(id, client, storage).await.map(SyncItem::Todo),
+        };
+
+        match item {
+            Ok(item) => Ok(Some(Self::applied(item))),
+            Err(WebError::NotFound(_)) => Ok(Some(MutationResult::Applied { item: None })),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Work list without its todos, which are synced on their own.
+    async fn find_work_list(
+        id: i64,
+        client: &Client,
+        storage: &Storage,
+    ) -> Result<WorkList, WebError> {
+        storage
+            .find_work_list(id, client.id())
+            .await?
+            .map(WorkList::without_todos)
+            .ok_or(WebError::NotFound(Resource::WorkList))
+    }
+}
@@ -0,0 +1,78 @@
+use serde::Serialize;
+
+use crate::database::{Storage, TagRepository};
+use crate::error::{Resource, WebError};
+use crate::forms::tag::{CreateTag, UpdateTag};
+use crate::web_app::Client;
+
+/// Label attached to todos across work lists of a client. Names are unique per client.
+#[derive(Debug, Clone, Serialize)]
+pub struct Tag {
+    pub id: i64,
+    pub name: String,
+    pub color: Option<String>,
+    /// Number of tagged todos, set in listings only.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub todos_count: Option<i64>,
+}
+
+impl Tag {
+    /// Tags of the client ordered by name. Only todos accessible by the client are counted.
+    pub async fn list(client: &Client, storage: &Storage) -> Result<Vec<Self>, WebError> {
+        storage
+            .list_tags(client.id(), client.permissions().work_list_ids.as_deref())
+            .await
+    }
+
+    pub async fn find(id: i64, client: &Client, storage: &Storage) -> Result<Self, WebError> {
+        storage
+            .find_tag(id, client.id())
+            .await?
+            .ok_or(WebError::NotFound(Resource::Tag))
+    }
+
+    pub async fn create(
+        form: CreateTag,
+        client: &Client,
+        storage: &Storage,
+    ) -> Result<Self, WebError> {
+        storage
+            .create_tag(client.id(), &form.name, form.color.as_deref())
+            .await
+    }
+
+    pub async fn update(
+        &mut self,
+        form: UpdateTag,
+        client: &Client,
+        storage: &Storage,
+    ) -> Result<&mut Self, WebError> {
+        let color = form.color.as_ref().map(|color| color.as_deref());
+
+        if !storage
+            .update_tag(self.id, client.id(), form.name.as_deref(), color)
+            .await?
+        {
+            return Err(WebError::NotFound(Resource::Tag));
+        }
+
+        if let Some(name) = form.name {
+            self.name = name;
+        }
+
+        if let Some(color) = form.color {
+            self.color = color;
+        }
+
+        Ok(self)
+    }
+
+    /// Deletes tag and detaches it from all todos.
+    pub async fn delete(self, client: &Client, storage: &Storage) -> Result<(), WebError> {
+        if storage.delete_tag(self.id, client.id()).await? {
+            Ok(())
+        } else {
+            Err(WebError::NotFound(Resource::Tag))
+        }
+    }
+}
@@ -1,160 +1,1239 @@
-use crate::database::Pool;
-use crate::error::WebError;
-use crate::web_app::Client;
+use crate::database::{Storage, TagRepository, TodoRepository, WorkListRepository};
+use crate::error::{Resource, WebError};
+use crate::web_app::{entity_tag, tagged_response, Client};
 
-use crate::forms::todo::{CreateTodo, UpdateTodo};
+use super::page::{decode_cursor, encode_cursor, Page, DEFAULT_PAGE_SIZE};
+use super::{EventKind, NewEvent, Recurrence, Tag};
+use crate::forms::todo::{CreateTodo, ListTodos, MoveTodo, SetRecurrence, UpdateTodo};
 
 use actix_web::{error::Error, HttpRequest, HttpResponse, Responder};
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
 use futures::future::{ready, Ready};
-use serde::Serialize;
-use sqlx::FromRow;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use std::slice;
 
 impl Responder for Todo {
     type Error = Error;
     type Future = Ready<Result<HttpResponse, Error>>;
 
-    fn respond_to(self, _req: &HttpRequest) -> Self::Future {
-        let body = serde_json::to_string(&self).unwrap();
-
-        ready(Ok(HttpResponse::Ok()
-            .content_type("application/json")
-            .body(body)))
+    fn respond_to(self, req: &HttpRequest) -> Self::Future {
+        ready(tagged_response(req, &self.etag(), &self).map_err(Error::from))
     }
 }
 
-#[derive(Debug, Serialize, FromRow)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Todo {
     pub id: i64,
     pub content: String,
     pub completed: bool,
     pub work_list_id: i64,
+    /// Todo of the same work list this one is a subtask of.
+    pub parent_id: Option<i64>,
+    pub due_at: Option<Due>,
+    pub priority: Priority,
+    /// Manual order within the work list, ascending. Only the relative order is meaningful.
+    pub position: i64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+    /// Change version, taken anew by every change of the todo. Versions of all todos and work
+    /// lists grow together.
+    pub version: i64,
+    /// Names of tags, in alphabetical order.
+    pub tags: Vec<String>,
+    /// Rule of a recurring todo. Completing it creates the next occurrence, which takes the rule
+    /// over.
+    pub recurrence: Option<Recurrence>,
+    /// ID of the first todo of a recurring series.
+    pub series_id: Option<i64>,
+    /// Number of the occurrence within its series, starting from 1.
+    pub occurrence: u32,
+    /// Set only when todos are returned as a tree.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subtasks: Option<Vec<Todo>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub progress: Option<Progress>,
+}
+
+/// Completion of all subtasks of a todo, at any depth.
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct Progress {
+    pub completed: u32,
+    pub total: u32,
+}
+
+/// Distance between positions of todos appended to a work list. Todos moved in between take the
+/// middle of the gap, so a move changes a single row until the gap is used up.
+pub const POSITION_STEP: i64 = 1 << 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Priority {
+    #[serde(rename = "low")]
+    Low,
+    #[serde(rename = "normal")]
+    Normal,
+    #[serde(rename = "high")]
+    High,
+    #[serde(rename = "urgent")]
+    Urgent,
+}
+
+impl Default for Priority {
+    fn default() -> Self {
+        Priority::Normal
+    }
+}
+
+impl Priority {
+    /// Priorities are stored as integers, higher is more important.
+    pub fn from_column(value: i32) -> Self {
+        use Priority::*;
+
+        match value {
+            i32::MIN..=0 => Low,
+            1 => Normal,
+            2 => High,
+            _ => Urgent,
+        }
+    }
+
+    pub fn to_column(self) -> i32 {
+        self as i32
+    }
+}
+
+/// When a todo is due - either at an exact moment, or during a whole day of client's calendar.
+/// Serialized as RFC 3339 timestamp or `YYYY-MM-DD` date respectively.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Due {
+    At(DateTime<Utc>),
+    On(NaiveDate),
+}
+
+impl Due {
+    /// Due dates are stored in two columns, at most one of them is set.
+    pub fn from_columns(
+        due_date: Option<NaiveDate>,
+        due_at: Option<DateTime<Utc>>,
+    ) -> Option<Self> {
+        due_at.map(Due::At).or_else(|| due_date.map(Due::On))
+    }
+
+    pub fn date(self) -> Option<NaiveDate> {
+        match self {
+            Due::On(date) => Some(date),
+            Due::At(_) => None,
+        }
+    }
+
+    pub fn time(self) -> Option<DateTime<Utc>> {
+        match self {
+            Due::At(time) => Some(time),
+            Due::On(_) => None,
+        }
+    }
+
+    /// Moment the todo is due at. Todos due on a date are due at the start of that day.
+    pub fn instant<Tz: TimeZone>(self, time_zone: &Tz) -> DateTime<Utc> {
+        match self {
+            Due::At(time) => time,
+            Due::On(date) => start_of_day(time_zone, date),
+        }
+    }
+}
+
+/// Todo to be stored. Storage assigns its ID and puts it at the end of its work list.
+#[derive(Debug, Clone)]
+pub struct NewTodo {
+    pub work_list_id: i64,
+    pub parent_id: Option<i64>,
+    pub content: String,
+    pub due_at: Option<Due>,
+    pub priority: Priority,
+    pub recurrence: Option<Recurrence>,
+    /// A recurring todo without a series starts a new one.
+    pub series_id: Option<i64>,
+    pub occurrence: u32,
+    pub created_at: DateTime<Utc>,
+    /// Tags ordered by name.
+    pub tags: Vec<Tag>,
+    /// Sync mutation creating the todo, remembered so that it is not applied twice.
+    pub mutation_id: Option<String>,
+}
+
+impl NewTodo {
+    /// Series started by the todo gets its ID.
+    pub fn series_id(&self, id: i64) -> Option<i64> {
+        self.series_id
+            .or_else(|| self.recurrence.as_ref().map(|_| id))
+    }
+
+    pub fn into_todo(self, id: i64, version: i64, position: i64) -> Todo {
+        Todo {
+            id,
+            content: self.content,
+            completed: false,
+            work_list_id: self.work_list_id,
+            parent_id: self.parent_id,
+            due_at: self.due_at,
+            priority: self.priority,
+            position,
+            created_at: self.created_at,
+            updated_at: self.created_at,
+            completed_at: None,
+            version,
+            tags: self.tags.into_iter().map(|tag| tag.name).collect(),
+            series_id: self.series_id(id),
+            recurrence: self.recurrence,
+            occurrence: self.occurrence,
+            subtasks: None,
+            progress: None,
+        }
+    }
+}
+
+/// Fields of a todo to be changed. `None` leaves the field as it is.
+#[derive(Debug, Clone, Default)]
+pub struct TodoChanges {
+    pub content: Option<String>,
+    pub completed: Option<bool>,
+    pub due_at: Option<Option<Due>>,
+    pub priority: Option<Priority>,
+    /// Moves the todo to the end of the work list, `position` is ignored then.
+    pub work_list_id: Option<i64>,
+    pub parent_id: Option<Option<i64>>,
+    pub position: Option<i64>,
+    pub recurrence: Option<Option<Recurrence>>,
+    pub series_id: Option<i64>,
+    pub occurrence: Option<u32>,
+    pub updated_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<Option<DateTime<Utc>>>,
+    /// Replaces all tags, ordered by name.
+    pub tags: Option<Vec<Tag>>,
+}
+
+/// Change of a single todo within a `TodoCascade`.
+#[derive(Debug, Clone)]
+pub struct TodoWrite {
+    /// The todo as read before the change.
+    pub todo: Todo,
+    pub changes: TodoChanges,
+    /// Kind of the event describing the change.
+    pub kind: EventKind,
+}
+
+impl TodoWrite {
+    /// The todo as stored by the write.
+    pub fn stored(&self, version: i64, position: i64) -> Todo {
+        let mut todo = self.todo.clone();
+        todo.apply(&self.changes);
+        todo.version = version;
+        todo.position = position;
+        todo
+    }
+}
+
+/// Update of a todo together with the writes following from it, stored all or none. Todos
+/// changing their work list go to its end in the order of the writes.
+#[derive(Debug, Clone)]
+pub struct TodoCascade {
+    pub todo: TodoWrite,
+    /// Subtasks at any depth, in their order.
+    pub subtasks: Vec<TodoWrite>,
+    /// Next occurrence of a completed recurring todo. It gets reminders of the todo, shifted by
+    /// `reminder_offset`.
+    pub next_occurrence: Option<NewTodo>,
+    pub reminder_offset: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum DueView {
+    /// Todos due today in client's time zone.
+    #[serde(rename = "today")]
+    Today,
+    /// Todos not completed in time.
+    #[serde(rename = "overdue")]
+    Overdue,
+}
+
+/// Due date condition of a todo search, resolved against client's calendar.
+#[derive(Debug, Clone, Copy)]
+pub enum DueCondition {
+    /// Due on `date`, or at a time in `[from, to)`.
+    Within {
+        date: NaiveDate,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    },
+    /// Not completed and due before `date`, or before `time`.
+    Before {
+        date: NaiveDate,
+        time: DateTime<Utc>,
+    },
+}
+
+impl DueCondition {
+    pub fn resolve<Tz: TimeZone>(view: DueView, time_zone: &Tz, now: DateTime<Utc>) -> Self {
+        let today = now.with_timezone(time_zone).date().naive_local();
+
+        match view {
+            DueView::Today => {
+                let from = start_of_day(time_zone, today);
+                let to = start_of_day(time_zone, today + Duration::days(1));
+
+                DueCondition::Within {
+                    date: today,
+                    from,
+                    to,
+                }
+            }
+            DueView::Overdue => DueCondition::Before {
+                date: today,
+                time: now,
+            },
+        }
+    }
+}
+
+/// First moment of a day in given time zone. Days starting with a DST gap begin when it ends.
+fn start_of_day<Tz: TimeZone>(time_zone: &Tz, date: NaiveDate) -> DateTime<Utc> {
+    (0..3)
+        .filter_map(|hour| {
+            time_zone
+                .from_local_datetime(&date.and_hms(hour, 0, 0))
+                .earliest()
+        })
+        .next()
+        .map(|start| start.with_timezone(&Utc))
+        .unwrap_or_else(|| Utc.from_utc_datetime(&date.and_hms(0, 0, 0)))
+}
+
+/// Position in the middle of the gap between two todos, `None` meaning the end of the list.
+/// Returns `None` when the gap is too narrow.
+fn position_between(gap: (Option<i64>, Option<i64>)) -> Option<i64> {
+    match gap {
+        (Some(low), Some(high)) if high - low > 1 => Some(low + (high - low) / 2),
+        (Some(_), Some(_)) => None,
+        (Some(low), None) => Some(low + POSITION_STEP),
+        (None, Some(high)) => Some(high - POSITION_STEP),
+        (None, None) => Some(0),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum TodoSort {
+    #[serde(rename = "id")]
+    IdAsc,
+    #[serde(rename = "-id")]
+    IdDesc,
+    #[serde(rename = "content")]
+    ContentAsc,
+    #[serde(rename = "-content")]
+    ContentDesc,
+}
+
+impl Default for TodoSort {
+    fn default() -> Self {
+        TodoSort::IdAsc
+    }
+}
+
+impl TodoSort {
+    pub fn is_descending(self) -> bool {
+        use TodoSort::*;
+
+        match self {
+            IdDesc | ContentDesc => true,
+            IdAsc | ContentAsc => false,
+        }
+    }
+}
+
+/// Last todo of a page, see `WorkListPosition`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TodoPosition {
+    pub sort: TodoSort,
+    pub id: i64,
+    pub content: String,
+}
+
+/// Criteria of a todo search across work lists of a client, as passed to storage.
+#[derive(Debug)]
+pub struct TodoQuery {
+    pub completed: Option<bool>,
+    /// `None` means every work list of the client.
+    pub work_list_ids: Option<Vec<i64>>,
+    /// Case-insensitive substring of the content.
+    pub content: Option<String>,
+    pub due: Option<DueCondition>,
+    /// Name of a tag todos have to carry.
+    pub tag: Option<String>,
+    pub sort: TodoSort,
+    pub after: Option<TodoPosition>,
+    pub limit: u32,
 }
 
 impl Todo {
-    async fn authorize(work_list_id: i64, client: &Client, pool: &Pool) -> Result<(), WebError> {
-        let result: (bool,) =
-            sqlx::query_as("SELECT ? IN (SELECT id FROM work_lists WHERE client_id = ?)")
-                .bind(work_list_id)
-                .bind(client.id())
-                .fetch_one(&*pool)
-                .await?;
+    /// Entity tag of a single todo. Subtasks are not covered.
+    pub fn etag(&self) -> String {
+        entity_tag(self.version)
+    }
 
-        if result.0 == false {
-            Err(WebError::Unauthorized)
+    async fn authorize(
+        work_list_id: i64,
+        client: &Client,
+        storage: &Storage,
+    ) -> Result<(), WebError> {
+        if storage.work_list_exists(work_list_id, client.id()).await? == false {
+            Err(WebError::NotFound(Resource::WorkList))
         } else {
             Ok(())
         }
     }
 
-    fn new(id: i64, content: String, completed: bool, work_list_id: i64) -> Self {
-        Self {
-            id,
-            content,
-            completed,
-            work_list_id,
+    /// Applies changes already persisted in storage.
+    pub fn apply(&mut self, changes: &TodoChanges) {
+        if let Some(content) = changes.content.as_ref() {
+            self.content = content.clone();
+        }
+
+        if let Some(completed) = changes.completed {
+            self.completed = completed;
+        }
+
+        if let Some(due_at) = changes.due_at {
+            self.due_at = due_at;
+        }
+
+        if let Some(priority) = changes.priority {
+            self.priority = priority;
+        }
+
+        if let Some(work_list_id) = changes.work_list_id {
+            self.work_list_id = work_list_id;
+        }
+
+        if let Some(parent_id) = changes.parent_id {
+            self.parent_id = parent_id;
+        }
+
+        if let Some(position) = changes.position {
+            self.position = position;
+        }
+
+        if let Some(recurrence) = changes.recurrence.as_ref() {
+            self.recurrence = recurrence.clone();
+        }
+
+        if let Some(series_id) = changes.series_id {
+            self.series_id = Some(series_id);
+        }
+
+        if let Some(occurrence) = changes.occurrence {
+            self.occurrence = occurrence;
+        }
+
+        if let Some(updated_at) = changes.updated_at {
+            self.updated_at = updated_at;
+        }
+
+        if let Some(completed_at) = changes.completed_at {
+            self.completed_at = completed_at;
+        }
+
+        if let Some(tags) = changes.tags.as_ref() {
+            self.tags = tags.iter().map(|tag| tag.name.clone()).collect();
+        }
+    }
+
+    /// Parent todo has to belong to the same work list as its subtasks.
+    async fn check_parent(
+        parent_id: i64,
+        work_list_id: i64,
+        client: &Client,
+        storage: &Storage,
+    ) -> Result<(), WebError> {
+        let parent = Self::find(parent_id, client, storage).await?;
+
+        if parent.work_list_id != work_list_id {
+            return Err(WebError::BadRequest(
+                "Parent todo belongs to a different work list".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Nests todos under their parents, keeping the order of the list. Todos whose parent is not
+    /// in the list stay on the top level.
+    pub fn into_tree(todos: Vec<Self>) -> Vec<Self> {
+        let ids: HashSet<i64> = todos.iter().map(|todo| todo.id).collect();
+        let mut children: HashMap<i64, Vec<Self>> = HashMap::new();
+        let mut roots = vec![];
+
+        for todo in todos {
+            match todo.parent_id.filter(|parent_id| ids.contains(parent_id)) {
+                Some(parent_id) => children.entry(parent_id).or_default().push(todo),
+                None => roots.push(todo),
+            }
         }
+
+        roots
+            .into_iter()
+            .map(|root| root.with_subtasks(&mut children))
+            .collect()
+    }
+
+    fn with_subtasks(mut self, children: &mut HashMap<i64, Vec<Self>>) -> Self {
+        if let Some(subtasks) = children.remove(&self.id) {
+            let subtasks: Vec<Self> = subtasks
+                .into_iter()
+                .map(|subtask| subtask.with_subtasks(children))
+                .collect();
+            let mut progress = Progress::default();
+
+            for subtask in subtasks.iter() {
+                let nested = subtask.progress.unwrap_or_default();
+
+                progress.total += 1 + nested.total;
+                progress.completed += nested.completed + if subtask.completed { 1 } else { 0 };
+            }
+
+            self.subtasks = Some(subtasks);
+            self.progress = Some(progress);
+        }
+
+        self
+    }
+
+    pub async fn find(id: i64, client: &Client, storage: &Storage) -> Result<Self, WebError> {
+        let mut todo = storage
+            .find_todo(id, client.id())
+            .await?
+            .ok_or(WebError::NotFound(Resource::Todo))?;
+
+        Self::load_tags(slice::from_mut(&mut todo), storage).await?;
+        Ok(todo)
+    }
+
+    /// Fills in tags of todos loaded from storage.
+    pub async fn load_tags(todos: &mut [Self], storage: &Storage) -> Result<(), WebError> {
+        if todos.is_empty() {
+            return Ok(());
+        }
+
+        let ids: Vec<i64> = todos.iter().map(|todo| todo.id).collect();
+        let mut tags: HashMap<i64, Vec<String>> = HashMap::new();
+
+        for (todo_id, name) in storage.todo_tag_names(&ids).await? {
+            tags.entry(todo_id).or_default().push(name);
+        }
+
+        for todo in todos.iter_mut() {
+            todo.tags = tags.remove(&todo.id).unwrap_or_default();
+        }
+
+        Ok(())
+    }
+
+    /// Tags of the client with given names ordered by name, creating missing ones.
+    async fn ensure_tags(
+        mut names: Vec<String>,
+        client: &Client,
+        storage: &Storage,
+    ) -> Result<Vec<Tag>, WebError> {
+        if names.is_empty() {
+            return Ok(vec![]);
+        }
+
+        names.sort();
+        names.dedup();
+
+        let mut tags = storage.ensure_tags(client.id(), &names).await?;
+        tags.sort_by(|tag, other| tag.name.cmp(&other.name));
+
+        Ok(tags)
     }
 
-    pub async fn find(id: i64, client: &Client, pool: &Pool) -> Result<Self, WebError> {
+    /// Stores a new todo together with the event of its creation.
+    async fn insert(
+        new_todo: NewTodo,
+        client: &Client,
+        storage: &Storage,
+    ) -> Result<Self, WebError> {
         let client_id = client.id();
-        sqlx::query_as!(Todo, "SELECT todos.* FROM todos JOIN work_lists ON work_lists.id = todos.work_list_id WHERE todos.id = ? AND work_lists.client_id = ?", id, client_id)
-            .fetch_one(&*pool)
-            .await
-            .map_err(|err| err.into())
+        let (todo, event) = storage
+            .create_todo(&new_todo, &|kind, todo| {
+                NewEvent::new(kind, client_id, todo)
+            })
+            .await?;
+
+        event.publish(client);
+        Ok(todo)
     }
 
-    pub async fn create(form: CreateTodo, client: &Client, pool: &Pool) -> Result<Self, WebError> {
-        // We are going to fetch last row id, it needs to be performed in the same query.
-        let mut conn = pool.acquire().await?;
-        Self::authorize(form.work_list_id, &client, pool).await?;
+    /// Stores changes of the todo together with the event describing them. With
+    /// `expected_version`, fails with 412 Precondition Failed if the todo is no longer at that
+    /// version.
+    async fn save(
+        &mut self,
+        changes: TodoChanges,
+        kind: EventKind,
+        expected_version: Option<i64>,
+        client: &Client,
+        storage: &Storage,
+    ) -> Result<(), WebError> {
+        self.apply(&changes);
 
-        #[cfg(target_feature = "postgres")]
-        {
-            let id = sqlx::query!(
-        "INSERT INTO todos (content, completed, work_list_id) VALUES ($1, false, $2) RETURNING id",
-        form.content,
-        form.work_list_id
-      )
-            .fetch_one(&mut conn)
-            .await
-            .map(|row: database::Row| row.get("id"))?;
-            Ok(Self::new(id, form.content, false, form.work_list_id))
-        }
-
-        #[cfg(not(target_feature = "postgres"))]
-        {
-            sqlx::query!(
-                "INSERT INTO todos (content, completed, work_list_id) VALUES (?, false, ?)",
-                form.content,
-                form.work_list_id
-            )
-            .execute(&mut conn)
+        let client_id = client.id();
+        let todo = &*self;
+        let (version, event) = storage
+            .update_todo(todo.id, &changes, expected_version, &|_, version| {
+                let todo = Todo {
+                    version,
+                    ..todo.clone()
+                };
+                NewEvent::new(kind, client_id, &todo)
+            })
             .await?;
 
-            let id: (i64,) = sqlx::query_as("SELECT last_insert_rowid()")
-                .fetch_one(&mut conn)
-                .await?;
+        self.version = version;
+        event.publish(client);
+        Ok(())
+    }
+
+    pub async fn list(
+        form: ListTodos,
+        client: &Client,
+        storage: &Storage,
+    ) -> Result<Page<Self>, WebError> {
+        let sort = form.sort.unwrap_or_default();
+        let limit = form.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+        let after = match form.after.as_deref() {
+            Some(cursor) => Some(decode_cursor::<TodoPosition>(cursor)?),
+            None => None,
+        };
+
+        if after.as_ref().map(|position| position.sort != sort) == Some(true) {
+            return Err(WebError::BadRequest(
+                "Cursor was issued for a different sort order".to_string(),
+            ));
+        }
+
+        let work_list_ids = match form.work_list_id {
+            Some(work_list_id) => Some(vec![work_list_id]),
+            None => client.permissions().work_list_ids.clone(),
+        };
+
+        let due = form
+            .due
+            .map(|view| DueCondition::resolve(view, client.time_zone(), Utc::now()));
+
+        let query = TodoQuery {
+            completed: form.completed,
+            work_list_ids,
+            content: form.content,
+            due,
+            tag: form.tag,
+            sort,
+            after,
+            limit: limit + 1,
+        };
+        let mut todos = storage.search_todos(client.id(), &query).await?;
+        Self::load_tags(&mut todos, storage).await?;
 
-            Ok(Self::new(id.0, form.content, false, form.work_list_id))
+        Ok(Page::new(todos, limit, |todo| {
+            encode_cursor(&TodoPosition {
+                sort,
+                id: todo.id,
+                content: todo.content.clone(),
+            })
+        }))
+    }
+
+    /// `mutation_id` identifies a sync mutation creating the todo, see `MutationResult`.
+    pub async fn create(
+        form: CreateTodo,
+        mutation_id: Option<&str>,
+        client: &Client,
+        storage: &Storage,
+    ) -> Result<Self, WebError> {
+        Self::authorize(form.work_list_id, &client, storage).await?;
+
+        if let Some(parent_id) = form.parent_id {
+            Self::check_parent(parent_id, form.work_list_id, client, storage).await?;
+        }
+
+        if form.recurrence.is_some() && form.due_at.is_none() {
+            return Err(WebError::BadRequest(
+                "Recurring todos need a due date".to_string(),
+            ));
         }
+
+        let tags = Self::ensure_tags(form.tags.unwrap_or_default(), client, storage).await?;
+        let new_todo = NewTodo {
+            work_list_id: form.work_list_id,
+            parent_id: form.parent_id,
+            content: form.content,
+            due_at: form.due_at,
+            priority: form.priority.unwrap_or_default(),
+            recurrence: form.recurrence,
+            series_id: None,
+            occurrence: 1,
+            created_at: Utc::now(),
+            tags,
+            mutation_id: mutation_id.map(str::to_owned),
+        };
+
+        Self::insert(new_todo, client, storage).await
     }
 
+    /// `expected_version` is checked as by `save`, subtasks are not checked. Changes of
+    /// subtasks and the next occurrence of a completed recurring todo are stored together with
+    /// the todo, all or none.
     pub async fn update(
         &mut self,
         mut form: UpdateTodo,
+        expected_version: Option<i64>,
         client: &Client,
-        pool: &Pool,
+        storage: &Storage,
     ) -> Result<&mut Self, WebError> {
-        Self::authorize(self.work_list_id, &client, pool).await?;
+        Self::authorize(self.work_list_id, &client, storage).await?;
+
+        // Moved todos go to the end of the target list, see `TodoCascade`.
+        let work_list_id = match form.work_list_id.take() {
+            Some(work_list_id) if work_list_id != self.work_list_id => {
+                Self::authorize(work_list_id, &client, storage).await?;
+                Some(work_list_id)
+            }
+            _ => None,
+        };
+
+        // Parent of a todo moved to another list stays behind.
+        let parent_id = match form.parent_id.take() {
+            None if work_list_id.is_some() && self.parent_id.is_some() => Some(None),
+            parent_id => parent_id,
+        };
 
-        let mut where_list = Vec::with_capacity(2);
-        let new_content = form.content.take();
-        let new_completed = form.completed.take();
+        let now = Utc::now();
+        let completed_at = match form.completed {
+            Some(true) if !self.completed => Some(Some(now)),
+            Some(false) if self.completed => Some(None),
+            _ => None,
+        };
+        let complete_subtasks =
+            form.completed == Some(true) && form.complete_subtasks == Some(true);
 
-        if new_content.is_some() {
-            where_list.push("content = ?".to_owned());
+        // Completing a recurring todo hands its rule over to the next occurrence.
+        let recurrence = match completed_at {
+            Some(Some(_)) => self.recurrence.clone(),
+            _ => None,
+        };
+
+        let mut subtasks = if work_list_id.is_some() || parent_id.is_some() || complete_subtasks {
+            storage.find_subtasks(self.id).await?
+        } else {
+            vec![]
+        };
+
+        if let Some(Some(parent_id)) = parent_id {
+            if parent_id == self.id || subtasks.iter().any(|subtask| subtask.id == parent_id) {
+                return Err(WebError::BadRequest(
+                    "Todo cannot become a subtask of itself".to_string(),
+                ));
+            }
+
+            let target = work_list_id.unwrap_or(self.work_list_id);
+            Self::check_parent(parent_id, target, client, storage).await?;
         }
 
-        if new_completed.is_some() {
-            where_list.push("completed = ?".to_owned());
+        let tags = match form.tags.take() {
+            Some(names) => Some(Self::ensure_tags(names, client, storage).await?),
+            None => None,
+        };
+
+        let changes = TodoChanges {
+            content: form.content.take(),
+            completed: form.completed.take(),
+            due_at: form.due_at.take(),
+            priority: form.priority.take(),
+            work_list_id,
+            parent_id,
+            recurrence: recurrence.as_ref().map(|_| None),
+            updated_at: Some(now),
+            completed_at,
+            tags,
+            ..TodoChanges::default()
+        };
+
+        let kind = match completed_at {
+            Some(Some(_)) => EventKind::TodoCompleted,
+            _ => EventKind::TodoUpdated,
+        };
+
+        // The next occurrence follows the todo as it is after the update.
+        let (next_occurrence, reminder_offset) = match recurrence {
+            Some(recurrence) => {
+                let mut updated = self.clone();
+                updated.apply(&changes);
+                updated.next_occurrence(recurrence, client, storage).await?
+            }
+            None => (None, Duration::zero()),
+        };
+
+        // Subtasks follow their todo, keeping their order.
+        Self::load_tags(&mut subtasks, storage).await?;
+        let subtasks = subtasks
+            .into_iter()
+            .filter_map(|subtask| {
+                let mut changes = TodoChanges {
+                    work_list_id,
+                    ..TodoChanges::default()
+                };
+
+                if complete_subtasks && !subtask.completed {
+                    changes.completed = Some(true);
+                    changes.completed_at = Some(Some(now));
+                }
+
+                let kind = match (changes.work_list_id, changes.completed) {
+                    (None, None) => return None,
+                    (_, Some(_)) => EventKind::TodoCompleted,
+                    (_, None) => EventKind::TodoUpdated,
+                };
+
+                changes.updated_at = Some(now);
+                Some(TodoWrite {
+                    todo: subtask,
+                    changes,
+                    kind,
+                })
+            })
+            .collect();
+
+        let cascade = TodoCascade {
+            todo: TodoWrite {
+                todo: self.clone(),
+                changes,
+                kind,
+            },
+            subtasks,
+            next_occurrence,
+            reminder_offset,
+        };
+        let client_id = client.id();
+        let (todo, events) = storage
+            .update_todo_cascade(&cascade, expected_version, &|kind, todo| {
+                NewEvent::new(kind, client_id, todo)
+            })
+            .await?;
+
+        *self = todo;
+        for event in events {
+            event.publish(client);
         }
 
-        let sql = format!(
-            "UPDATE todos SET {} WHERE id = {}",
-            where_list.join(", "),
-            where_list.len()
-        );
+        Ok(self)
+    }
 
-        let mut q = sqlx::query(&sql);
+    /// Todos found in storage, in the order of given IDs. Fails if any of them is missing.
+    pub async fn find_many(
+        ids: &[i64],
+        client: &Client,
+        storage: &Storage,
+    ) -> Result<Vec<Self>, WebError> {
+        let mut todos = Vec::with_capacity(ids.len());
 
-        if let Some(content) = new_content.as_ref() {
-            q = q.bind(content);
+        for id in ids {
+            todos.push(Self::find(*id, client, storage).await?);
         }
 
-        if let Some(completed) = new_completed.as_ref() {
-            q = q.bind(completed);
+        Ok(todos)
+    }
+
+    /// Creates copies of todos at the end of a work list. Copies are not completed, get new
+    /// timestamps and are neither subtasks nor recurring. Tags are copied.
+    pub async fn copy_to(
+        todos: &[Self],
+        work_list_id: i64,
+        client: &Client,
+        storage: &Storage,
+    ) -> Result<Vec<Self>, WebError> {
+        Self::authorize(work_list_id, &client, storage).await?;
+
+        let now = Utc::now();
+        let mut copies = Vec::with_capacity(todos.len());
+
+        for todo in todos {
+            let tags = Self::ensure_tags(todo.tags.clone(), client, storage).await?;
+            let new_todo = NewTodo {
+                work_list_id,
+                parent_id: None,
+                content: todo.content.clone(),
+                due_at: todo.due_at,
+                priority: todo.priority,
+                recurrence: None,
+                series_id: None,
+                occurrence: 1,
+                created_at: now,
+                tags,
+                mutation_id: None,
+            };
+
+            copies.push(Self::insert(new_todo, client, storage).await?);
         }
 
-        q.execute(&*pool).await?;
+        Ok(copies)
+    }
 
-        if let Some(content) = new_content {
-            self.content = content;
+    /// The occurrence following this one, unless the series has ended, together with the
+    /// distance between their due dates. Reminders of this occurrence are shifted by it.
+    async fn next_occurrence(
+        &self,
+        recurrence: Recurrence,
+        client: &Client,
+        storage: &Storage,
+    ) -> Result<(Option<NewTodo>, Duration), WebError> {
+        let occurrence = self.occurrence + 1;
+        if recurrence.count.map(|count| occurrence > count) == Some(true) {
+            return Ok((None, Duration::zero()));
         }
 
-        if let Some(completed) = new_completed {
-            self.completed = completed;
+        let time_zone = client.time_zone();
+        let (due, due_at) = match self
+            .due_at
+            .and_then(|due| Some((due, recurrence.next_due(due, time_zone)?)))
+        {
+            Some(dates) => dates,
+            None => return Ok((None, Duration::zero())),
+        };
+
+        let new_todo = NewTodo {
+            work_list_id: self.work_list_id,
+            parent_id: self.parent_id,
+            content: self.content.clone(),
+            due_at: Some(due_at),
+            priority: self.priority,
+            recurrence: Some(recurrence),
+            series_id: Some(self.series_id.unwrap_or(self.id)),
+            occurrence,
+            created_at: Utc::now(),
+            tags: Self::ensure_tags(self.tags.clone(), client, storage).await?,
+            mutation_id: None,
+        };
+        let offset = due_at.instant(time_zone) - due.instant(time_zone);
+
+        Ok((Some(new_todo), offset))
+    }
+
+    /// Sets or changes the rule of the series. Only todos not completed yet carry the rule.
+    pub async fn set_recurrence(
+        &mut self,
+        form: SetRecurrence,
+        client: &Client,
+        storage: &Storage,
+    ) -> Result<&mut Self, WebError> {
+        Self::authorize(self.work_list_id, &client, storage).await?;
+
+        if self.completed {
+            return Err(WebError::BadRequest(
+                "Recurrence of a completed todo can't be changed".to_string(),
+            ));
         }
 
+        if self.due_at.is_none() {
+            return Err(WebError::BadRequest(
+                "Recurring todos need a due date".to_string(),
+            ));
+        }
+
+        let changes = TodoChanges {
+            recurrence: Some(Some(form.rule)),
+            series_id: Some(self.series_id.unwrap_or(self.id)),
+            updated_at: Some(Utc::now()),
+            ..TodoChanges::default()
+        };
+
+        self.save(changes, EventKind::TodoUpdated, None, client, storage)
+            .await?;
         Ok(self)
     }
 
-    pub async fn delete(self, client: &Client, pool: &Pool) -> Result<(), WebError> {
-        Self::authorize(self.work_list_id, &client, pool).await?;
+    /// Ends the series with this todo.
+    pub async fn stop_recurrence(
+        &mut self,
+        client: &Client,
+        storage: &Storage,
+    ) -> Result<&mut Self, WebError> {
+        Self::authorize(self.work_list_id, &client, storage).await?;
 
-        sqlx::query!("DELETE FROM todos WHERE id = ?", self.id)
-            .execute(&*pool)
+        let changes = TodoChanges {
+            recurrence: Some(None),
+            updated_at: Some(Utc::now()),
+            ..TodoChanges::default()
+        };
+
+        self.save(changes, EventKind::TodoUpdated, None, client, storage)
+            .await?;
+        Ok(self)
+    }
+
+    /// Moves the todo to the next occurrence of its series without completing it.
+    pub async fn skip_occurrence(
+        &mut self,
+        client: &Client,
+        storage: &Storage,
+    ) -> Result<&mut Self, WebError> {
+        Self::authorize(self.work_list_id, &client, storage).await?;
+
+        let recurrence = self.recurrence.clone().ok_or_else(|| {
+            WebError::BadRequest("Only recurring todos can be skipped".to_string())
+        })?;
+        let occurrence = self.occurrence + 1;
+
+        let due_at = self
+            .due_at
+            .and_then(|due| recurrence.next_due(due, client.time_zone()))
+            .filter(|_| recurrence.count.map(|count| occurrence <= count) != Some(false))
+            .ok_or_else(|| {
+                WebError::BadRequest("The series has no further occurrences".to_string())
+            })?;
+
+        let changes = TodoChanges {
+            due_at: Some(Some(due_at)),
+            occurrence: Some(occurrence),
+            updated_at: Some(Utc::now()),
+            ..TodoChanges::default()
+        };
+
+        self.save(changes, EventKind::TodoUpdated, None, client, storage)
+            .await?;
+        Ok(self)
+    }
+
+    /// Moves todo next to the anchor given in the form. Positions of other todos change only when
+    /// there is no room left between the anchor and its neighbour.
+    pub async fn reorder(
+        &mut self,
+        form: MoveTodo,
+        client: &Client,
+        storage: &Storage,
+    ) -> Result<&mut Self, WebError> {
+        Self::authorize(self.work_list_id, &client, storage).await?;
+
+        let (anchor_id, after) = match (form.before, form.after) {
+            (Some(anchor_id), None) => (anchor_id, false),
+            (None, Some(anchor_id)) => (anchor_id, true),
+            _ => {
+                return Err(WebError::BadRequest(
+                    "Exactly one of `before` and `after` has to be given".to_string(),
+                ))
+            }
+        };
+
+        if anchor_id == self.id {
+            return Err(WebError::BadRequest(
+                "Todo cannot be moved next to itself".to_string(),
+            ));
+        }
+
+        let mut rebalanced = false;
+        let position = loop {
+            let anchor = Self::find(anchor_id, client, storage).await?;
+            if anchor.work_list_id != self.work_list_id {
+                return Err(WebError::BadRequest(
+                    "Anchor todo belongs to a different work list".to_string(),
+                ));
+            }
+
+            let neighbour = storage
+                .adjacent_todo_position(self.work_list_id, anchor.position, after, self.id)
+                .await?;
+            let gap = if after {
+                (Some(anchor.position), neighbour)
+            } else {
+                (neighbour, Some(anchor.position))
+            };
+
+            match position_between(gap) {
+                Some(position) => break position,
+                None if !rebalanced => {
+                    // Todos changed meanwhile would be moved by a stale order, leave it to a retry.
+                    Self::rebalance(self.work_list_id, client, storage)
+                        .await
+                        .map_err(|err| match err {
+                            WebError::PreconditionFailed => WebError::ReorderConflict,
+                            err => err,
+                        })?;
+                    rebalanced = true;
+                }
+                None => {
+                    return Err(WebError::ReorderConflict);
+                }
+            }
+        };
+
+        let changes = TodoChanges {
+            position: Some(position),
+            updated_at: Some(Utc::now()),
+            ..TodoChanges::default()
+        };
+
+        self.save(changes, EventKind::TodoUpdated, None, client, storage)
+            .await?;
+        Ok(self)
+    }
+
+    /// Spaces positions of all todos in a work list `POSITION_STEP` apart, keeping their order.
+    /// Fails with `WebError::PreconditionFailed` if any of the todos changes meanwhile.
+    async fn rebalance(
+        work_list_id: i64,
+        client: &Client,
+        storage: &Storage,
+    ) -> Result<(), WebError> {
+        let mut todos = storage
+            .todos_in_work_lists(slice::from_ref(&work_list_id), None)
+            .await?;
+        Self::load_tags(&mut todos, storage).await?;
+
+        let mut moved = HashMap::new();
+        let mut moves = Vec::new();
+        for (idx, todo) in todos.into_iter().enumerate() {
+            let position = idx as i64 * POSITION_STEP;
+
+            if todo.position != position {
+                moves.push((todo.id, position, todo.version));
+                moved.insert(todo.id, Self { position, ..todo });
+            }
+        }
+
+        let client_id = client.id();
+        let events = storage
+            .move_todos(&moves, &|id, version| {
+                let todo = Self {
+                    version,
+                    ..moved[&id].clone()
+                };
+                NewEvent::new(EventKind::TodoUpdated, client_id, &todo)
+            })
             .await?;
 
+        for event in events {
+            event.publish(client);
+        }
+
         Ok(())
     }
+
+    /// Deletes todo together with its subtasks. `expected_version` is checked as by `save`,
+    /// subtasks are not checked.
+    pub async fn delete(
+        self,
+        expected_version: Option<i64>,
+        client: &Client,
+        storage: &Storage,
+    ) -> Result<(), WebError> {
+        Self::authorize(self.work_list_id, &client, storage).await?;
+
+        // Deleted todos are described by their IDs only. Subtasks share the work list.
+        let client_id = client.id();
+        let work_list_id = self.work_list_id;
+        let events = storage
+            .delete_todo(self.id, expected_version, &|id, _| {
+                let data = json!({ "id": id, "work_list_id": work_list_id });
+                NewEvent::new(EventKind::TodoDeleted, client_id, &data)
+            })
+            .await?;
+
+        for event in events {
+            event.publish(client);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono_tz::Tz;
+
+    fn utc(time: &str) -> DateTime<Utc> {
+        time.parse().unwrap()
+    }
+
+    fn date(date: &str) -> NaiveDate {
+        date.parse().unwrap()
+    }
+
+    fn today(time_zone: Tz, now: &str) -> (NaiveDate, DateTime<Utc>, DateTime<Utc>) {
+        match DueCondition::resolve(DueView::Today, &time_zone, utc(now)) {
+            DueCondition::Within { date, from, to } => (date, from, to),
+            condition => panic!("unexpected condition: {:?}", condition),
+        }
+    }
+
+    #[test]
+    fn today_follows_time_zones_either_side_of_utc() {
+        let (date_east, from, to) = today(Tz::Pacific__Auckland, "2020-01-01T12:00:00Z");
+        assert_eq!(date_east, date("2020-01-02"));
+        assert_eq!(from, utc("2020-01-01T11:00:00Z"));
+        assert_eq!(to, utc("2020-01-02T11:00:00Z"));
+
+        let (date_west, from, to) = today(Tz::America__Los_Angeles, "2020-01-02T03:00:00Z");
+        assert_eq!(date_west, date("2020-01-01"));
+        assert_eq!(from, utc("2020-01-01T08:00:00Z"));
+        assert_eq!(to, utc("2020-01-02T08:00:00Z"));
+
+        let (date_utc, from, to) = today(Tz::UTC, "2020-01-01T23:59:59Z");
+        assert_eq!(date_utc, date("2020-01-01"));
+        assert_eq!(from, utc("2020-01-01T00:00:00Z"));
+        assert_eq!(to, utc("2020-01-02T00:00:00Z"));
+    }
+
+    #[test]
+    fn today_spans_dst_transitions() {
+        let (date_spring, from, to) = today(Tz::Europe__Prague, "2020-03-29T12:00:00Z");
+        assert_eq!(date_spring, date("2020-03-29"));
+        assert_eq!(from, utc("2020-03-28T23:00:00Z"));
+        assert_eq!(to - from, Duration::hours(23));
+
+        let (date_autumn, from, to) = today(Tz::Europe__Prague, "2020-10-25T12:00:00Z");
+        assert_eq!(date_autumn, date("2020-10-25"));
+        assert_eq!(from, utc("2020-10-24T22:00:00Z"));
+        assert_eq!(to - from, Duration::hours(25));
+    }
+
+    #[test]
+    fn today_starts_after_a_dst_gap_at_midnight() {
+        // Clocks in São Paulo jumped from midnight to 1 AM on 4 November 2018.
+        let (date_gap, from, to) = today(Tz::America__Sao_Paulo, "2018-11-04T12:00:00Z");
+        assert_eq!(date_gap, date("2018-11-04"));
+        assert_eq!(from, utc("2018-11-04T03:00:00Z"));
+        assert_eq!(to, utc("2018-11-05T02:00:00Z"));
+
+        let (_, _, to) = today(Tz::America__Sao_Paulo, "2018-11-03T12:00:00Z");
+        assert_eq!(to, from);
+    }
+
+    #[test]
+    fn overdue_uses_the_local_date() {
+        let now = utc("2020-01-01T20:00:00Z");
+
+        match DueCondition::resolve(DueView::Overdue, &Tz::Asia__Tokyo, now) {
+            DueCondition::Before { date: today, time } => {
+                assert_eq!(today, date("2020-01-02"));
+                assert_eq!(time, now);
+            }
+            condition => panic!("unexpected condition: {:?}", condition),
+        }
+
+        match DueCondition::resolve(DueView::Overdue, &Tz::America__New_York, now) {
+            DueCondition::Before { date: today, .. } => assert_eq!(today, date("2020-01-01")),
+            condition => panic!("unexpected condition: {:?}", condition),
+        }
+    }
 }
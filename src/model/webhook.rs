@@ -0,0 +1,374 @@
+use actix_web::http::Uri;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::net::{IpAddr, SocketAddr};
+use tokio::net::lookup_host;
+
+use super::{Event, EventKind};
+use crate::database::{Storage, WebhookRepository};
+use crate::error::{Resource, WebError};
+use crate::forms::webhook::{CreateWebhook, ListDeliveries, UpdateWebhook};
+use crate::web_app::Client;
+
+/// Webhooks a single client can register.
+const MAX_WEBHOOKS: usize = 10;
+const DEFAULT_DELIVERIES_LIMIT: u32 = 50;
+
+/// Endpoint of a client notified about events with signed HTTP requests.
+#[derive(Debug, Clone, Serialize)]
+pub struct Webhook {
+    pub id: i64,
+    pub url: String,
+    /// Key of HMAC signatures of deliveries. It is never sent back to clients.
+    #[serde(skip)]
+    pub secret: String,
+    /// `None` subscribes to every event.
+    pub events: Option<Vec<EventKind>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    /// Waiting for the first attempt or for a retry.
+    Pending,
+    Delivered,
+    /// Given up after too many failed attempts.
+    Failed,
+}
+
+impl DeliveryStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DeliveryStatus::Pending => "pending",
+            DeliveryStatus::Delivered => "delivered",
+            DeliveryStatus::Failed => "failed",
+        }
+    }
+
+    pub fn from_column(status: &str) -> Self {
+        match status {
+            "delivered" => DeliveryStatus::Delivered,
+            "failed" => DeliveryStatus::Failed,
+            _ => DeliveryStatus::Pending,
+        }
+    }
+}
+
+/// Entry of the delivery log of a webhook.
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookDelivery {
+    pub id: i64,
+    pub event_id: i64,
+    pub event: EventKind,
+    pub status: DeliveryStatus,
+    pub attempts: u32,
+    /// HTTP status of the last response, if there was one.
+    pub response_status: Option<u16>,
+    /// Reason of the last failure.
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_attempt_at: Option<DateTime<Utc>>,
+    /// Set while the delivery is pending.
+    pub next_attempt_at: Option<DateTime<Utc>>,
+}
+
+/// Delivery leased by the worker, with everything needed to send it.
+#[derive(Debug)]
+pub struct PendingDelivery {
+    pub id: i64,
+    /// Failed attempts so far.
+    pub attempts: u32,
+    pub url: String,
+    pub secret: String,
+    pub event: Event,
+}
+
+/// Outcome of a single delivery attempt.
+#[derive(Debug)]
+pub struct DeliveryAttempt {
+    pub status: DeliveryStatus,
+    pub at: DateTime<Utc>,
+    pub response_status: Option<u16>,
+    pub error: Option<String>,
+    /// Time of the next attempt of a delivery still pending.
+    pub next_attempt_at: Option<DateTime<Utc>>,
+}
+
+/// Addresses of the network the server runs in: loopback, private, shared (carrier-grade NAT),
+/// link-local - cloud metadata endpoints included - and unique local ones. Multicast, reserved
+/// and translated addresses (NAT64, 6to4) are refused as well, as they may lead to any of those.
+fn is_internal(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let octets = ip.octets();
+
+            ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                || octets[0] == 0
+                || octets[0] >= 240
+                || (octets[0] == 100 && octets[1] & 0xc0 == 64)
+        }
+        IpAddr::V6(ip) => {
+            let segments = ip.segments();
+
+            ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                || segments[0] & 0xfe00 == 0xfc00
+                || segments[0] & 0xffc0 == 0xfe80
+                || segments[0] == 0x2002
+                || segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0]
+                || ip
+                    .to_ipv4()
+                    .map(|ip| is_internal(IpAddr::V4(ip)))
+                    .unwrap_or(false)
+        }
+    }
+}
+
+impl Webhook {
+    /// Resolves the host of a webhook URL and refuses it if any of its addresses is internal,
+    /// so that webhooks cannot reach services behind the server. Checked when a webhook is
+    /// registered and again before every delivery, as DNS records may change in between.
+    ///
+    /// Returns the checked address to connect to. Deliveries have to use it instead of
+    /// resolving the host again, which could give a different answer.
+    pub async fn check_destination(url: &str) -> Result<SocketAddr, String> {
+        let uri: Uri = url.parse().map_err(|_| "Invalid URL".to_string())?;
+        let host = uri
+            .host()
+            .ok_or_else(|| "URL has no host".to_string())?
+            .trim_start_matches('[')
+            .trim_end_matches(']');
+        let port = uri.port_u16().unwrap_or_else(|| match uri.scheme_str() {
+            Some("https") => 443,
+            _ => 80,
+        });
+
+        let addresses: Vec<SocketAddr> = lookup_host((host, port))
+            .await
+            .map_err(|_| format!("Host {} could not be resolved", host))?
+            .collect();
+
+        if addresses.is_empty() {
+            return Err(format!("Host {} could not be resolved", host));
+        }
+
+        if addresses.iter().any(|address| is_internal(address.ip())) {
+            return Err(format!("Host {} resolves to an internal address", host));
+        }
+
+        Ok(addresses[0])
+    }
+
+    pub fn accepts(&self, kind: EventKind) -> bool {
+        self.events
+            .as_ref()
+            .map(|events| events.contains(&kind))
+            .unwrap_or(true)
+    }
+
+    /// Events are stored space-separated, like scopes of API keys.
+    pub fn events_column(events: &[EventKind]) -> String {
+        events
+            .iter()
+            .map(|kind| kind.as_str())
+            .collect::<Vec<&str>>()
+            .join(" ")
+    }
+
+    /// Unknown events are ignored.
+    pub fn events_from_column(events: Option<&str>) -> Option<Vec<EventKind>> {
+        events.map(|events| {
+            events
+                .split_whitespace()
+                .filter_map(EventKind::parse)
+                .collect()
+        })
+    }
+
+    pub async fn list(client: &Client, storage: &Storage) -> Result<Vec<Self>, WebError> {
+        storage.list_webhooks(client.id()).await
+    }
+
+    pub async fn find(id: i64, client: &Client, storage: &Storage) -> Result<Self, WebError> {
+        storage
+            .find_webhook(id, client.id())
+            .await?
+            .ok_or(WebError::NotFound(Resource::Webhook))
+    }
+
+    pub async fn create(
+        form: CreateWebhook,
+        client: &Client,
+        storage: &Storage,
+    ) -> Result<Self, WebError> {
+        Self::check_destination(&form.url)
+            .await
+            .map_err(WebError::BadRequest)?;
+
+        storage
+            .create_webhook(
+                client.id(),
+                &form.url,
+                &form.secret,
+                form.events.as_deref(),
+                Utc::now(),
+                MAX_WEBHOOKS,
+            )
+            .await?
+            .ok_or_else(|| {
+                WebError::BadRequest(format!(
+                    "A client can have at most {} webhooks",
+                    MAX_WEBHOOKS
+                ))
+            })
+    }
+
+    pub async fn update(
+        &mut self,
+        form: UpdateWebhook,
+        client: &Client,
+        storage: &Storage,
+    ) -> Result<&mut Self, WebError> {
+        let events = form.events.as_ref().map(|events| events.as_deref());
+
+        if let Some(url) = form.url.as_deref() {
+            Self::check_destination(url)
+                .await
+                .map_err(WebError::BadRequest)?;
+        }
+
+        if !storage
+            .update_webhook(
+                self.id,
+                client.id(),
+                form.url.as_deref(),
+                form.secret.as_deref(),
+                events,
+            )
+            .await?
+        {
+            return Err(WebError::NotFound(Resource::Webhook));
+        }
+
+        if let Some(url) = form.url {
+            self.url = url;
+        }
+
+        if let Some(secret) = form.secret {
+            self.secret = secret;
+        }
+
+        if let Some(events) = form.events {
+            self.events = events;
+        }
+
+        Ok(self)
+    }
+
+    /// Deletes webhook together with its delivery log. Pending deliveries are dropped.
+    pub async fn delete(self, client: &Client, storage: &Storage) -> Result<(), WebError> {
+        if storage.delete_webhook(self.id, client.id()).await? {
+            Ok(())
+        } else {
+            Err(WebError::NotFound(Resource::Webhook))
+        }
+    }
+
+    /// Latest deliveries to the webhook, newest first.
+    pub async fn deliveries(
+        &self,
+        form: ListDeliveries,
+        storage: &Storage,
+    ) -> Result<Vec<WebhookDelivery>, WebError> {
+        storage
+            .list_webhook_deliveries(self.id, form.limit.unwrap_or(DEFAULT_DELIVERIES_LIMIT))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn internal(ip: &str) -> bool {
+        is_internal(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn refuses_internal_ipv4_addresses() {
+        for ip in &[
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "0.0.0.0",
+            "0.1.2.3",
+            "100.64.0.1",
+            "100.127.255.255",
+            "224.0.0.1",
+            "239.255.255.250",
+            "240.0.0.1",
+            "255.255.255.255",
+        ] {
+            assert!(internal(ip), "{} should be internal", ip);
+        }
+    }
+
+    #[test]
+    fn refuses_internal_ipv6_addresses() {
+        for ip in &[
+            "::1",
+            "::",
+            "fc00::1",
+            "fd12:3456::1",
+            "fe80::1",
+            "ff02::1",
+            "::ffff:127.0.0.1",
+            "::ffff:169.254.169.254",
+            "64:ff9b::7f00:1",
+            "64:ff9b::808:808",
+            "2002:7f00:1::",
+        ] {
+            assert!(internal(ip), "{} should be internal", ip);
+        }
+    }
+
+    #[test]
+    fn accepts_public_addresses() {
+        for ip in &[
+            "8.8.8.8",
+            "1.1.1.1",
+            "100.128.0.1",
+            "172.32.0.1",
+            "223.255.255.255",
+            "2001:4860:4860::8888",
+            "2606:4700::1111",
+            "::ffff:8.8.8.8",
+        ] {
+            assert!(!internal(ip), "{} should be public", ip);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn checks_literal_addresses_of_urls() {
+        let address = Webhook::check_destination("http://8.8.8.8:8080/hook")
+            .await
+            .unwrap();
+        assert_eq!(address, "8.8.8.8:8080".parse().unwrap());
+
+        assert!(Webhook::check_destination("http://127.0.0.1/hook")
+            .await
+            .is_err());
+        assert!(Webhook::check_destination("http://[::1]/hook")
+            .await
+            .is_err());
+        assert!(Webhook::check_destination("not a url").await.is_err());
+    }
+}
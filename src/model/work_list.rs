@@ -1,160 +1,295 @@
-use serde::Serialize;
-use sqlx::{self, FromRow};
+use actix_web::{error::Error, HttpRequest, HttpResponse, Responder};
+use chrono::{DateTime, Utc};
+use futures::future::{ready, Ready};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
 use std::collections::HashMap;
 
-use super::Todo;
-use crate::database::Pool;
-use crate::error::WebError;
-use crate::forms::work_list::{CreateWorkList, UpdateWorkList};
-use crate::web_app::Client;
+use super::page::{decode_cursor, encode_cursor, Page, DEFAULT_PAGE_SIZE};
+use super::{EventKind, NewEvent, Todo};
+use crate::database::{Storage, TodoRepository, WorkListRepository};
+use crate::error::{Resource, WebError};
+use crate::forms::work_list::{CreateWorkList, ListWorkLists, UpdateWorkList};
+use crate::web_app::{entity_tag, tagged_response, Client};
 
-#[derive(Serialize, Debug, FromRow)]
+impl Responder for WorkList {
+    type Error = Error;
+    type Future = Ready<Result<HttpResponse, Error>>;
+
+    fn respond_to(self, req: &HttpRequest) -> Self::Future {
+        ready(tagged_response(req, &self.etag(), &self).map_err(Error::from))
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
 pub struct WorkList {
     id: i64,
     name: String,
-    todos: Vec<Todo>,
+    created_at: DateTime<Utc>,
+    /// Change version, see `Todo::version`.
+    version: i64,
+    /// Omitted when a listing was requested without todos.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    todos: Option<Vec<Todo>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum WorkListSort {
+    #[serde(rename = "id")]
+    IdAsc,
+    #[serde(rename = "-id")]
+    IdDesc,
+    #[serde(rename = "name")]
+    NameAsc,
+    #[serde(rename = "-name")]
+    NameDesc,
+    #[serde(rename = "created_at")]
+    CreatedAtAsc,
+    #[serde(rename = "-created_at")]
+    CreatedAtDesc,
+}
+
+impl Default for WorkListSort {
+    fn default() -> Self {
+        WorkListSort::IdAsc
+    }
+}
+
+impl WorkListSort {
+    pub fn is_descending(self) -> bool {
+        use WorkListSort::*;
+
+        match self {
+            IdDesc | NameDesc | CreatedAtDesc => true,
+            IdAsc | NameAsc | CreatedAtAsc => false,
+        }
+    }
+}
+
+/// Last work list of a page. Listing continues with work lists sorted after it, ties on the
+/// sort key are broken by ID.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkListPosition {
+    pub sort: WorkListSort,
+    pub id: i64,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Criteria of a work list listing, as passed to storage.
+#[derive(Debug)]
+pub struct WorkListQuery {
+    /// Case-insensitive substring of the name.
+    pub name: Option<String>,
+    /// `None` means every work list of the client.
+    pub ids: Option<Vec<i64>>,
+    /// Name of a tag carried by at least one todo of the work list.
+    pub tag: Option<String>,
+    pub sort: WorkListSort,
+    pub after: Option<WorkListPosition>,
+    pub limit: u32,
 }
 
 impl WorkList {
-    fn new(id: i64, name: String, todos: Vec<Todo>) -> Self {
-        Self { id, name, todos }
+    pub(crate) fn new(
+        id: i64,
+        name: String,
+        created_at: DateTime<Utc>,
+        version: i64,
+        todos: Vec<Todo>,
+    ) -> Self {
+        Self {
+            id,
+            name,
+            created_at,
+            version,
+            todos: Some(todos),
+        }
+    }
+
+    pub fn id(&self) -> i64 {
+        self.id
+    }
+
+    pub fn version(&self) -> i64 {
+        self.version
+    }
+
+    /// Entity tag of the work list together with its todos, which changes whenever any of them
+    /// does. Versions only grow, so the latest one and the number of todos tell every change.
+    pub fn etag(&self) -> String {
+        let mut latest = self.version;
+        let mut count = 0;
+        let mut todos: Vec<&Todo> = self.todos.iter().flatten().collect();
+
+        while let Some(todo) = todos.pop() {
+            latest = latest.max(todo.version);
+            count += 1;
+            todos.extend(todo.subtasks.iter().flatten());
+        }
+
+        entity_tag(format!("{}-{}", latest, count))
     }
 
+    /// Omits todos, for responses listing them on their own.
+    pub(crate) fn without_todos(mut self) -> Self {
+        self.todos = None;
+        self
+    }
+
+    fn position(&self, sort: WorkListSort) -> WorkListPosition {
+        WorkListPosition {
+            sort,
+            id: self.id,
+            name: self.name.clone(),
+            created_at: self.created_at,
+        }
+    }
+
+    /// `mutation_id` identifies a sync mutation creating the work list, see `MutationResult`.
     pub async fn create(
         form: CreateWorkList,
+        mutation_id: Option<&str>,
         client: &Client,
-        pool: &Pool,
+        storage: &Storage,
     ) -> Result<Self, WebError> {
-        let mut conn = pool.acquire().await?;
-
-        #[cfg(target_feature = "postgres")]
-        {
-            let name = form.name.clone();
-            let client_id = client.id();
-            let id = sqlx::query!(
-                "INSERT INTO work_lists (name, client_id) VALUES ($1, false, $2) RETURNING id",
-                name,
-                client_id
+        let client_id = client.id();
+        let created_at = Utc::now();
+        let (work_list, event) = storage
+            .create_work_list(
+                &form.name,
+                client_id,
+                created_at,
+                mutation_id,
+                &|id, version| {
+                    let work_list = Self::new(id, form.name.clone(), created_at, version, vec![]);
+                    NewEvent::new(EventKind::WorkListCreated, client_id, &work_list)
+                },
             )
-            .fetch_one(&mut conn)
-            .await
-            .map(|row: database::Row| row.get("id"))?;
+            .await?;
 
-            Ok(Self::new(id, form.name, vec![]))
-        }
+        event.publish(client);
+        Ok(work_list)
+    }
 
-        #[cfg(not(target_feature = "postgres"))]
-        {
-            let name = form.name.clone();
-            let client_id = client.id();
-            sqlx::query!(
-                "INSERT INTO work_lists (name, client_id) VALUES (?, ?)",
-                name,
-                client_id
-            )
-            .execute(&mut conn)
-            .await?;
+    pub async fn list(
+        form: ListWorkLists,
+        client: &Client,
+        storage: &Storage,
+    ) -> Result<Page<Self>, WebError> {
+        let sort = form.sort.unwrap_or_default();
+        let limit = form.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+        let after = match form.after.as_deref() {
+            Some(cursor) => Some(decode_cursor::<WorkListPosition>(cursor)?),
+            None => None,
+        };
+
+        if after.as_ref().map(|position| position.sort != sort) == Some(true) {
+            return Err(WebError::BadRequest(
+                "Cursor was issued for a different sort order".to_string(),
+            ));
+        }
 
-            let id: (i64,) = sqlx::query_as("SELECT last_insert_rowid()")
-                .fetch_one(&mut conn)
-                .await?;
+        let query = WorkListQuery {
+            name: form.name,
+            ids: client.permissions().work_list_ids.clone(),
+            tag: form.tag,
+            sort,
+            after,
+            limit: limit + 1,
+        };
+        let work_lists = storage.list_work_lists(client.id(), &query).await?;
+        let mut page = Page::new(work_lists, limit, |work_list| {
+            encode_cursor(&work_list.position(sort))
+        });
 
-            Ok(Self::new(id.0, form.name, vec![]))
+        if form.todos_limit == Some(0) {
+            for work_list in page.items.iter_mut() {
+                work_list.todos = None;
+            }
+            return Ok(page);
         }
-    }
 
-    pub async fn list(client: &Client, pool: &Pool) -> Result<Vec<Self>, WebError> {
-        let mut conn = pool.acquire().await?;
-
-        let work_lists_query: Vec<(i64, String)> =
-            sqlx::query_as("SELECT id, name FROM work_lists WHERE work_lists.client_id = ?")
-                .bind(client.id())
-                .fetch_all(&mut conn)
-                .await?;
-
-        // FIXME: There is no way to bind IN-list parameter in sqlx reliably now - let's create it manually.
-        let todos_sql = format!(
-            "SELECT * FROM todos WHERE todos.work_list_id IN ({})",
-            work_lists_query
-                .iter()
-                .map(|wl| wl.0.to_string())
-                .collect::<Vec<String>>()
-                .join(", ")
-        );
-
-        let todos: Vec<Todo> = sqlx::query_as(&todos_sql).fetch_all(&mut conn).await?;
+        let ids: Vec<i64> = page.items.iter().map(|wl| wl.id).collect();
+        let mut todos = storage.todos_in_work_lists(&ids, form.todos_limit).await?;
+        Todo::load_tags(&mut todos, storage).await?;
         let mut todos_map = todos.into_iter().fold(HashMap::new(), |mut map, todo| {
             map.entry(todo.work_list_id).or_insert(vec![]).push(todo);
             map
         });
 
-        Ok(work_lists_query
-            .into_iter()
-            .map(|(id, name)| Self::new(id, name, todos_map.remove(&id).unwrap_or(vec![])))
-            .collect())
+        for work_list in page.items.iter_mut() {
+            work_list.todos = Some(todos_map.remove(&work_list.id).unwrap_or(vec![]));
+        }
+
+        Ok(page)
     }
 
-    pub async fn delete(self, client: &Client, pool: &Pool) -> Result<(), WebError> {
+    /// Todos of the work list are deleted with it, without events of their own. With
+    /// `expected_version`, fails with 412 Precondition Failed if the work list is no longer at
+    /// that version.
+    pub async fn delete(
+        self,
+        expected_version: Option<i64>,
+        client: &Client,
+        storage: &Storage,
+    ) -> Result<(), WebError> {
         let client_id = client.id();
-        let rows_affected: u64 = sqlx::query!(
-            "DELETE FROM work_lists WHERE id = ? AND client_id = ?",
-            self.id,
-            client_id
-        )
-        .execute(&*pool)
-        .await?;
-
-        if rows_affected > 0 {
-            Ok(())
-        } else {
-            Err(WebError::DatabaseError(sqlx::Error::RowNotFound))
-        }
+        let data = json!({ "id": self.id });
+        let event = storage
+            .delete_work_list(self.id, client_id, expected_version, &|_, _| {
+                NewEvent::new(EventKind::WorkListDeleted, client_id, &data)
+            })
+            .await?
+            .ok_or(WebError::NotFound(Resource::WorkList))?;
+
+        event.publish(client);
+        Ok(())
     }
 
+    /// `expected_version` is checked the same way as by `delete`.
     pub async fn update(
         &mut self,
         client: &Client,
         form: UpdateWorkList,
-        pool: &Pool,
+        expected_version: Option<i64>,
+        storage: &Storage,
     ) -> Result<&mut Self, WebError> {
+        self.name = form.name;
+
         let client_id = client.id();
-        let new_name = form.name.clone();
-        let rows_affected: u64 = sqlx::query!(
-            "UPDATE work_lists SET name = ? WHERE id = ? AND client_id = ?",
-            new_name,
-            self.id,
-            client_id
-        )
-        .execute(&*pool)
-        .await?;
-
-        if rows_affected > 0 {
-            self.name = form.name;
-            Ok(self)
-        } else {
-            Err(WebError::DatabaseError(sqlx::Error::RowNotFound))
-        }
-    }
+        let work_list = &*self;
+        let (version, event) = storage
+            .update_work_list(
+                work_list.id,
+                client_id,
+                &work_list.name,
+                expected_version,
+                &|_, version| {
+                    let work_list = Self {
+                        version,
+                        ..work_list.clone()
+                    };
+                    NewEvent::new(EventKind::WorkListUpdated, client_id, &work_list)
+                },
+            )
+            .await?
+            .ok_or(WebError::NotFound(Resource::WorkList))?;
 
-    pub async fn find(id: i64, client: &Client, pool: &Pool) -> Result<Self, WebError> {
-        let mut conn = pool.acquire().await?;
+        self.version = version;
+        event.publish(client);
+        Ok(self)
+    }
 
-        let todos = sqlx::query_as!(Todo, "SELECT * FROM todos WHERE todos.work_list_id = ?", id)
-            .fetch_all(&mut conn)
-            .await?;
+    pub async fn find(id: i64, client: &Client, storage: &Storage) -> Result<Self, WebError> {
+        let mut work_list = storage
+            .find_work_list(id, client.id())
+            .await?
+            .ok_or(WebError::NotFound(Resource::WorkList))?;
 
-        let mut query: Option<(i64, String)> = sqlx::query_as(
-            "SELECT id, name FROM work_lists WHERE work_lists.id = ? AND work_lists.client_id = ?",
-        )
-        .bind(id)
-        .bind(client.id())
-        .fetch_optional(&mut conn)
-        .await?;
-
-        if let Some(row) = query.take() {
-            Ok(WorkList::new(row.0, row.1, todos))
-        } else {
-            Err(WebError::DatabaseError(sqlx::Error::RowNotFound))
-        }
+        let mut todos = storage.todos_in_work_lists(&[work_list.id], None).await?;
+        Todo::load_tags(&mut todos, storage).await?;
+        work_list.todos = Some(Todo::into_tree(todos));
+        Ok(work_list)
     }
 }
@@ -0,0 +1,65 @@
+use actix_rt::time::delay_for;
+use anyhow::Result;
+use chrono::Utc;
+use log::{error, info, warn};
+use std::env;
+use std::time::Duration;
+
+use super::{enabled_in_env, millis_from_env};
+use crate::database::{EventRepository, Storage};
+
+/// Events are kept for so many days unless `EVENT_RETENTION_DAYS` says otherwise.
+const DEFAULT_RETENTION_DAYS: u32 = 30;
+
+pub struct EventRetentionConfig {
+    interval: Duration,
+    retention_days: u32,
+}
+
+impl EventRetentionConfig {
+    /// Returns `None` if pruning of events is disabled with `EVENT_PRUNING_ENABLED`.
+    pub fn from_env() -> Result<Option<Self>> {
+        if !enabled_in_env("EVENT_PRUNING_ENABLED") {
+            info!("Pruning of events is disabled");
+            return Ok(None);
+        }
+
+        let interval = millis_from_env("EVENT_PRUNE_INTERVAL", Duration::from_millis(3600000));
+        let retention_days = env::var("EVENT_RETENTION_DAYS")
+            .map(|days| {
+                days.parse()
+                    .map_err(|err| {
+                        warn!(
+                            "Failed to read EVENT_RETENTION_DAYS from env var: {:?}",
+                            err
+                        )
+                    })
+                    .unwrap_or(DEFAULT_RETENTION_DAYS)
+            })
+            .unwrap_or(DEFAULT_RETENTION_DAYS);
+
+        info!("Event prune interval: {} ms", interval.as_millis());
+        info!("Event retention: {} days", retention_days);
+
+        Ok(Some(Self {
+            interval,
+            retention_days,
+        }))
+    }
+}
+
+/// Deletes events older than the retention until the process exits. Change streams can't be
+/// resumed from pruned events, which are skipped.
+pub async fn run(storage: Storage, config: EventRetentionConfig) {
+    info!("Event pruning started");
+
+    loop {
+        let before = Utc::now() - chrono::Duration::days(config.retention_days as i64);
+
+        if let Err(err) = storage.prune_events(before).await {
+            error!("Failed to prune events: {:?}", err);
+        }
+
+        delay_for(config.interval).await;
+    }
+}
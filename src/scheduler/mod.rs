@@ -0,0 +1,65 @@
+use anyhow::Result;
+use log::warn;
+use rand::Rng;
+use std::env;
+use std::time::Duration;
+
+use crate::database::Storage;
+
+mod events;
+mod notifier;
+mod reminders;
+mod webhooks;
+
+pub use events::EventRetentionConfig;
+pub use notifier::{LogNotifier, Notification, Notifier, SmtpNotifier, WebhookNotifier};
+pub use reminders::ReminderConfig;
+pub use webhooks::{signature, WebhookConfig};
+
+/// Workers are enabled unless their variable is set to `0` or `false`.
+fn enabled_in_env(name: &str) -> bool {
+    env::var(name)
+        .map(|value| value != "0" && value.to_lowercase() != "false")
+        .unwrap_or(true)
+}
+
+fn millis_from_env(name: &str, default: Duration) -> Duration {
+    env::var(name)
+        .map(|millis| {
+            millis
+                .parse()
+                .map_err(|err| warn!("Failed to read {} from env var: {:?}", name, err))
+                .map(Duration::from_millis)
+                .unwrap_or(default)
+        })
+        .unwrap_or(default)
+}
+
+/// Identifies this instance in leases of reminders and webhook deliveries.
+fn lease_owner() -> String {
+    format!(
+        "{}-{:08x}",
+        std::process::id(),
+        rand::thread_rng().gen::<u32>()
+    )
+}
+
+/// Starts background workers enabled in the environment on the current arbiter.
+pub async fn spawn(storage: Storage) -> Result<()> {
+    let owner = lease_owner();
+
+    if let Some(config) = ReminderConfig::from_env()? {
+        config.check(&storage).await?;
+        actix_rt::spawn(reminders::run(storage.clone(), config, owner.clone()));
+    }
+
+    if let Some(config) = WebhookConfig::from_env()? {
+        actix_rt::spawn(webhooks::run(storage.clone(), config, owner));
+    }
+
+    if let Some(config) = EventRetentionConfig::from_env()? {
+        actix_rt::spawn(events::run(storage, config));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,242 @@
+use actix_web::client::Client as HttpClient;
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use chrono::Utc;
+use log::info;
+use serde::Serialize;
+use serde_json::json;
+use std::time::Duration;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+
+use crate::model::{Due, Reminder, Todo};
+
+/// Time a single delivery may take.
+pub const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Reminder due for delivery, together with its todo.
+#[derive(Debug, Serialize)]
+pub struct Notification {
+    pub client_id: i64,
+    pub reminder: Reminder,
+    pub todo: Todo,
+}
+
+impl Notification {
+    /// Human readable description used by notifiers sending plain text.
+    fn text(&self) -> String {
+        let due = match self.todo.due_at {
+            Some(Due::At(time)) => format!("Due at {}", time.to_rfc3339()),
+            Some(Due::On(date)) => format!("Due on {}", date),
+            None => "No due date".to_string(),
+        };
+
+        format!(
+            "{}\n\n{}\nTodo {} in work list {}",
+            self.todo.content, due, self.todo.id, self.todo.work_list_id
+        )
+    }
+}
+
+/// Channel reminders are delivered through. An error makes the scheduler retry the delivery
+/// later, so a notification may be delivered again if a notifier fails after sending it. Delivery
+/// is at least once - notifiers pass the reminder ID on, so that receivers can drop duplicates.
+#[async_trait(?Send)]
+pub trait Notifier {
+    async fn notify(&self, notification: &Notification) -> Result<()>;
+}
+
+/// Writes reminders to the application log.
+pub struct LogNotifier;
+
+#[async_trait(?Send)]
+impl Notifier for LogNotifier {
+    async fn notify(&self, notification: &Notification) -> Result<()> {
+        info!(
+            "Reminder {} of todo {} (client {}): {}",
+            notification.reminder.id,
+            notification.todo.id,
+            notification.client_id,
+            notification.todo.content
+        );
+
+        Ok(())
+    }
+}
+
+/// POSTs reminders as JSON to a fixed URL. Responses other than 2xx count as failures. The
+/// `X-Reminder-Id` header is the same for all attempts.
+pub struct WebhookNotifier {
+    url: String,
+    http: HttpClient,
+}
+
+impl WebhookNotifier {
+    pub fn new(url: &str) -> Self {
+        Self {
+            url: url.to_owned(),
+            http: HttpClient::build().timeout(TIMEOUT).finish(),
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Notifier for WebhookNotifier {
+    async fn notify(&self, notification: &Notification) -> Result<()> {
+        let payload = json!({
+            "event": "todo.reminder",
+            "client_id": notification.client_id,
+            "reminder": notification.reminder,
+            "todo": notification.todo,
+        });
+
+        let response = self
+            .http
+            .post(&self.url)
+            .header("X-Reminder-Id", notification.reminder.id.to_string())
+            .send_json(&payload)
+            .await
+            .map_err(|err| anyhow!("Webhook request failed: {}", err))?;
+
+        if response.status().is_success() {
+            Ok(())
+        } else {
+            Err(anyhow!("Webhook responded with {}", response.status()))
+        }
+    }
+}
+
+/// Sends reminders as plain text e-mails through an SMTP relay, e.g. one listening on
+/// localhost. Neither TLS nor authentication is supported. All attempts carry the same
+/// `Message-ID`, built from the reminder ID.
+pub struct SmtpNotifier {
+    address: String,
+    from: String,
+    to: String,
+}
+
+impl SmtpNotifier {
+    pub fn new(address: &str, from: &str, to: &str) -> Self {
+        Self {
+            address: address.to_owned(),
+            from: from.to_owned(),
+            to: to.to_owned(),
+        }
+    }
+
+    fn message_id(&self, notification: &Notification) -> String {
+        let domain = match self.from.rsplit('@').next() {
+            Some(domain) if domain != self.from && !domain.is_empty() => domain,
+            _ => "localhost",
+        };
+
+        format!("<reminder-{}@{}>", notification.reminder.id, domain)
+    }
+
+    fn message(&self, notification: &Notification) -> String {
+        let subject: String = notification
+            .todo
+            .content
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .chars()
+            .filter(|c| !c.is_control())
+            .take(80)
+            .collect();
+
+        let mut message = format!(
+            "From: <{}>\r\nTo: <{}>\r\nSubject: Reminder: {}\r\nDate: {}\r\nMessage-ID: {}\r\nMIME-Version: 1.0\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: 8bit\r\n\r\n",
+            self.from,
+            self.to,
+            subject,
+            Utc::now().to_rfc2822(),
+            self.message_id(notification)
+        );
+
+        // Lines starting with a dot are escaped, a lone dot would end the message.
+        for line in notification.text().lines() {
+            if line.starts_with('.') {
+                message.push('.');
+            }
+
+            message.push_str(line);
+            message.push_str("\r\n");
+        }
+
+        message.push_str(".\r\n");
+        message
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<()> {
+        let mut stream = TcpStream::connect(self.address.as_str()).await?;
+        let (reader, mut writer) = stream.split();
+        let mut reader = BufReader::new(reader);
+
+        read_reply(&mut reader, "220").await?;
+        command(&mut writer, &mut reader, "HELO localhost", "250").await?;
+        command(
+            &mut writer,
+            &mut reader,
+            &format!("MAIL FROM:<{}>", self.from),
+            "250",
+        )
+        .await?;
+        command(
+            &mut writer,
+            &mut reader,
+            &format!("RCPT TO:<{}>", self.to),
+            "250",
+        )
+        .await?;
+        command(&mut writer, &mut reader, "DATA", "354").await?;
+
+        writer
+            .write_all(self.message(notification).as_bytes())
+            .await?;
+        read_reply(&mut reader, "250").await?;
+
+        command(&mut writer, &mut reader, "QUIT", "221").await
+    }
+}
+
+/// Reads a possibly multiline SMTP reply and checks its code.
+async fn read_reply<R: AsyncBufRead + Unpin>(reader: &mut R, code: &str) -> Result<()> {
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+
+        if reader.read_line(&mut line).await? == 0 {
+            return Err(anyhow!("SMTP server closed the connection"));
+        }
+
+        if !line.starts_with(code) {
+            return Err(anyhow!("Unexpected SMTP reply: {}", line.trim_end()));
+        }
+
+        // Every line but the last one has a dash after the code.
+        if line.as_bytes().get(3) != Some(&b'-') {
+            return Ok(());
+        }
+    }
+}
+
+async fn command<W, R>(writer: &mut W, reader: &mut R, command: &str, code: &str) -> Result<()>
+where
+    W: AsyncWrite + Unpin,
+    R: AsyncBufRead + Unpin,
+{
+    writer.write_all(command.as_bytes()).await?;
+    writer.write_all(b"\r\n").await?;
+    read_reply(reader, code).await
+}
+
+#[async_trait(?Send)]
+impl Notifier for SmtpNotifier {
+    async fn notify(&self, notification: &Notification) -> Result<()> {
+        tokio::time::timeout(TIMEOUT, self.send(notification))
+            .await
+            .map_err(|_| anyhow!("SMTP server timed out"))?
+    }
+}
@@ -0,0 +1,252 @@
+use actix_rt::time::delay_for;
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use log::{error, info, warn};
+use std::env;
+use std::time::Duration;
+
+use super::notifier::{self, LogNotifier, Notification, Notifier, SmtpNotifier, WebhookNotifier};
+use super::{enabled_in_env, millis_from_env};
+use crate::database::{ClientRepository, ReminderRepository, Storage};
+use crate::error::WebError;
+use crate::model::{Reminder, Todo};
+
+/// Reminders leased at once. Together with `notifier::TIMEOUT` it bounds the time a batch
+/// takes, which has to stay below the lease.
+const BATCH_SIZE: u32 = 10;
+/// Delivery of a reminder is given up after so many failed attempts.
+const MAX_ATTEMPTS: u32 = 5;
+/// Delay before the first retry, doubled after every failed attempt.
+const RETRY_DELAY_SECS: i64 = 30;
+
+/// Notifier chosen with `REMINDER_NOTIFIER`.
+enum NotifierKind {
+    Log,
+    Webhook {
+        url: String,
+    },
+    Smtp {
+        address: String,
+        from: String,
+        to: String,
+    },
+}
+
+pub struct ReminderConfig {
+    interval: Duration,
+    lease: Duration,
+    notifier: NotifierKind,
+    /// The webhook and SMTP notifiers deliver to a single operator-owned destination, so they
+    /// serve reminders of this client only. Others are written to the log.
+    client_id: Option<i64>,
+}
+
+impl ReminderConfig {
+    /// Returns `None` if reminders are disabled with `REMINDERS_ENABLED`.
+    pub fn from_env() -> Result<Option<Self>> {
+        if !enabled_in_env("REMINDERS_ENABLED") {
+            info!("Reminders are disabled");
+            return Ok(None);
+        }
+
+        let interval = millis_from_env("REMINDER_INTERVAL", Duration::from_millis(10000));
+        let lease = millis_from_env("REMINDER_LEASE", Duration::from_millis(120000));
+
+        let notifier = match env::var("REMINDER_NOTIFIER")
+            .unwrap_or("log".to_string())
+            .as_str()
+        {
+            "log" => NotifierKind::Log,
+            "webhook" => NotifierKind::Webhook {
+                url: env::var("REMINDER_WEBHOOK_URL")
+                    .map_err(|_| anyhow!("REMINDER_WEBHOOK_URL not provided"))?,
+            },
+            "smtp" => NotifierKind::Smtp {
+                address: env::var("REMINDER_SMTP_ADDRESS").unwrap_or("127.0.0.1:25".to_string()),
+                from: env::var("REMINDER_SMTP_FROM")
+                    .unwrap_or("todo-list-api@localhost".to_string()),
+                to: env::var("REMINDER_SMTP_TO")
+                    .map_err(|_| anyhow!("REMINDER_SMTP_TO not provided"))?,
+            },
+            other => {
+                return Err(anyhow!(
+                    "Unsupported REMINDER_NOTIFIER {}, expected log, webhook or smtp",
+                    other
+                ))
+            }
+        };
+
+        let client_id = match notifier {
+            NotifierKind::Log => None,
+            NotifierKind::Webhook { .. } | NotifierKind::Smtp { .. } => Some(
+                env::var("REMINDER_CLIENT_ID")
+                    .map_err(|_| anyhow!("REMINDER_CLIENT_ID not provided"))?
+                    .parse()
+                    .map_err(|err| anyhow!("Invalid REMINDER_CLIENT_ID: {:?}", err))?,
+            ),
+        };
+
+        if lease < notifier::TIMEOUT * BATCH_SIZE {
+            warn!(
+                "Reminder lease of {} ms may expire before a batch is delivered",
+                lease.as_millis()
+            );
+        }
+
+        info!("Reminder check interval: {} ms", interval.as_millis());
+        info!("Reminder lease: {} ms", lease.as_millis());
+
+        if let Some(client_id) = client_id {
+            info!("Reminders are sent for client {} only", client_id);
+        }
+
+        Ok(Some(Self {
+            interval,
+            lease,
+            notifier,
+            client_id,
+        }))
+    }
+
+    /// Fails if the client reminders are sent for does not exist, so that a mistyped
+    /// `REMINDER_CLIENT_ID` doesn't silently send every reminder to the log.
+    pub async fn check(&self, storage: &Storage) -> Result<()> {
+        if let Some(client_id) = self.client_id {
+            let client = storage
+                .find_client(client_id)
+                .await
+                .map_err(|err| anyhow!("Failed to find reminder client: {:?}", err))?;
+
+            if client.is_none() {
+                return Err(anyhow!(
+                    "REMINDER_CLIENT_ID {} does not exist. Refusing to start.",
+                    client_id
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn notifier(&self) -> Box<dyn Notifier> {
+        match &self.notifier {
+            NotifierKind::Log => Box::new(LogNotifier),
+            NotifierKind::Webhook { url } => Box::new(WebhookNotifier::new(url)),
+            NotifierKind::Smtp { address, from, to } => {
+                Box::new(SmtpNotifier::new(address, from, to))
+            }
+        }
+    }
+}
+
+/// Delivers due reminders through the configured notifier until the process exits.
+pub async fn run(storage: Storage, config: ReminderConfig, owner: String) {
+    let notifier = config.notifier();
+    let notifier = notifier.as_ref();
+    info!("Reminder scheduler started as {}", owner);
+
+    loop {
+        match deliver_due(&storage, &config, notifier, &owner).await {
+            // A full batch means there may be more reminders waiting.
+            Ok(leased) if leased == BATCH_SIZE as usize => continue,
+            Ok(_) => {}
+            Err(err) => error!("Failed to lease reminders: {:?}", err),
+        }
+
+        delay_for(config.interval).await;
+    }
+}
+
+/// Loads what the notifier needs to know about a reminder. Returns `None` if the client or the
+/// todo is gone.
+async fn notification(
+    reminder: Reminder,
+    client_id: i64,
+    storage: &Storage,
+) -> Result<Option<Notification>, WebError> {
+    let client = match storage.find_client(client_id).await? {
+        Some(client) => client,
+        None => return Ok(None),
+    };
+
+    match Todo::find(reminder.todo_id, &client, storage).await {
+        Ok(todo) => Ok(Some(Notification {
+            client_id,
+            reminder,
+            todo,
+        })),
+        Err(WebError::NotFound(_)) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Leases a batch of due reminders and delivers them one by one. Returns the number of leased
+/// reminders.
+async fn deliver_due(
+    storage: &Storage,
+    config: &ReminderConfig,
+    notifier: &dyn Notifier,
+    owner: &str,
+) -> Result<usize, WebError> {
+    let now = Utc::now();
+    let lease_until = now + chrono::Duration::milliseconds(config.lease.as_millis() as i64);
+    let timeout = chrono::Duration::milliseconds(notifier::TIMEOUT.as_millis() as i64);
+
+    let leased = storage
+        .lease_due_reminders(owner, now, lease_until, BATCH_SIZE)
+        .await?;
+    let count = leased.len();
+
+    for (reminder, client_id) in leased {
+        // Reminders left over are leased again once the lease expires.
+        if Utc::now() + timeout >= lease_until {
+            warn!("Reminder lease is about to expire, postponing the rest of the batch");
+            break;
+        }
+
+        let id = reminder.id;
+        let attempts = reminder.attempts + 1;
+
+        let notification = match notification(reminder, client_id, storage).await? {
+            Some(notification) => notification,
+            None => {
+                storage
+                    .finish_reminder(id, owner, false, Utc::now())
+                    .await?;
+                continue;
+            }
+        };
+
+        // Reminders of other clients must not reach the destination of the configured one.
+        let channel: &dyn Notifier = match config.client_id {
+            Some(owner_id) if owner_id != client_id => &LogNotifier,
+            _ => notifier,
+        };
+
+        let recorded = match channel.notify(&notification).await {
+            Ok(()) => storage.finish_reminder(id, owner, true, Utc::now()).await?,
+            Err(err) if attempts >= MAX_ATTEMPTS => {
+                error!(
+                    "Giving up reminder {} after {} attempts: {:?}",
+                    id, attempts, err
+                );
+                storage
+                    .finish_reminder(id, owner, false, Utc::now())
+                    .await?
+            }
+            Err(err) => {
+                warn!("Failed to deliver reminder {}: {:?}", id, err);
+                let delay = chrono::Duration::seconds(RETRY_DELAY_SECS << (attempts - 1));
+                storage
+                    .release_reminder(id, owner, Utc::now() + delay)
+                    .await?
+            }
+        };
+
+        if !recorded {
+            warn!("Lease of reminder {} expired before it was processed", id);
+        }
+    }
+
+    Ok(count)
+}
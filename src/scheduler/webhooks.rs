@@ -0,0 +1,262 @@
+use actix_rt::time::delay_for;
+use actix_web::client::{Client as HttpClient, SendRequestError};
+use anyhow::Result;
+use chrono::Utc;
+use hmac::{Hmac, Mac, NewMac};
+use log::{error, info, warn};
+use sha2::Sha256;
+use std::time::{Duration, Instant};
+
+use super::{enabled_in_env, millis_from_env};
+use crate::database::{Storage, WebhookRepository};
+use crate::error::WebError;
+use crate::model::{DeliveryAttempt, DeliveryStatus, PendingDelivery, Webhook};
+
+/// Deliveries leased at once. Together with `TIMEOUT` it bounds the time a batch takes, which
+/// has to stay below the lease.
+const BATCH_SIZE: u32 = 20;
+/// Delivery is given up after so many failed attempts.
+const MAX_ATTEMPTS: u32 = 8;
+/// Delay before the first retry, doubled after every failed attempt.
+const RETRY_DELAY_SECS: i64 = 10;
+/// Time a single request may take.
+const TIMEOUT: Duration = Duration::from_secs(10);
+/// Finished deliveries are kept in the log for so many days.
+const LOG_RETENTION_DAYS: i64 = 30;
+const PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+/// Longest error message stored in the delivery log.
+const MAX_ERROR_LENGTH: usize = 500;
+
+pub struct WebhookConfig {
+    interval: Duration,
+    lease: Duration,
+}
+
+impl WebhookConfig {
+    /// Returns `None` if webhook delivery is disabled with `WEBHOOKS_ENABLED`.
+    pub fn from_env() -> Result<Option<Self>> {
+        if !enabled_in_env("WEBHOOKS_ENABLED") {
+            info!("Webhook delivery is disabled");
+            return Ok(None);
+        }
+
+        let interval = millis_from_env("WEBHOOK_INTERVAL", Duration::from_millis(5000));
+        let lease = millis_from_env("WEBHOOK_LEASE", Duration::from_millis(300000));
+
+        if lease < TIMEOUT * BATCH_SIZE {
+            warn!(
+                "Webhook lease of {} ms may expire before a batch is delivered",
+                lease.as_millis()
+            );
+        }
+
+        info!("Webhook check interval: {} ms", interval.as_millis());
+        info!("Webhook lease: {} ms", lease.as_millis());
+
+        Ok(Some(Self { interval, lease }))
+    }
+}
+
+/// Value of the `X-Webhook-Signature` header: hex-encoded HMAC-SHA256 of
+/// `{timestamp}.{body}`, keyed with the secret of the webhook.
+pub fn signature(secret: &str, timestamp: i64, body: &[u8]) -> String {
+    // HMAC accepts keys of any length.
+    let mut mac = Hmac::<Sha256>::new_varkey(secret.as_bytes()).unwrap();
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body);
+
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Posts the event of a delivery to its webhook. Returns the status of the response, or a
+/// description of the error if there was none. Descriptions end up in the delivery log shown to
+/// clients, so details of transport errors are only logged.
+async fn send(http: &HttpClient, delivery: &PendingDelivery) -> Result<u16, String> {
+    // The request connects to the checked address, so DNS is not asked again.
+    let address = Webhook::check_destination(&delivery.url).await?;
+
+    let body = serde_json::to_vec(&delivery.event).map_err(|err| {
+        error!(
+            "Failed to encode event of webhook delivery {}: {}",
+            delivery.id, err
+        );
+        "Failed to encode the event".to_string()
+    })?;
+    let timestamp = Utc::now().timestamp();
+
+    let response = http
+        .post(&delivery.url)
+        .address(address)
+        .content_type("application/json")
+        .header("X-Webhook-Event", delivery.event.kind.as_str())
+        .header("X-Webhook-Delivery", delivery.id.to_string())
+        .header("X-Webhook-Timestamp", timestamp.to_string())
+        .header(
+            "X-Webhook-Signature",
+            signature(&delivery.secret, timestamp, &body),
+        )
+        .send_body(body)
+        .await
+        .map_err(|err| {
+            warn!(
+                "Request of webhook delivery {} failed: {}",
+                delivery.id, err
+            );
+
+            match err {
+                SendRequestError::Timeout => "Request timed out",
+                SendRequestError::Connect(_) => "Could not connect to the endpoint",
+                _ => "Request failed",
+            }
+            .to_string()
+        })?;
+
+    Ok(response.status().as_u16())
+}
+
+/// What to record after an attempt, depending on its result.
+fn attempt(delivery: &PendingDelivery, result: Result<u16, String>) -> DeliveryAttempt {
+    let now = Utc::now();
+    let (response_status, error) = match result {
+        Ok(status) if (200..300).contains(&status) => {
+            return DeliveryAttempt {
+                status: DeliveryStatus::Delivered,
+                at: now,
+                response_status: Some(status),
+                error: None,
+                next_attempt_at: None,
+            }
+        }
+        Ok(status) => (Some(status), format!("Endpoint responded with {}", status)),
+        Err(error) => (None, error.chars().take(MAX_ERROR_LENGTH).collect()),
+    };
+
+    let attempts = delivery.attempts + 1;
+    let (status, next_attempt_at) = if attempts >= MAX_ATTEMPTS {
+        (DeliveryStatus::Failed, None)
+    } else {
+        let delay = chrono::Duration::seconds(RETRY_DELAY_SECS << (attempts - 1));
+        (DeliveryStatus::Pending, Some(now + delay))
+    };
+
+    DeliveryAttempt {
+        status,
+        at: now,
+        response_status,
+        error: Some(error),
+        next_attempt_at,
+    }
+}
+
+/// Leases a batch of pending deliveries and sends them one by one. Returns the number of
+/// leased deliveries.
+async fn deliver_due(
+    storage: &Storage,
+    config: &WebhookConfig,
+    http: &HttpClient,
+    owner: &str,
+) -> Result<usize, WebError> {
+    let now = Utc::now();
+    let lease_until = now + chrono::Duration::milliseconds(config.lease.as_millis() as i64);
+    let timeout = chrono::Duration::milliseconds(TIMEOUT.as_millis() as i64);
+
+    let leased = storage
+        .lease_webhook_deliveries(owner, now, lease_until, BATCH_SIZE)
+        .await?;
+    let count = leased.len();
+
+    for delivery in leased {
+        // Deliveries left over are leased again once the lease expires.
+        if Utc::now() + timeout >= lease_until {
+            warn!("Webhook lease is about to expire, postponing the rest of the batch");
+            break;
+        }
+
+        let attempt = attempt(&delivery, send(http, &delivery).await);
+
+        match attempt.status {
+            DeliveryStatus::Failed => error!(
+                "Giving up webhook delivery {}: {}",
+                delivery.id,
+                attempt.error.as_deref().unwrap_or_default()
+            ),
+            DeliveryStatus::Pending => warn!(
+                "Webhook delivery {} failed: {}",
+                delivery.id,
+                attempt.error.as_deref().unwrap_or_default()
+            ),
+            DeliveryStatus::Delivered => {}
+        }
+
+        if !storage
+            .record_delivery_attempt(delivery.id, owner, &attempt)
+            .await?
+        {
+            warn!(
+                "Lease of webhook delivery {} expired before it was processed",
+                delivery.id
+            );
+        }
+    }
+
+    Ok(count)
+}
+
+/// Delivers queued events to webhooks until the process exits.
+pub async fn run(storage: Storage, config: WebhookConfig, owner: String) {
+    // Redirects could lead to internal addresses, which are checked for the webhook URL only.
+    let http = HttpClient::build()
+        .timeout(TIMEOUT)
+        .disable_redirects()
+        .finish();
+    let mut pruned_at: Option<Instant> = None;
+    info!("Webhook worker started as {}", owner);
+
+    loop {
+        if pruned_at.map(|at| at.elapsed() >= PRUNE_INTERVAL) != Some(false) {
+            let before = Utc::now() - chrono::Duration::days(LOG_RETENTION_DAYS);
+
+            if let Err(err) = storage.prune_webhook_deliveries(before).await {
+                error!("Failed to prune webhook deliveries: {:?}", err);
+            }
+
+            pruned_at = Some(Instant::now());
+        }
+
+        match deliver_due(&storage, &config, &http, &owner).await {
+            // A full batch means there may be more deliveries waiting.
+            Ok(leased) if leased == BATCH_SIZE as usize => continue,
+            Ok(_) => {}
+            Err(err) => error!("Failed to lease webhook deliveries: {:?}", err),
+        }
+
+        delay_for(config.interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signs_timestamp_and_body() {
+        assert_eq!(
+            signature("secret", 1600000000, br#"{"id":1}"#),
+            "sha256=49847f6653f3434dc0d5563850815d91e18471282eeccadbf48380236b3ed25f"
+        );
+        assert_eq!(
+            signature("", 0, b""),
+            "sha256=b849d5a581847b281957065739df36df2463d1977ea8d6e1e4e6cf33fadc68c3"
+        );
+    }
+
+    #[test]
+    fn signature_depends_on_every_part() {
+        let signed = signature("secret", 1600000000, b"body");
+
+        assert_ne!(signed, signature("other", 1600000000, b"body"));
+        assert_ne!(signed, signature("secret", 1600000001, b"body"));
+        assert_ne!(signed, signature("secret", 1600000000, b"body "));
+    }
+}
@@ -1,33 +1,128 @@
-use crate::database::Pool;
-use crate::error::WebError;
+use crate::database::{ClientRepository, Storage};
+use crate::error::{AuthError, WebError};
+use crate::model::{ApiKey, ChangeFeed, Permissions, Scope};
 use actix_web::{dev, web, FromRequest, HttpRequest};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
 use futures::future::{ready, LocalBoxFuture};
 use futures::prelude::*;
 use log::warn;
-use sqlx::FromRow;
 
-#[derive(Debug, FromRow)]
+#[derive(Debug)]
 pub struct Client {
     id: i64,
     display_name: String,
+    /// Calendar of the client, used to tell which todos are due today.
+    time_zone: Tz,
+    /// Permissions of the API key used to authorize current request.
+    permissions: Permissions,
+    /// Expiry of the API key used to authorize current request, `None` for clients not
+    /// authorized by a key.
+    key_valid_to: Option<DateTime<Utc>>,
+    /// Open change streams, notified about changes made on behalf of the client.
+    changes: Option<ChangeFeed>,
 }
 
 impl Client {
+    /// Unknown time zone names fall back to UTC.
+    pub(crate) fn new(id: i64, display_name: String, time_zone: &str) -> Self {
+        let time_zone = time_zone.parse().unwrap_or_else(|_| {
+            warn!("Unknown time zone {} of client {}", time_zone, id);
+            Tz::UTC
+        });
+
+        Self {
+            id,
+            display_name,
+            time_zone,
+            permissions: Permissions::full(),
+            key_valid_to: None,
+            changes: None,
+        }
+    }
+
     pub fn id(&self) -> i64 {
         self.id
     }
 
-    pub async fn authorize(token: &str, pool: &Pool) -> Result<Option<Self>, WebError> {
-        let client_id: Option<(i64,)> = sqlx::query_as("SELECT client_id FROM client_api_keys WHERE key = ? AND valid_to > strftime('%s','now')").bind(token).fetch_optional(&*pool).await?;
+    pub fn display_name(&self) -> &str {
+        &self.display_name
+    }
+
+    pub fn time_zone(&self) -> &Tz {
+        &self.time_zone
+    }
+
+    pub fn permissions(&self) -> &Permissions {
+        &self.permissions
+    }
 
-        if let Some((id,)) = client_id {
-            sqlx::query_as!(Client, "SELECT * FROM clients WHERE id = ?", id)
-                .fetch_optional(&*pool)
-                .await
-                .map_err(|err| err.into())
+    pub fn key_valid_to(&self) -> Option<DateTime<Utc>> {
+        self.key_valid_to
+    }
+
+    pub fn changes(&self) -> Option<&ChangeFeed> {
+        self.changes.as_ref()
+    }
+
+    pub fn require(&self, scope: Scope) -> Result<(), WebError> {
+        if self.permissions.allows(scope) {
+            Ok(())
+        } else {
+            Err(WebError::Forbidden)
+        }
+    }
+
+    pub fn require_work_list(&self, scope: Scope, work_list_id: i64) -> Result<(), WebError> {
+        self.require(scope)?;
+
+        if self.permissions.allows_work_list(work_list_id) {
+            Ok(())
         } else {
-            Ok(None)
+            Err(WebError::Forbidden)
+        }
+    }
+
+    /// Resolves the client a token belongs to. Expired keys are reported separately from unknown
+    /// ones, so clients know to issue a new key.
+    pub async fn authorize(token: &str, storage: &Storage) -> Result<Self, WebError> {
+        let candidates = storage
+            .find_api_keys_by_prefix(ApiKey::token_prefix(token))
+            .await?;
+
+        // Every candidate is verified, so timing does not depend on which one matches.
+        let matching = candidates
+            .into_iter()
+            .fold(None, |matching, (api_key, key_hash)| {
+                if key_hash.verify(token) {
+                    Some(api_key)
+                } else {
+                    matching
+                }
+            });
+
+        let api_key = matching.ok_or(WebError::Unauthorized(AuthError::InvalidToken))?;
+        if api_key.is_expired() {
+            return Err(WebError::Unauthorized(AuthError::KeyExpired));
         }
+
+        // Usage is tracked on a best-effort basis, it never fails a request.
+        if api_key.needs_touch() {
+            if let Err(err) = storage.touch_api_key(api_key.id).await {
+                warn!("Failed to record use of API key {}: {:?}", api_key.id, err);
+            }
+        }
+
+        let client = storage
+            .find_client(api_key.client_id)
+            .await?
+            .ok_or(WebError::Unauthorized(AuthError::InvalidToken))?;
+
+        Ok(Self {
+            permissions: api_key.permissions,
+            key_valid_to: Some(api_key.valid_to),
+            ..client
+        })
     }
 }
 
@@ -37,44 +132,77 @@ impl FromRequest for Client {
     type Config = ();
 
     fn from_request(req: &HttpRequest, payload: &mut dev::Payload) -> Self::Future {
-        let pool = web::Data::<Pool>::from_request(req, payload)
+        let storage = web::Data::<Storage>::from_request(req, payload)
             .into_inner()
             .map_err(|err| {
-                warn!("Failed to obtain database pool: {:?}", err);
-                WebError::Unauthorized
+                warn!("Failed to obtain storage: {:?}", err);
+                WebError::Unauthorized(AuthError::InvalidToken)
             });
+        let changes = req
+            .app_data::<web::Data<ChangeFeed>>()
+            .map(|changes| changes.get_ref().clone());
 
         let token = req
             .headers()
             .get("Authorization")
-            .ok_or(WebError::Unauthorized)
-            .and_then(|header| header.to_str().map_err(|_| WebError::Unauthorized))
-            .map(|header| header.to_lowercase())
+            .ok_or(WebError::Unauthorized(AuthError::MissingToken))
             .and_then(|header| {
-                if header.starts_with("token") {
-                    Ok(header
-                        .trim_start_matches("token")
-                        .trim_start()
-                        .trim_end()
-                        .to_string())
-                } else {
-                    Err(WebError::Unauthorized)
-                }
+                header
+                    .to_str()
+                    .map_err(|_| WebError::Unauthorized(AuthError::InvalidToken))
+            })
+            .and_then(|header| {
+                token_from_header(header)
+                    .map(str::to_string)
+                    .ok_or(WebError::Unauthorized(AuthError::InvalidToken))
             });
 
-        if token.is_err() || pool.is_err() {
-            return ready(Err(WebError::Unauthorized)).boxed_local();
-        } else {
-            // SAFETY: Safe because we've checked for error case in conditional.
-            let pool = pool.unwrap();
-            let token = token.unwrap();
-            let fut = async move {
-                Self::authorize(&token, &pool)
-                    .await
-                    .and_then(|client| client.ok_or(WebError::Unauthorized))
-            };
-
-            fut.boxed_local()
+        match (token, storage) {
+            (Ok(token), Ok(storage)) => {
+                let fut = async move {
+                    let client = Self::authorize(&token, &storage).await?;
+                    Ok(Self { changes, ..client })
+                };
+
+                fut.boxed_local()
+            }
+            (Err(err), _) | (_, Err(err)) => ready(Err(err)).boxed_local(),
         }
     }
 }
+
+/// Extracts the token from `Authorization: Token <token>`. The scheme is case-insensitive, but
+/// the token itself is not.
+fn token_from_header(header: &str) -> Option<&str> {
+    let scheme = header.get(..6)?;
+    if !scheme.eq_ignore_ascii_case("token ") {
+        return None;
+    }
+
+    match header[6..].trim() {
+        "" => None,
+        token => Some(token),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::token_from_header;
+
+    #[test]
+    fn reads_token_scheme() {
+        assert_eq!(token_from_header("Token abc.DEF"), Some("abc.DEF"));
+        assert_eq!(token_from_header("token  abc.DEF "), Some("abc.DEF"));
+        assert_eq!(token_from_header("TOKEN abc.DEF"), Some("abc.DEF"));
+    }
+
+    #[test]
+    fn rejects_other_schemes() {
+        assert_eq!(token_from_header("Tokenabc.DEF"), None);
+        assert_eq!(token_from_header("Tokens abc.DEF"), None);
+        assert_eq!(token_from_header("Bearer abc.DEF"), None);
+        assert_eq!(token_from_header("Token "), None);
+        assert_eq!(token_from_header("Token"), None);
+        assert_eq!(token_from_header("abc.DEF"), None);
+    }
+}
@@ -0,0 +1,208 @@
+use actix_web::http::{header, Method};
+use actix_web::{dev, FromRequest, HttpRequest, HttpResponse};
+use futures::future::{ready, Ready};
+use serde::Serialize;
+use std::fmt::Display;
+
+use crate::error::WebError;
+
+/// Entity tags are built from versions of resources, which change with every write, so they
+/// are strong.
+pub fn entity_tag(value: impl Display) -> String {
+    format!("\"{}\"", value)
+}
+
+/// Whether the value of `If-Match` or `If-None-Match` header lists the tag. Weak tags match
+/// only in weak comparison, as used by `If-None-Match`.
+fn lists_tag(value: &str, etag: &str, weak: bool) -> bool {
+    value
+        .split(',')
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag == etag || (weak && tag.trim_start_matches("W/") == etag))
+}
+
+/// `If-Match` header of a request changing a resource. Requests without it are not checked.
+pub struct IfMatch(Option<String>);
+
+impl IfMatch {
+    /// Fails with 412 Precondition Failed if the resource has changed since the client fetched
+    /// it. Otherwise returns the version the resource has to stay at until it is written, `None`
+    /// if the client does not care about it.
+    pub fn check(&self, etag: &str, version: i64) -> Result<Option<i64>, WebError> {
+        match self.0.as_deref() {
+            Some(value) if !lists_tag(value, etag, false) => Err(WebError::PreconditionFailed),
+            Some(value) if value.trim() != "*" => Ok(Some(version)),
+            _ => Ok(None),
+        }
+    }
+}
+
+impl FromRequest for IfMatch {
+    type Error = WebError;
+    type Future = Ready<Result<Self, Self::Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _payload: &mut dev::Payload) -> Self::Future {
+        let value = match req.headers().get(header::IF_MATCH) {
+            Some(value) => match value.to_str() {
+                Ok(value) => Some(value.to_owned()),
+                Err(_) => {
+                    return ready(Err(WebError::BadRequest(
+                        "Invalid If-Match header".to_string(),
+                    )))
+                }
+            },
+            None => None,
+        };
+
+        ready(Ok(IfMatch(value)))
+    }
+}
+
+/// JSON response carrying the `ETag` header. `GET` requests with `If-None-Match` listing the
+/// tag get 304 Not Modified without a body instead.
+pub fn tagged_response<T: Serialize>(
+    req: &HttpRequest,
+    etag: &str,
+    body: &T,
+) -> Result<HttpResponse, WebError> {
+    let not_modified = (*req.method() == Method::GET || *req.method() == Method::HEAD)
+        && req
+            .headers()
+            .get(header::IF_NONE_MATCH)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| lists_tag(value, etag, true))
+            .unwrap_or(false);
+
+    if not_modified {
+        return Ok(HttpResponse::NotModified()
+            .header(header::ETAG, etag)
+            .finish());
+    }
+
+    let body = serde_json::to_string(body)
+        .map_err(|err| WebError::Internal(format!("Failed to serialize response: {}", err)))?;
+
+    Ok(HttpResponse::Ok()
+        .header(header::ETAG, etag)
+        .content_type("application/json")
+        .body(body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::http::{HeaderValue, StatusCode};
+    use actix_web::test::TestRequest;
+
+    const ETAG: &str = "\"7\"";
+
+    fn if_match(value: &str) -> IfMatch {
+        IfMatch(Some(value.to_string()))
+    }
+
+    #[test]
+    fn lists_tags() {
+        assert!(lists_tag("*", ETAG, false));
+        assert!(lists_tag(" * ", ETAG, false));
+        assert!(lists_tag("\"7\"", ETAG, false));
+        assert!(lists_tag("\"5\", \"7\"", ETAG, false));
+        assert!(lists_tag("\"5\",\"7\",\"9\"", ETAG, false));
+        assert!(!lists_tag("\"5\", \"6\"", ETAG, false));
+        assert!(!lists_tag("\"77\"", ETAG, false));
+    }
+
+    #[test]
+    fn weak_tags_match_only_in_weak_comparison() {
+        assert!(!lists_tag("W/\"7\"", ETAG, false));
+        assert!(lists_tag("W/\"7\"", ETAG, true));
+        assert!(lists_tag("\"5\", W/\"7\"", ETAG, true));
+    }
+
+    #[test]
+    fn malformed_tags_do_not_match() {
+        assert!(!lists_tag("", ETAG, true));
+        assert!(!lists_tag("7", ETAG, true));
+        assert!(!lists_tag("\"7", ETAG, true));
+        assert!(!lists_tag("w/\"7\"", ETAG, true));
+        assert!(!lists_tag(",,", ETAG, true));
+    }
+
+    #[test]
+    fn checks_if_match() {
+        assert!(matches!(IfMatch(None).check(ETAG, 7), Ok(None)));
+        assert!(matches!(if_match("*").check(ETAG, 7), Ok(None)));
+        assert!(matches!(if_match("\"7\"").check(ETAG, 7), Ok(Some(7))));
+        assert!(matches!(
+            if_match("\"5\", \"7\"").check(ETAG, 7),
+            Ok(Some(7))
+        ));
+        assert!(matches!(
+            if_match("\"5\"").check(ETAG, 7),
+            Err(WebError::PreconditionFailed)
+        ));
+        assert!(matches!(
+            if_match("W/\"7\"").check(ETAG, 7),
+            Err(WebError::PreconditionFailed)
+        ));
+        assert!(matches!(
+            if_match("7").check(ETAG, 7),
+            Err(WebError::PreconditionFailed)
+        ));
+    }
+
+    #[actix_rt::test]
+    async fn rejects_invalid_if_match_header() {
+        let req = TestRequest::default()
+            .header(
+                header::IF_MATCH,
+                HeaderValue::from_bytes(b"\"\xfa\"").unwrap(),
+            )
+            .to_http_request();
+        let if_match = IfMatch::from_request(&req, &mut dev::Payload::None).await;
+        assert!(matches!(if_match, Err(WebError::BadRequest(_))));
+
+        let req = TestRequest::default().to_http_request();
+        let if_match = IfMatch::from_request(&req, &mut dev::Payload::None).await;
+        assert!(matches!(if_match, Ok(IfMatch(None))));
+    }
+
+    fn respond(method: Method, if_none_match: Option<&str>) -> HttpResponse {
+        let mut req = TestRequest::default().method(method);
+
+        if let Some(value) = if_none_match {
+            req = req.header(header::IF_NONE_MATCH, value);
+        }
+
+        tagged_response(&req.to_http_request(), ETAG, &"body").unwrap()
+    }
+
+    #[test]
+    fn tags_responses() {
+        let response = respond(Method::GET, None);
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers().get(header::ETAG).unwrap(), ETAG);
+
+        let response = respond(Method::GET, Some("\"5\""));
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn answers_not_modified_to_reads() {
+        for method in &[Method::GET, Method::HEAD] {
+            for value in &["\"7\"", "W/\"7\"", "\"5\", \"7\"", "*"] {
+                let response = respond(method.clone(), Some(value));
+                assert_eq!(response.status(), StatusCode::NOT_MODIFIED, "{}", value);
+                assert_eq!(response.headers().get(header::ETAG).unwrap(), ETAG);
+            }
+        }
+    }
+
+    #[test]
+    fn ignores_if_none_match_on_writes() {
+        for method in &[Method::POST, Method::PUT, Method::PATCH] {
+            let response = respond(method.clone(), Some(ETAG));
+            assert_eq!(response.status(), StatusCode::OK);
+        }
+    }
+}
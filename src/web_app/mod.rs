@@ -1,5 +1,11 @@
 mod client;
+mod etag;
+mod problem;
 mod validated_json;
+mod validated_query;
 
 pub use client::Client;
+pub use etag::{entity_tag, tagged_response, IfMatch};
+pub use problem::{not_found, path_config, problem_instance, query_config};
 pub use validated_json::ValidatedJson;
+pub use validated_query::ValidatedQuery;
@@ -0,0 +1,52 @@
+use crate::error::{Resource, WebError};
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse};
+use actix_web::{web, Error, HttpResponse};
+use futures::prelude::*;
+
+/// Middleware filling `instance` of problem responses with the request path.
+///
+/// `ResponseError` has no access to the request, so responses built from `WebError` are rebuilt
+/// here once the path is known. Use with `App::wrap_fn`.
+pub fn problem_instance<S>(
+    req: ServiceRequest,
+    srv: &mut S,
+) -> impl Future<Output = Result<ServiceResponse, Error>>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse, Error = Error>,
+{
+    let instance = req.path().to_string();
+
+    srv.call(req).map_ok(move |res| {
+        let problem = res
+            .response()
+            .error()
+            .and_then(|err| err.as_error::<WebError>())
+            .map(|err| err.problem_response(Some(&instance)));
+
+        match problem {
+            Some(response) => {
+                let (req, _) = res.into_parts();
+                ServiceResponse::new(req, response)
+            }
+            None => res,
+        }
+    })
+}
+
+/// Reports path segments which can't be parsed, e.g. a non-numeric ID, as problems. Register with
+/// `App::app_data`.
+pub fn path_config() -> web::PathConfig {
+    web::PathConfig::default()
+        .error_handler(|err, _| WebError::BadRequest(format!("Invalid path: {}", err)).into())
+}
+
+/// Reports query strings which can't be parsed as problems. Register with `App::app_data`.
+pub fn query_config() -> web::QueryConfig {
+    web::QueryConfig::default()
+        .error_handler(|err, _| WebError::BadRequest(format!("Invalid query: {}", err)).into())
+}
+
+/// Answers requests no route matches. Use with `App::default_service`.
+pub async fn not_found() -> Result<HttpResponse, WebError> {
+    Err(WebError::NotFound(Resource::Unknown))
+}
@@ -30,7 +30,7 @@ impl<T: Validate + DeserializeOwned + 'static> FromRequest for ValidatedJson<T>
                         Ok(_) => Ok(Self(inner)),
                     }
                 }
-                Err(aerr) => Err(WebError::ActixError(aerr)),
+                Err(aerr) => Err(WebError::BadRequest(aerr.to_string())),
             })
             .boxed_local()
     }
@@ -0,0 +1,36 @@
+use crate::error::WebError;
+use actix_web::{dev, web, FromRequest, HttpRequest};
+use futures::future::{ready, Ready};
+
+use serde::de::DeserializeOwned;
+use validator::Validate;
+
+pub struct ValidatedQuery<T>(T);
+
+impl<T> ValidatedQuery<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T: Validate + DeserializeOwned + 'static> FromRequest for ValidatedQuery<T> {
+    type Error = WebError;
+    type Config = ();
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut dev::Payload) -> Self::Future {
+        let result = match web::Query::<T>::from_query(req.query_string()) {
+            Ok(query) => {
+                let inner = query.into_inner();
+
+                match inner.validate() {
+                    Err(verr) => Err(WebError::ValidationError(verr)),
+                    Ok(_) => Ok(Self(inner)),
+                }
+            }
+            Err(qerr) => Err(WebError::BadRequest(qerr.to_string())),
+        };
+
+        ready(result)
+    }
+}
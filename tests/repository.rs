@@ -0,0 +1,707 @@
+//! Scenarios run against every storage backend through `Storage`, so that the backends keep
+//! behaving the same. Postgres is tested only when `TEST_POSTGRES_URL` is set.
+
+use std::env;
+use std::fs::{self, File};
+
+use chrono::{DateTime, Duration, Utc};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+use todo_list_api::database::{
+    self, migrations, ClientRepository, EventRepository, PoolConfig, ReminderRepository, Storage,
+    TodoRepository, WebhookRepository,
+};
+use todo_list_api::error::WebError;
+use todo_list_api::forms::search::Search;
+use todo_list_api::forms::sync::{PushMutations, SyncQuery};
+use todo_list_api::forms::todo::{MoveTodo, UpdateTodo};
+use todo_list_api::model::{
+    ApiKey, DeliveryAttempt, DeliveryStatus, Due, EventKind, MutationResult, Permissions, Reminder,
+    SearchResult, SyncChanges, SyncItem, Tag, Todo, TombstoneKind, Webhook, WorkList,
+};
+use todo_list_api::web_app::Client;
+
+fn form<T: DeserializeOwned>(value: Value) -> T {
+    serde_json::from_value(value).expect("invalid form")
+}
+
+async fn connect(url: &str) -> Storage {
+    let config = PoolConfig::from_url(url).expect("invalid database URL");
+    let storage = database::connect(config).await.expect("failed to connect");
+    migrations::run(&storage).await.expect("failed to migrate");
+    storage
+}
+
+/// Creates a client with a key granting everything, authorized the way requests are.
+async fn authorized_client(storage: &Storage, name: &str) -> (Client, String) {
+    let client = storage.create_client(name).await.unwrap();
+    let issued = ApiKey::issue(
+        client.id(),
+        Some("tests"),
+        Utc::now() + Duration::days(1),
+        &Permissions::full(),
+        storage,
+    )
+    .await
+    .unwrap();
+    let client = Client::authorize(&issued.token, storage).await.unwrap();
+
+    (client, issued.token)
+}
+
+async fn run_scenarios(storage: Storage) {
+    clients(&storage).await;
+    work_lists(&storage).await;
+    todos(&storage).await;
+    sync(&storage).await;
+    search(&storage).await;
+    tags(&storage).await;
+    reminders(&storage).await;
+    recurrence(&storage).await;
+    webhooks(&storage).await;
+}
+
+async fn clients(storage: &Storage) {
+    let (client, token) = authorized_client(storage, "Clients").await;
+    assert_eq!(client.display_name(), "Clients");
+
+    let wrong = Client::authorize(&ApiKey::generate_token(), storage).await;
+    assert!(matches!(wrong, Err(WebError::Unauthorized(_))));
+
+    assert!(storage.rename_client(client.id(), "Renamed").await.unwrap());
+    assert!(storage
+        .set_client_time_zone(client.id(), "Europe/Prague")
+        .await
+        .unwrap());
+    let found = storage.find_client(client.id()).await.unwrap().unwrap();
+    assert_eq!(found.display_name(), "Renamed");
+    assert_eq!(found.time_zone().name(), "Europe/Prague");
+
+    let api_keys = ApiKey::list(&client, storage).await.unwrap();
+    assert_eq!(api_keys.len(), 1);
+    let api_key = api_keys.into_iter().next().unwrap();
+    api_key.revoke(&client, storage).await.unwrap();
+    let revoked = Client::authorize(&token, storage).await;
+    assert!(matches!(revoked, Err(WebError::Unauthorized(_))));
+
+    assert!(storage.delete_client(client.id()).await.unwrap());
+    assert!(storage.find_client(client.id()).await.unwrap().is_none());
+}
+
+async fn work_lists(storage: &Storage) {
+    let (client, _) = authorized_client(storage, "Work lists").await;
+    let (other, _) = authorized_client(storage, "Other").await;
+
+    let created = WorkList::create(form(json!({ "name": "Groceries" })), None, &client, storage)
+        .await
+        .unwrap();
+    let mut work_list = WorkList::find(created.id(), &client, storage)
+        .await
+        .unwrap();
+    assert_eq!(work_list.version(), created.version());
+
+    let not_owned = WorkList::find(created.id(), &other, storage).await;
+    assert!(matches!(not_owned, Err(WebError::NotFound(_))));
+
+    let version = work_list.version();
+    work_list
+        .update(
+            &client,
+            form(json!({ "name": "Shopping" })),
+            Some(version),
+            storage,
+        )
+        .await
+        .unwrap();
+    assert!(work_list.version() > version);
+
+    let mut stale = WorkList::find(created.id(), &client, storage)
+        .await
+        .unwrap();
+    let stale_update = stale
+        .update(
+            &client,
+            form(json!({ "name": "Stale" })),
+            Some(version),
+            storage,
+        )
+        .await;
+    assert!(matches!(stale_update, Err(WebError::PreconditionFailed)));
+
+    let found = WorkList::find(created.id(), &client, storage)
+        .await
+        .unwrap();
+    assert_eq!(
+        serde_json::to_value(&found).unwrap()["name"],
+        json!("Shopping")
+    );
+
+    let stale_delete = found.clone().delete(Some(version), &client, storage).await;
+    assert!(matches!(stale_delete, Err(WebError::PreconditionFailed)));
+    let current = found.version();
+    found.delete(Some(current), &client, storage).await.unwrap();
+    let deleted = WorkList::find(created.id(), &client, storage).await;
+    assert!(matches!(deleted, Err(WebError::NotFound(_))));
+
+    let events = storage.list_events(client.id(), 0, 100).await.unwrap();
+    let kinds: Vec<EventKind> = events.iter().map(|event| event.kind).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            EventKind::WorkListCreated,
+            EventKind::WorkListUpdated,
+            EventKind::WorkListDeleted,
+        ]
+    );
+
+    storage.delete_client(client.id()).await.unwrap();
+    storage.delete_client(other.id()).await.unwrap();
+}
+
+async fn todos(storage: &Storage) {
+    let (client, _) = authorized_client(storage, "Todos").await;
+    let work_list = WorkList::create(form(json!({ "name": "Chores" })), None, &client, storage)
+        .await
+        .unwrap();
+    let create = |content: &str| {
+        form(json!({
+            "content": content,
+            "work_list_id": work_list.id(),
+            "tags": ["home"],
+        }))
+    };
+
+    let first = Todo::create(create("Vacuum"), None, &client, storage)
+        .await
+        .unwrap();
+    let mut second = Todo::create(create("Dishes"), None, &client, storage)
+        .await
+        .unwrap();
+    assert!(first.position < second.position);
+    assert_eq!(second.tags, vec!["home".to_string()]);
+
+    let both: MoveTodo = form(json!({ "before": first.id, "after": first.id }));
+    let ambiguous = second.reorder(both, &client, storage).await;
+    assert!(matches!(ambiguous, Err(WebError::BadRequest(_))));
+    second
+        .reorder(form(json!({ "before": first.id })), &client, storage)
+        .await
+        .unwrap();
+    let first = Todo::find(first.id, &client, storage).await.unwrap();
+    let mut second = Todo::find(second.id, &client, storage).await.unwrap();
+    assert!(second.position < first.position);
+
+    let version = second.version;
+    let update: UpdateTodo = form(json!({ "content": "Wash dishes", "completed": true }));
+    second
+        .update(update, Some(version), &client, storage)
+        .await
+        .unwrap();
+    assert!(second.completed);
+    assert!(second.version > version);
+
+    let mut stale = Todo::find(second.id, &client, storage).await.unwrap();
+    let stale_update = stale
+        .update(
+            form(json!({ "content": "Stale" })),
+            Some(version),
+            &client,
+            storage,
+        )
+        .await;
+    assert!(matches!(stale_update, Err(WebError::PreconditionFailed)));
+    let found = Todo::find(second.id, &client, storage).await.unwrap();
+    assert_eq!(found.content, "Wash dishes");
+
+    let subtask = Todo::create(
+        form(json!({
+            "content": "Empty the bin",
+            "work_list_id": work_list.id(),
+            "parent_id": first.id,
+        })),
+        None,
+        &client,
+        storage,
+    )
+    .await
+    .unwrap();
+    let first = Todo::find(first.id, &client, storage).await.unwrap();
+    let version = first.version;
+    first.delete(Some(version), &client, storage).await.unwrap();
+    let deleted = Todo::find(subtask.id, &client, storage).await;
+    assert!(matches!(deleted, Err(WebError::NotFound(_))));
+
+    let events = storage.list_events(client.id(), 0, 100).await.unwrap();
+    let kinds: Vec<EventKind> = events.iter().map(|event| event.kind).collect();
+    assert_eq!(kinds.first(), Some(&EventKind::WorkListCreated));
+    assert_eq!(kinds.last(), Some(&EventKind::TodoDeleted));
+    assert!(kinds.contains(&EventKind::TodoCompleted));
+    assert!(events.windows(2).all(|pair| pair[0].id < pair[1].id));
+
+    storage.delete_client(client.id()).await.unwrap();
+}
+
+async fn sync(storage: &Storage) {
+    let (client, _) = authorized_client(storage, "Sync").await;
+    let work_list = WorkList::create(form(json!({ "name": "Errands" })), None, &client, storage)
+        .await
+        .unwrap();
+
+    let push: PushMutations = form(json!({
+        "mutations": [{
+            "op": "create_todo",
+            "mutation_id": "create-post",
+            "data": { "content": "Post a letter", "work_list_id": work_list.id() },
+        }],
+    }));
+    let pushed_again: PushMutations = form(json!({
+        "mutations": [{
+            "op": "create_todo",
+            "mutation_id": "create-post",
+            "data": { "content": "Post a letter", "work_list_id": work_list.id() },
+        }],
+    }));
+    let created_id = |results: Vec<MutationResult>| match results.into_iter().next() {
+        Some(MutationResult::Applied {
+            item: Some(SyncItem::Todo(todo)),
+        }) => todo.id,
+        result => panic!("unexpected result: {:?}", result),
+    };
+    let id = created_id(MutationResult::push(push, &client, storage).await.unwrap());
+    let again = created_id(
+        MutationResult::push(pushed_again, &client, storage)
+            .await
+            .unwrap(),
+    );
+    assert_eq!(id, again);
+
+    let changes = SyncChanges::since(form(json!({})), &client, storage)
+        .await
+        .unwrap();
+    assert_eq!(changes.work_lists.len(), 1);
+    assert_eq!(changes.todos.len(), 1);
+    assert!(!changes.has_more);
+
+    let todo = Todo::find(id, &client, storage).await.unwrap();
+    todo.delete(None, &client, storage).await.unwrap();
+    let query: SyncQuery = form(json!({ "since": changes.token }));
+    let changes = SyncChanges::since(query, &client, storage).await.unwrap();
+    assert!(changes.todos.is_empty());
+    assert_eq!(changes.deleted.len(), 1);
+    assert_eq!(changes.deleted[0].kind, TombstoneKind::Todo);
+    assert_eq!(changes.deleted[0].id, id);
+
+    storage.delete_client(client.id()).await.unwrap();
+}
+
+async fn search(storage: &Storage) {
+    let (client, _) = authorized_client(storage, "Search").await;
+    let work_list = WorkList::create(form(json!({ "name": "Dinner" })), None, &client, storage)
+        .await
+        .unwrap();
+    Todo::create(
+        form(json!({ "content": "Fish & chips", "work_list_id": work_list.id() })),
+        None,
+        &client,
+        storage,
+    )
+    .await
+    .unwrap();
+
+    let query: Search = form(json!({ "q": "chips", "kind": "todo" }));
+    let results = SearchResult::search(query, &client, storage).await.unwrap();
+    assert_eq!(results.len(), 1);
+    let snippet = &results[0].snippet;
+    assert!(snippet.contains("&amp;"), "unescaped snippet: {}", snippet);
+    assert!(
+        snippet.contains("<mark>chips</mark>"),
+        "snippet: {}",
+        snippet
+    );
+
+    storage.delete_client(client.id()).await.unwrap();
+}
+
+async fn tags(storage: &Storage) {
+    let (client, _) = authorized_client(storage, "Tags").await;
+    let work_list = WorkList::create(form(json!({ "name": "Desk" })), None, &client, storage)
+        .await
+        .unwrap();
+
+    let mut work = Tag::create(
+        form(json!({ "name": "work", "color": "#ff0000" })),
+        &client,
+        storage,
+    )
+    .await
+    .unwrap();
+    let taken = Tag::create(form(json!({ "name": "work" })), &client, storage).await;
+    assert!(matches!(taken, Err(WebError::Conflict)));
+
+    let todo = Todo::create(
+        form(json!({
+            "content": "File taxes",
+            "work_list_id": work_list.id(),
+            "tags": ["work", "home"],
+        })),
+        None,
+        &client,
+        storage,
+    )
+    .await
+    .unwrap();
+    assert_eq!(todo.tags, vec!["home".to_string(), "work".to_string()]);
+
+    let tags = Tag::list(&client, storage).await.unwrap();
+    let names: Vec<&str> = tags.iter().map(|tag| tag.name.as_str()).collect();
+    assert_eq!(names, vec!["home", "work"]);
+    assert!(tags.iter().all(|tag| tag.todos_count == Some(1)));
+
+    work.update(
+        form(json!({ "name": "office", "color": null })),
+        &client,
+        storage,
+    )
+    .await
+    .unwrap();
+    let renamed = Tag::find(work.id, &client, storage).await.unwrap();
+    assert_eq!(renamed.name, "office");
+    assert_eq!(renamed.color, None);
+
+    let home = tags.into_iter().find(|tag| tag.name == "home").unwrap();
+    let home_id = home.id;
+    home.delete(&client, storage).await.unwrap();
+    let deleted = Tag::find(home_id, &client, storage).await;
+    assert!(matches!(deleted, Err(WebError::NotFound(_))));
+
+    let todo = Todo::find(todo.id, &client, storage).await.unwrap();
+    assert_eq!(todo.tags, vec!["office".to_string()]);
+
+    storage.delete_client(client.id()).await.unwrap();
+}
+
+async fn reminders(storage: &Storage) {
+    let (client, _) = authorized_client(storage, "Reminders").await;
+    let work_list = WorkList::create(form(json!({ "name": "Plants" })), None, &client, storage)
+        .await
+        .unwrap();
+    let create = |content: &str| {
+        form(json!({
+            "content": content,
+            "work_list_id": work_list.id(),
+            "due_at": "2020-01-01",
+        }))
+    };
+    let todo = Todo::create(create("Water the ficus"), None, &client, storage)
+        .await
+        .unwrap();
+    let mut done = Todo::create(create("Water the cactus"), None, &client, storage)
+        .await
+        .unwrap();
+
+    let remind = |remind_at: &str| form(json!({ "remind_at": remind_at }));
+    let reminder = Reminder::create(&todo, remind("2020-01-01T08:00:00Z"), storage)
+        .await
+        .unwrap();
+    let later = Reminder::create(&todo, remind("2020-01-01T09:00:00Z"), storage)
+        .await
+        .unwrap();
+    Reminder::create(&done, remind("2020-01-01T07:00:00Z"), storage)
+        .await
+        .unwrap();
+    Reminder::delete(&todo, later.id, storage).await.unwrap();
+    let deleted = Reminder::delete(&todo, later.id, storage).await;
+    assert!(matches!(deleted, Err(WebError::NotFound(_))));
+    done.update(form(json!({ "completed": true })), None, &client, storage)
+        .await
+        .unwrap();
+
+    // Reminders of other scenarios never share the client, so they are filtered out by it.
+    let client_id = client.id();
+    let lease = |owner: &'static str, now: DateTime<Utc>| async move {
+        storage
+            .lease_due_reminders(owner, now, now + Duration::minutes(1), 100)
+            .await
+            .unwrap()
+            .into_iter()
+            .filter(|(_, owner_id)| *owner_id == client_id)
+            .map(|(reminder, _)| reminder.id)
+            .collect::<Vec<i64>>()
+    };
+
+    // Reminders of completed todos are not delivered.
+    let now = Utc::now();
+    assert_eq!(lease("first", now).await, vec![reminder.id]);
+    assert!(lease("second", now).await.is_empty());
+
+    // An expired lease can be taken over, the original owner can no longer finish it.
+    let expired = now + Duration::minutes(2);
+    assert_eq!(lease("second", expired).await, vec![reminder.id]);
+    let finished = storage
+        .finish_reminder(reminder.id, "first", true, expired)
+        .await
+        .unwrap();
+    assert!(!finished);
+
+    let retry_at = now + Duration::hours(1);
+    assert!(storage
+        .release_reminder(reminder.id, "second", retry_at)
+        .await
+        .unwrap());
+    assert!(lease("first", now + Duration::minutes(3)).await.is_empty());
+
+    let retried = retry_at + Duration::minutes(1);
+    assert_eq!(lease("first", retried).await, vec![reminder.id]);
+    assert!(storage
+        .finish_reminder(reminder.id, "first", true, retried)
+        .await
+        .unwrap());
+    assert!(lease("first", retried + Duration::hours(1))
+        .await
+        .is_empty());
+
+    let reminders = Reminder::list(&todo, storage).await.unwrap();
+    assert_eq!(reminders.len(), 1);
+    assert!(reminders[0].sent_at.is_some());
+    assert_eq!(reminders[0].attempts, 1);
+
+    storage.delete_client(client.id()).await.unwrap();
+}
+
+async fn recurrence(storage: &Storage) {
+    let (client, _) = authorized_client(storage, "Recurrence").await;
+    let work_list = WorkList::create(form(json!({ "name": "Garden" })), None, &client, storage)
+        .await
+        .unwrap();
+
+    let mut first = Todo::create(
+        form(json!({
+            "content": "Feed the fish",
+            "work_list_id": work_list.id(),
+            "due_at": "2020-01-01",
+            "recurrence": "FREQ=DAILY;COUNT=2",
+            "tags": ["pets"],
+        })),
+        None,
+        &client,
+        storage,
+    )
+    .await
+    .unwrap();
+    let subtask = Todo::create(
+        form(json!({
+            "content": "Buy flakes",
+            "work_list_id": work_list.id(),
+            "parent_id": first.id,
+        })),
+        None,
+        &client,
+        storage,
+    )
+    .await
+    .unwrap();
+    Reminder::create(
+        &first,
+        form(json!({ "remind_at": "2020-01-01T08:00:00Z" })),
+        storage,
+    )
+    .await
+    .unwrap();
+    let last_event_id = storage.last_event_id(client.id()).await.unwrap().unwrap();
+
+    // Completing the todo completes its subtask and creates the next occurrence, all at once.
+    let complete: UpdateTodo = form(json!({ "completed": true, "complete_subtasks": true }));
+    first
+        .update(complete, None, &client, storage)
+        .await
+        .unwrap();
+    assert!(first.completed);
+    assert_eq!(first.recurrence, None);
+    assert!(
+        Todo::find(subtask.id, &client, storage)
+            .await
+            .unwrap()
+            .completed
+    );
+
+    let events = storage
+        .list_events(client.id(), last_event_id, 100)
+        .await
+        .unwrap();
+    let kinds: Vec<EventKind> = events.iter().map(|event| event.kind).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            EventKind::TodoCompleted,
+            EventKind::TodoCompleted,
+            EventKind::TodoCreated,
+        ]
+    );
+
+    let todos = storage
+        .todos_in_work_lists(&[work_list.id()], None)
+        .await
+        .unwrap();
+    assert_eq!(todos.len(), 3);
+    let next_id = todos
+        .iter()
+        .find(|todo| todo.occurrence == 2)
+        .map(|todo| todo.id)
+        .unwrap();
+    let mut next = Todo::find(next_id, &client, storage).await.unwrap();
+    assert_eq!(next.series_id, Some(first.id));
+    assert_eq!(next.due_at, Some(Due::On("2020-01-02".parse().unwrap())));
+    assert!(next.recurrence.is_some());
+    assert!(!next.completed);
+    assert_eq!(next.tags, vec!["pets".to_string()]);
+    assert!(todos.iter().all(|todo| todo.position <= next.position));
+
+    let reminders = Reminder::list(&next, storage).await.unwrap();
+    assert_eq!(reminders.len(), 1);
+    assert_eq!(
+        reminders[0].remind_at,
+        "2020-01-02T08:00:00Z".parse::<DateTime<Utc>>().unwrap()
+    );
+
+    // The series ends with its second occurrence.
+    let complete: UpdateTodo = form(json!({ "completed": true }));
+    next.update(complete, None, &client, storage).await.unwrap();
+    let todos = storage
+        .todos_in_work_lists(&[work_list.id()], None)
+        .await
+        .unwrap();
+    assert_eq!(todos.len(), 3);
+
+    storage.delete_client(client.id()).await.unwrap();
+}
+
+async fn webhooks(storage: &Storage) {
+    let (client, _) = authorized_client(storage, "Webhooks").await;
+    let work_list = WorkList::create(form(json!({ "name": "Hooks" })), None, &client, storage)
+        .await
+        .unwrap();
+
+    // Webhooks are created directly in storage, as the model resolves their URLs.
+    let mut created = vec![];
+    for url in &["https://example.com/hooks", "https://example.com/more"] {
+        let webhook = storage
+            .create_webhook(
+                client.id(),
+                url,
+                "a sufficiently long secret",
+                Some(&[EventKind::TodoCreated]),
+                Utc::now(),
+                1,
+            )
+            .await
+            .unwrap();
+        created.push(webhook);
+    }
+    let mut webhook = created.remove(0).expect("webhook not created");
+    assert!(created[0].is_none(), "webhook over the limit created");
+
+    // Only events the webhook is subscribed to are delivered.
+    let mut todo = Todo::create(
+        form(json!({ "content": "Ring the bell", "work_list_id": work_list.id() })),
+        None,
+        &client,
+        storage,
+    )
+    .await
+    .unwrap();
+    todo.update(
+        form(json!({ "content": "Ring twice" })),
+        None,
+        &client,
+        storage,
+    )
+    .await
+    .unwrap();
+
+    let deliveries = webhook.deliveries(form(json!({})), storage).await.unwrap();
+    assert_eq!(deliveries.len(), 1);
+    assert_eq!(deliveries[0].event, EventKind::TodoCreated);
+    assert_eq!(deliveries[0].status, DeliveryStatus::Pending);
+    let delivery_id = deliveries[0].id;
+
+    let lease = |owner: &'static str, now: DateTime<Utc>| async move {
+        storage
+            .lease_webhook_deliveries(owner, now, now + Duration::minutes(1), 100)
+            .await
+            .unwrap()
+            .into_iter()
+            .filter(|delivery| delivery.id == delivery_id)
+            .collect::<Vec<_>>()
+    };
+
+    let now = Utc::now() + Duration::seconds(1);
+    let leased = lease("first", now).await;
+    assert_eq!(leased.len(), 1);
+    assert_eq!(leased[0].event.kind, EventKind::TodoCreated);
+    assert_eq!(leased[0].url, "https://example.com/hooks");
+    assert!(lease("second", now).await.is_empty());
+
+    let attempt = DeliveryAttempt {
+        status: DeliveryStatus::Delivered,
+        at: now,
+        response_status: Some(204),
+        error: None,
+        next_attempt_at: None,
+    };
+    let recorded = storage
+        .record_delivery_attempt(delivery_id, "second", &attempt)
+        .await
+        .unwrap();
+    assert!(!recorded);
+    assert!(storage
+        .record_delivery_attempt(delivery_id, "first", &attempt)
+        .await
+        .unwrap());
+    assert!(lease("first", now + Duration::hours(1)).await.is_empty());
+
+    let deliveries = webhook.deliveries(form(json!({})), storage).await.unwrap();
+    assert_eq!(deliveries[0].status, DeliveryStatus::Delivered);
+    assert_eq!(deliveries[0].attempts, 1);
+    assert_eq!(deliveries[0].response_status, Some(204));
+
+    webhook
+        .update(form(json!({ "events": null })), &client, storage)
+        .await
+        .unwrap();
+    let found = Webhook::find(webhook.id, &client, storage).await.unwrap();
+    assert_eq!(found.events, None);
+
+    found.delete(&client, storage).await.unwrap();
+    let deleted = Webhook::find(webhook.id, &client, storage).await;
+    assert!(matches!(deleted, Err(WebError::NotFound(_))));
+
+    storage.delete_client(client.id()).await.unwrap();
+}
+
+#[actix_rt::test]
+async fn memory() {
+    run_scenarios(connect("memory://").await).await;
+}
+
+#[actix_rt::test]
+async fn sqlite() {
+    let path = env::temp_dir().join(format!("todo-api-test-{}.db", std::process::id()));
+    File::create(&path).expect("failed to create database file");
+
+    run_scenarios(connect(&format!("sqlite://{}", path.display())).await).await;
+    fs::remove_file(&path).ok();
+}
+
+#[actix_rt::test]
+async fn postgres() {
+    let url = match env::var("TEST_POSTGRES_URL") {
+        Ok(url) => url,
+        Err(_) => {
+            eprintln!("TEST_POSTGRES_URL not set, skipping Postgres");
+            return;
+        }
+    };
+
+    run_scenarios(connect(&url).await).await;
+}